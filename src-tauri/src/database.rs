@@ -1,39 +1,308 @@
-use rusqlite::{Connection, params, Result as SqlResult};
-use chrono::{DateTime, Utc, Local};
-use std::path::PathBuf;
+use rusqlite::{Connection, DatabaseName, OpenFlags, OptionalExtension, params, params_from_iter, Result as SqlResult};
+use rusqlite::types::Value;
+use chrono::{DateTime, Utc, Local, Datelike, Timelike, NaiveDate, NaiveDateTime, NaiveTime};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use log::{info, error};
+use sha2::{Digest, Sha256};
 
-use crate::models::{GameSession, BudgetStatus, LearningActivity, AppSettings};
+use crate::encryption;
+use crate::game_monitor::{GameMonitor, UNCONFIRMED_NAME_PREFIX, normalize_process_name};
+use crate::models::{DEFAULT_EARN_RATIO, GameMatchKind, Profile, WeeklyReport, ReportDay, ReportGame, TimeRequest, SessionFilter, SessionPage, EnforcementEntry, EnforcementReason, LearningCredit, LearningPlan, UsageStats, GameLimit, CurfewWindow, GameSession, SessionEndReason, EarnUseStats, HistogramBin, GameTotal, TimeseriesPoint, PersonalBests, AdherenceStats, AchievementBonus, AchievementGrant, BudgetStatus, LearningActivity, AppSettings, Grant, GameConfig, GameGoal, GameGoalProgress, ConfigWarning, RecurringGrant, PendingGameName, HistoryImportSummary};
 
 pub struct Database {
     conn: Connection,
+    data_dir: PathBuf,
+    profile_id: String, // Active profile; usage and budget queries only see its rows
+    encrypted: bool, // Opened with the key from the OS keyring
 }
 
+const DB_FILE_NAME: &str = "gaming_tracker.db";
+
+// Profile that existing data was moved into when profiles were added
+pub const PRIMARY_PROFILE_ID: &str = "primary";
+
+// Overrides where data is kept, e.g. for a portable install
+pub const DATA_DIR_ENV: &str = "GTT_DATA_DIR";
+
+// One step of the schema upgrade
+type Migration = fn(&Database) -> SqlResult<()>;
+
+// Schema migrations in order; entry N takes a database from version N to N + 1.
+// Append new ones here rather than editing old ones.
+const MIGRATIONS: &[(&str, Migration)] = &[
+    ("base schema", Database::migrate_base_schema),
+    ("launcher flag on known games", Database::migrate_launcher_flag),
+    ("window title patterns on known games", Database::migrate_title_patterns),
+    ("enforcement log", Database::migrate_enforcement_log),
+    ("extra time requests", Database::migrate_time_requests),
+    ("user profiles", Database::migrate_profiles),
+    ("session trash", Database::migrate_session_trash),
+    ("user blacklist", Database::migrate_blacklist),
+    ("process name patterns on known games", Database::migrate_game_patterns),
+    ("budget-exempt games", Database::migrate_exempt_games),
+    ("manually entered sessions", Database::migrate_manual_sessions),
+    ("Steam API key out of settings", Database::migrate_steam_api_key),
+    ("achievement bonus history", Database::migrate_achievement_history),
+    ("per-profile recurring grants, requests and weekday allowances", Database::migrate_profile_extras),
+];
+
+// Kept in PRAGMA user_version
+pub const SCHEMA_VERSION: i32 = MIGRATIONS.len() as i32;
+
+// Tables a backup must have before it's restored
+const REQUIRED_TABLES: &[&str] = &["sessions", "learning_activities", "settings", "custom_games"];
+
+// Column list matching Database::session_from_row
+const SESSION_COLUMNS: &str = "id, game_name, process_name, start_time, end_time, duration_seconds, is_social_session, is_concurrent, concurrent_session_ids, is_web_game, idle_seconds, is_imported, end_reason, locked_seconds, detection_source, profile_id, is_manual";
+
+// A played interval: (start, end, is_concurrent)
+type BillablePeriod = (DateTime<Utc>, DateTime<Utc>, bool);
+
+// How long sessions removed by reset_today_sessions can still be restored
+const SESSION_TRASH_DAYS: i64 = 7;
+
+// Column list matching Database::learning_activity_from_row
+const LEARNING_COLUMNS: &str = "id, activity_type, description, duration_minutes, earned_gaming_minutes, timestamp, is_voided";
+
 impl Database {
-    pub fn new() -> SqlResult<Self> {
-        let db_path = Self::get_db_path();
-        let conn = Connection::open(&db_path)?;
+    // Opens the database in data_dir, or in the directory resolve_data_dir picks
+    pub fn new(data_dir: Option<PathBuf>) -> SqlResult<Self> {
+        let data_dir = Self::resolve_data_dir(data_dir);
+        std::fs::create_dir_all(&data_dir).unwrap_or_else(|e| {
+            error!("Failed to create data directory: {}", e);
+        });
+        let db_path = data_dir.join(DB_FILE_NAME);
+        let (conn, encrypted) = Self::open_connection(&db_path)?;
 
-        info!("Database opened at: {:?}", db_path);
+        info!("Database opened at: {:?}{}", db_path, if encrypted { " (encrypted)" } else { "" });
 
-        let db = Database { conn };
-        db.create_tables()?;
+        let mut db = Database { conn, data_dir, profile_id: PRIMARY_PROFILE_ID.to_string(), encrypted };
+        db.run_migrations()?;
         db.insert_default_settings()?;
+        db.apply_encryption_setting()?;
+        db.load_active_profile()?;
 
         Ok(db)
     }
 
-    fn get_db_path() -> PathBuf {
-        let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
-        path.push("GamingTimeTracker");
-        std::fs::create_dir_all(&path).unwrap_or_else(|e| {
-            error!("Failed to create data directory: {}", e);
+    // Opens the file with the keyring key when there is one. A file that is
+    // still plaintext, because encrypting it hasn't run yet or was interrupted,
+    // opens without the key. A wrong or missing key is reported as such rather
+    // than as SQLite's "file is not a database".
+    fn open_connection(db_path: &Path) -> SqlResult<(Connection, bool)> {
+        let key = encryption::load_key().unwrap_or_else(|e| {
+            error!("{}", e);
+            None
+        });
+
+        if let Some(key) = &key {
+            let conn = Connection::open(db_path)?;
+            conn.execute_batch(&format!("PRAGMA key = \"{}\";", encryption::key_literal(key)))?;
+            if Self::is_readable(&conn) {
+                return Ok((conn, true));
+            }
+        }
+
+        let conn = Connection::open(db_path)?;
+        if Self::is_readable(&conn) {
+            return Ok((conn, false));
+        }
+
+        let message = if key.is_some() {
+            "The database is encrypted with a different key than the one in the OS keyring"
+        } else {
+            "The database can't be read; it may be encrypted with a key that is missing from the OS keyring"
+        };
+        Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_NOTADB),
+            Some(message.to_string()),
+        ))
+    }
+
+    fn is_readable(conn: &Connection) -> bool {
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)).is_ok()
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    // Encrypts or decrypts the file to match encryption_enabled. Only done while
+    // opening, as the file is replaced underneath the connection. A failed
+    // conversion leaves the database as it was and is only logged.
+    fn apply_encryption_setting(&mut self) -> SqlResult<()> {
+        let wanted = self.get_settings()?.encryption_enabled;
+        if wanted == self.encrypted {
+            return Ok(());
+        }
+        let action = if wanted { "encrypt" } else { "decrypt" };
+
+        let key = if wanted {
+            match encryption::create_key() {
+                Ok(key) => Some(key),
+                Err(e) => {
+                    error!("Can't encrypt the database: {}", e);
+                    return Ok(());
+                }
+            }
+        } else {
+            None
+        };
+
+        let db_path = self.data_dir.join(DB_FILE_NAME);
+        let converted_path = self.data_dir.join(format!("{}.converting", DB_FILE_NAME));
+        if let Err(e) = self.export_to(&converted_path, key.as_deref()) {
+            error!("Failed to {} the database: {}", action, e);
+            let _ = std::fs::remove_file(&converted_path);
+            if wanted {
+                let _ = encryption::delete_key();
+            }
+            return Ok(());
+        }
+
+        // The in-memory stand-in is replaced as soon as the file is swapped
+        let old = std::mem::replace(&mut self.conn, Connection::open_in_memory()?);
+        old.close().map_err(|(_, e)| e)?;
+        match std::fs::rename(&converted_path, &db_path) {
+            Ok(()) if !wanted => {
+                if let Err(e) = encryption::delete_key() {
+                    error!("{}", e);
+                }
+            }
+            Ok(()) => {}
+            Err(e) => {
+                error!("Failed to {} the database: can't replace the file: {}", action, e);
+                let _ = std::fs::remove_file(&converted_path);
+                if wanted {
+                    let _ = encryption::delete_key();
+                }
+            }
+        }
+
+        let (conn, encrypted) = Self::open_connection(&db_path)?;
+        self.conn = conn;
+        self.encrypted = encrypted;
+        info!("Database is {}", if encrypted { "now encrypted" } else { "now plaintext" });
+        Ok(())
+    }
+
+    // Copies everything into a new file with SQLCipher's sqlcipher_export;
+    // without a key the copy is plaintext
+    fn export_to(&self, path: &Path, key: Option<&str>) -> SqlResult<()> {
+        let _ = std::fs::remove_file(path);
+        let key = key.map(encryption::key_literal).unwrap_or_default();
+        let version = self.schema_version()?;
+
+        self.conn.execute(
+            "ATTACH DATABASE ?1 AS converted KEY ?2",
+            params![path.to_string_lossy().into_owned(), key],
+        )?;
+        let result = self.conn.query_row("SELECT sqlcipher_export('converted')", [], |_| Ok(()))
+            .and_then(|_| self.conn.pragma_update(Some(DatabaseName::Attached("converted")), "user_version", version));
+        self.conn.execute_batch("DETACH DATABASE converted")?;
+        result
+    }
+
+    // An explicit directory wins, then GTT_DATA_DIR, then the per-user default.
+    // Relative paths are taken to mean a portable install and are resolved
+    // against the executable's directory rather than the working directory.
+    pub fn resolve_data_dir(data_dir: Option<PathBuf>) -> PathBuf {
+        let configured = data_dir.or_else(|| {
+            std::env::var_os(DATA_DIR_ENV)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
         });
-        path.push("gaming_tracker.db");
-        path
+
+        match configured {
+            Some(path) if path.is_relative() => {
+                let exe_dir = std::env::current_exe().ok()
+                    .and_then(|exe| exe.parent().map(Path::to_path_buf))
+                    .unwrap_or_else(|| PathBuf::from("."));
+                exe_dir.join(path)
+            }
+            Some(path) => path,
+            None => {
+                let mut path = dirs::data_local_dir().unwrap_or_else(|| PathBuf::from("."));
+                path.push("GamingTimeTracker");
+                path
+            }
+        }
+    }
+
+    pub fn data_dir(&self) -> &Path {
+        &self.data_dir
+    }
+
+    // Reads the schema version and applies every newer migration in order. Each
+    // runs in its own transaction together with its version bump, so a failed
+    // migration leaves the database at the last good version.
+    pub fn run_migrations(&self) -> SqlResult<()> {
+        let current = self.schema_version()?;
+        if current > SCHEMA_VERSION {
+            error!("Database schema version {} is newer than this app supports ({}); leaving it as is",
+                   current, SCHEMA_VERSION);
+            return Ok(());
+        }
+
+        for (index, (description, migrate)) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+            let version = index as i32 + 1;
+            info!("Migrating database to schema version {}: {}", version, description);
+
+            self.conn.execute_batch("BEGIN")?;
+            let result = migrate(self)
+                .and_then(|_| self.conn.pragma_update(None, "user_version", version));
+            match result {
+                Ok(()) => self.conn.execute_batch("COMMIT")?,
+                Err(e) => {
+                    let _ = self.conn.execute_batch("ROLLBACK");
+                    error!("Migration to schema version {} failed: {}", version, e);
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn schema_version(&self) -> SqlResult<i32> {
+        self.conn.pragma_query_value(None, "user_version", |row| row.get(0))
+    }
+
+    // Takes the write lock and gives it straight back, which fails on a
+    // read-only file or directory without changing anything
+    pub fn is_writable(&self) -> bool {
+        self.conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK").is_ok()
+    }
+
+    // Rolls back a transaction left open by a panic between BEGIN and COMMIT,
+    // so the half-done writes never get committed by whatever runs next
+    pub fn rollback_unfinished(&self) -> SqlResult<bool> {
+        if self.conn.is_autocommit() {
+            return Ok(false);
+        }
+        self.conn.execute_batch("ROLLBACK")?;
+        Ok(true)
+    }
+
+    fn column_exists(&self, table: &str, column: &str) -> SqlResult<bool> {
+        self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+            params![table, column],
+            |row| row.get(0),
+        )
+    }
+
+    fn add_column_if_missing(&self, table: &str, column: &str, definition: &str) -> SqlResult<()> {
+        if !self.column_exists(table, column)? {
+            self.conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
+        }
+        Ok(())
     }
 
-    fn create_tables(&self) -> SqlResult<()> {
+    // Version 1: the schema as it stood before versioning. Databases from that
+    // time may have any subset of it, so every step checks before changing.
+    fn migrate_base_schema(&self) -> SqlResult<()> {
         // Game sessions table
         self.conn.execute(
             "CREATE TABLE IF NOT EXISTS sessions (
@@ -49,15 +318,15 @@ impl Database {
             [],
         )?;
 
-        // Add new columns if they don't exist (migration)
-        let _ = self.conn.execute(
-            "ALTER TABLE sessions ADD COLUMN is_concurrent BOOLEAN DEFAULT FALSE",
-            [],
-        );
-        let _ = self.conn.execute(
-            "ALTER TABLE sessions ADD COLUMN concurrent_session_ids TEXT DEFAULT '[]'",
-            [],
-        );
+        // Columns added after the first release
+        self.add_column_if_missing("sessions", "is_concurrent", "BOOLEAN DEFAULT FALSE")?;
+        self.add_column_if_missing("sessions", "concurrent_session_ids", "TEXT DEFAULT '[]'")?;
+        self.add_column_if_missing("sessions", "is_web_game", "BOOLEAN DEFAULT FALSE")?;
+        self.add_column_if_missing("sessions", "idle_seconds", "INTEGER DEFAULT 0")?;
+        self.add_column_if_missing("sessions", "is_imported", "BOOLEAN DEFAULT FALSE")?;
+        self.add_column_if_missing("sessions", "end_reason", "TEXT")?;
+        self.add_column_if_missing("sessions", "locked_seconds", "INTEGER DEFAULT 0")?;
+        self.add_column_if_missing("sessions", "detection_source", "TEXT DEFAULT ''")?;
 
         // Learning activities table
         self.conn.execute(
@@ -72,6 +341,7 @@ impl Database {
             )",
             [],
         )?;
+        self.add_column_if_missing("learning_activities", "is_voided", "BOOLEAN DEFAULT FALSE")?;
 
         // Settings table
         self.conn.execute(
@@ -93,142 +363,1469 @@ impl Database {
             [],
         )?;
 
-        info!("Database tables created successfully");
-        Ok(())
-    }
+        // Temporary grants table (bonus minutes that expire on their own)
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS temporary_grants (
+                id TEXT PRIMARY KEY,
+                grant_type TEXT NOT NULL,
+                minutes INTEGER NOT NULL,
+                reason TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            )",
+            [],
+        )?;
 
-    fn insert_default_settings(&self) -> SqlResult<()> {
-        // Insert default settings if they don't exist
+        // Known games table (built-in defaults plus user additions)
         self.conn.execute(
-            "INSERT OR IGNORE INTO settings (key, value) VALUES
-             ('daily_allowance_minutes', '120'),
-             ('rollover_days', '3'),
-             ('notifications_enabled', 'true'),
-             ('warning_threshold_minutes', '15')",
+            "CREATE TABLE IF NOT EXISTS custom_games (
+                process_name TEXT PRIMARY KEY,
+                display_name TEXT NOT NULL,
+                is_monitored BOOLEAN DEFAULT TRUE
+            )",
             [],
         )?;
-        Ok(())
-    }
+        self.add_column_if_missing("custom_games", "notify_on_limit", "BOOLEAN DEFAULT TRUE")?;
 
-    pub fn save_session(&self, session: &GameSession) -> SqlResult<()> {
-        let end_time_str = session.end_time.map(|dt| dt.to_rfc3339());
-        let concurrent_ids_json = serde_json::to_string(&session.concurrent_session_ids)
-            .unwrap_or_else(|_| "[]".to_string());
+        // Games sharing an executable name are told apart by path, so the key
+        // becomes (process_name, path_pattern); '' means name-only. SQLite can't
+        // change a primary key in place, so the table is rebuilt once.
+        let has_path_pattern: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('custom_games') WHERE name = 'path_pattern'",
+            [],
+            |row| row.get(0),
+        )?;
+        if has_path_pattern == 0 {
+            self.conn.execute_batch(
+                "CREATE TABLE custom_games_new (
+                     process_name TEXT NOT NULL,
+                     display_name TEXT NOT NULL,
+                     is_monitored BOOLEAN DEFAULT TRUE,
+                     notify_on_limit BOOLEAN DEFAULT TRUE,
+                     path_pattern TEXT NOT NULL DEFAULT '',
+                     PRIMARY KEY (process_name, path_pattern)
+                 );
+                 INSERT INTO custom_games_new (process_name, display_name, is_monitored, notify_on_limit)
+                     SELECT process_name, display_name, is_monitored, COALESCE(notify_on_limit, TRUE) FROM custom_games;
+                 DROP TABLE custom_games;
+                 ALTER TABLE custom_games_new RENAME TO custom_games;"
+            )?;
+        }
 
+        // Bonus minutes granted when an achievement milestone is reached
         self.conn.execute(
-            "INSERT INTO sessions (id, game_name, process_name, start_time, end_time, duration_seconds, is_social_session, is_concurrent, concurrent_session_ids)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![
-                session.id,
-                session.game_name,
-                session.process_name,
-                session.start_time.to_rfc3339(),
-                end_time_str,
-                session.duration_seconds,
-                session.is_social_session,
-                session.is_concurrent,
-                concurrent_ids_json
-            ],
+            "CREATE TABLE IF NOT EXISTS achievement_bonuses (
+                milestone TEXT PRIMARY KEY,
+                minutes INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Achievements already rewarded, so each bonus is only granted once
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS achievement_grants (
+                achievement TEXT PRIMARY KEY,
+                grant_id TEXT NOT NULL,
+                granted_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Per-game daily limits on top of the overall budget
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS game_limits (
+                process_name TEXT PRIMARY KEY,
+                daily_limit_minutes INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        // Snapshots of in-progress sessions, so a crash doesn't lose their time
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS active_sessions (
+                id TEXT PRIMARY KEY,
+                data TEXT NOT NULL,
+                last_seen TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        // Per-game weekly goals
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS game_goals (
+                id TEXT PRIMARY KEY,
+                game_name TEXT NOT NULL,
+                goal_type TEXT NOT NULL,
+                target_minutes INTEGER NOT NULL,
+                notified_status TEXT,
+                notified_week TEXT
+            )",
+            [],
+        )?;
+
+        // Weekly recurring allowance boosts
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS recurring_grants (
+                id TEXT PRIMARY KEY,
+                weekday INTEGER NOT NULL,
+                minutes INTEGER NOT NULL,
+                expires_at_time TEXT,
+                reason TEXT NOT NULL,
+                last_applied_date TEXT
+            )",
+            [],
+        )?;
+
+        // Borrowed time, keyed by the day it is repaid on
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS budget_debt (
+                date TEXT PRIMARY KEY,
+                minutes INTEGER NOT NULL
+            )",
+            [],
         )?;
 
-        info!("Session saved: {}{}", session.game_name,
-              if session.is_concurrent { " [CONCURRENT]" } else { "" });
         Ok(())
     }
 
-    pub fn get_recent_sessions(&self, limit: usize) -> SqlResult<Vec<GameSession>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, game_name, process_name, start_time, end_time, duration_seconds, is_social_session, is_concurrent, concurrent_session_ids
-             FROM sessions
-             ORDER BY start_time DESC
-             LIMIT ?1"
+    // Version 2: launchers such as the Steam client are told apart from games.
+    // The built-in launchers were seeded as games, so they're flagged here.
+    fn migrate_launcher_flag(&self) -> SqlResult<()> {
+        self.add_column_if_missing("custom_games", "is_launcher", "BOOLEAN DEFAULT FALSE")?;
+
+        let launchers: Vec<String> = GameMonitor::default_games().into_iter()
+            .filter(|game| game.is_launcher)
+            .map(|game| game.process_name)
+            .collect();
+        for process_name in &launchers {
+            self.conn.execute(
+                "UPDATE custom_games SET is_launcher = TRUE WHERE process_name = ?1 AND path_pattern = ''",
+                [process_name],
+            )?;
+        }
+        Ok(())
+    }
+
+    // Version 3: games sharing an executable (javaw.exe, UnrealGame.exe) are told
+    // apart by window title, so the title pattern joins the key ('' means any
+    // title). The table is rebuilt because SQLite can't change a primary key.
+    fn migrate_title_patterns(&self) -> SqlResult<()> {
+        if self.column_exists("custom_games", "title_pattern")? {
+            return Ok(());
+        }
+
+        self.conn.execute_batch(
+            "CREATE TABLE custom_games_new (
+                 process_name TEXT NOT NULL,
+                 display_name TEXT NOT NULL,
+                 is_monitored BOOLEAN DEFAULT TRUE,
+                 notify_on_limit BOOLEAN DEFAULT TRUE,
+                 path_pattern TEXT NOT NULL DEFAULT '',
+                 is_launcher BOOLEAN DEFAULT FALSE,
+                 title_pattern TEXT NOT NULL DEFAULT '',
+                 PRIMARY KEY (process_name, path_pattern, title_pattern)
+             );
+             INSERT INTO custom_games_new (process_name, display_name, is_monitored, notify_on_limit, path_pattern, is_launcher)
+                 SELECT process_name, display_name, is_monitored, notify_on_limit, path_pattern, COALESCE(is_launcher, FALSE) FROM custom_games;
+             DROP TABLE custom_games;
+             ALTER TABLE custom_games_new RENAME TO custom_games;"
+        )
+    }
+
+    // Version 4: every game enforcement closes is recorded, so a closure can be
+    // told apart from the player quitting
+    fn migrate_enforcement_log(&self) -> SqlResult<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS enforcement_log (
+                 id TEXT PRIMARY KEY,
+                 timestamp TEXT NOT NULL,
+                 game_name TEXT NOT NULL,
+                 reason TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_enforcement_log_timestamp ON enforcement_log (timestamp);"
+        )
+    }
+
+    // Version 5: requests for more time sent from the warning overlay
+    fn migrate_time_requests(&self) -> SqlResult<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS time_requests (
+                 id TEXT PRIMARY KEY,
+                 timestamp TEXT NOT NULL,
+                 minutes INTEGER NOT NULL,
+                 reason TEXT NOT NULL,
+                 status TEXT NOT NULL DEFAULT 'pending'
+             );"
+        )
+    }
+
+    // Version 6: several players can share one database. Everything recorded so
+    // far becomes the Primary profile's, and the allowance and last rollover
+    // move from settings onto the profile. Rollover and debt rows are keyed by
+    // profile as well as date, so those tables are rebuilt.
+    fn migrate_profiles(&self) -> SqlResult<()> {
+        let setting = |key: &str| -> SqlResult<Option<String>> {
+            self.conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0)).optional()
+        };
+        let allowance = setting("daily_allowance_minutes")?
+            .and_then(|value| value.parse::<i32>().ok())
+            .unwrap_or(120);
+        let last_rollover_date = setting("last_rollover_date")?;
+
+        self.conn.execute(
+            "CREATE TABLE IF NOT EXISTS profiles (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                daily_allowance_minutes INTEGER NOT NULL,
+                last_rollover_date TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        self.conn.execute(
+            "INSERT OR IGNORE INTO profiles (id, name, daily_allowance_minutes, last_rollover_date, created_at)
+             VALUES (?1, 'Primary', ?2, ?3, ?4)",
+            params![PRIMARY_PROFILE_ID, allowance, last_rollover_date, Utc::now().to_rfc3339()],
         )?;
 
-        let session_iter = stmt.query_map([limit], |row| {
-            let start_time_str: String = row.get(3)?;
-            let end_time_str: Option<String> = row.get(4)?;
-            let concurrent_ids_json: String = row.get(8).unwrap_or_else(|_| "[]".to_string());
-            let concurrent_session_ids: Vec<String> = serde_json::from_str(&concurrent_ids_json)
-                .unwrap_or_else(|_| Vec::new());
+        for table in ["sessions", "learning_activities", "temporary_grants"] {
+            self.add_column_if_missing(table, "profile_id", "TEXT NOT NULL DEFAULT 'primary' REFERENCES profiles (id)")?;
+        }
 
-            Ok(GameSession {
-                id: row.get(0)?,
-                game_name: row.get(1)?,
-                process_name: row.get(2)?,
-                start_time: DateTime::parse_from_rfc3339(&start_time_str)
-                    .map_err(|_| rusqlite::Error::InvalidColumnType(3, "start_time".to_string(), rusqlite::types::Type::Text))?
-                    .with_timezone(&Utc),
-                end_time: end_time_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                    .map(|dt| dt.with_timezone(&Utc)),
-                duration_seconds: row.get(5)?,
-                is_social_session: row.get(6)?,
-                is_concurrent: row.get(7).unwrap_or(false),
-                concurrent_session_ids,
-            })
-        })?;
+        if !self.column_exists("budget_rollover", "profile_id")? {
+            self.conn.execute_batch(
+                "CREATE TABLE budget_rollover_new (
+                     profile_id TEXT NOT NULL DEFAULT 'primary',
+                     date TEXT NOT NULL,
+                     unused_minutes INTEGER NOT NULL,
+                     expires_at TEXT NOT NULL,
+                     PRIMARY KEY (profile_id, date)
+                 );
+                 INSERT INTO budget_rollover_new (date, unused_minutes, expires_at)
+                     SELECT date, unused_minutes, expires_at FROM budget_rollover;
+                 DROP TABLE budget_rollover;
+                 ALTER TABLE budget_rollover_new RENAME TO budget_rollover;"
+            )?;
+        }
+        if !self.column_exists("budget_debt", "profile_id")? {
+            self.conn.execute_batch(
+                "CREATE TABLE budget_debt_new (
+                     profile_id TEXT NOT NULL DEFAULT 'primary',
+                     date TEXT NOT NULL,
+                     minutes INTEGER NOT NULL,
+                     PRIMARY KEY (profile_id, date)
+                 );
+                 INSERT INTO budget_debt_new (date, minutes)
+                     SELECT date, minutes FROM budget_debt;
+                 DROP TABLE budget_debt;
+                 ALTER TABLE budget_debt_new RENAME TO budget_debt;"
+            )?;
+        }
 
-        let mut sessions = Vec::new();
-        for session in session_iter {
-            sessions.push(session?);
+        self.conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_sessions_profile_start ON sessions (profile_id, start_time);
+             CREATE INDEX IF NOT EXISTS idx_learning_profile_timestamp ON learning_activities (profile_id, timestamp);
+             DELETE FROM settings WHERE key IN ('daily_allowance_minutes', 'last_rollover_date');"
+        )
+    }
+
+    // Version 7: sessions removed by the debug reset are moved here instead of
+    // being deleted, so an accidental reset can be undone
+    fn migrate_session_trash(&self) -> SqlResult<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS sessions_trash (
+                 id TEXT NOT NULL,
+                 game_name TEXT NOT NULL,
+                 process_name TEXT NOT NULL,
+                 start_time TEXT NOT NULL,
+                 end_time TEXT,
+                 duration_seconds INTEGER,
+                 is_social_session BOOLEAN,
+                 is_concurrent BOOLEAN,
+                 concurrent_session_ids TEXT,
+                 is_web_game BOOLEAN,
+                 idle_seconds INTEGER,
+                 is_imported BOOLEAN,
+                 end_reason TEXT,
+                 locked_seconds INTEGER,
+                 detection_source TEXT,
+                 profile_id TEXT NOT NULL,
+                 reset_id TEXT NOT NULL,
+                 deleted_at TEXT NOT NULL
+             );
+             CREATE INDEX IF NOT EXISTS idx_sessions_trash_deleted_at ON sessions_trash (deleted_at);"
+        )
+    }
+
+    // Version 8: processes the user never wants tracked, on top of the built-in list
+    fn migrate_blacklist(&self) -> SqlResult<()> {
+        self.conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS blacklisted_processes (
+                 process_name TEXT PRIMARY KEY,
+                 created_at TEXT NOT NULL
+             );"
+        )
+    }
+
+    // Version 9: a known game can match process names by regex; process_name
+    // then holds the pattern
+    fn migrate_game_patterns(&self) -> SqlResult<()> {
+        self.add_column_if_missing("custom_games", "kind", "TEXT NOT NULL DEFAULT 'exact'")
+    }
+
+    // Version 10: exempt games are tracked for history but not billed
+    fn migrate_exempt_games(&self) -> SqlResult<()> {
+        self.add_column_if_missing("custom_games", "is_exempt", "BOOLEAN DEFAULT FALSE")
+    }
+
+    // Version 11: sessions a parent added by hand. The trash keeps the same
+    // columns as sessions so deleted rows can be restored as they were.
+    fn migrate_manual_sessions(&self) -> SqlResult<()> {
+        self.add_column_if_missing("sessions", "is_manual", "BOOLEAN DEFAULT FALSE")?;
+        self.add_column_if_missing("sessions_trash", "is_manual", "BOOLEAN DEFAULT FALSE")
+    }
+
+    // Earlier versions kept the Steam API key in plain text in settings. It moves
+    // to the OS keyring where the build has one, and is dropped either way.
+    fn migrate_steam_api_key(&self) -> SqlResult<()> {
+        let key: Option<String> = self.conn.query_row(
+            "SELECT value FROM settings WHERE key = 'steam_api_key'",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        if let Some(key) = key {
+            if let Err(e) = encryption::store_secret(crate::steam::API_KEY_SECRET, &key) {
+                error!("{}", e);
+            }
+            self.conn.execute("DELETE FROM settings WHERE key = 'steam_api_key'", [])?;
         }
+        Ok(())
+    }
 
-        Ok(sessions)
+    // Achievement claims keep the minutes and profile they were granted to, so
+    // they still show in the history after the grant itself expires
+    fn migrate_achievement_history(&self) -> SqlResult<()> {
+        self.add_column_if_missing("achievement_grants", "minutes", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("achievement_grants", "profile_id", "TEXT NOT NULL DEFAULT 'primary'")
     }
 
-    pub fn get_today_usage_minutes(&self) -> SqlResult<i32> {
-        let today_start = Local::now().date_naive().and_hms_opt(0, 0, 0).unwrap()
-            .and_local_timezone(Local).single().unwrap()
-            .with_timezone(&Utc);
+    // Recurring grants, time requests and weekday allowances were still shared by
+    // every profile. Existing ones go to Primary, and the weekday overrides are
+    // copied to each profile since they applied to all of them until now.
+    fn migrate_profile_extras(&self) -> SqlResult<()> {
+        for table in ["recurring_grants", "time_requests"] {
+            self.add_column_if_missing(table, "profile_id", "TEXT NOT NULL DEFAULT 'primary'")?;
+        }
+        self.add_column_if_missing("profiles", "weekday_allowances", "TEXT")?;
+        self.conn.execute_batch(
+            "UPDATE profiles SET weekday_allowances = (SELECT value FROM settings WHERE key = 'weekday_allowances');
+             DELETE FROM settings WHERE key = 'weekday_allowances';"
+        )
+    }
 
-        // For concurrent sessions, we need to calculate overlapping time periods
-        // instead of just summing durations
+    // Picks up the saved active profile, falling back to Primary if it's gone
+    fn load_active_profile(&mut self) -> SqlResult<()> {
+        let saved: Option<String> = self.conn.query_row(
+            "SELECT profiles.id FROM settings JOIN profiles ON profiles.id = settings.value
+             WHERE settings.key = 'active_profile_id'",
+            [],
+            |row| row.get(0),
+        ).optional()?;
+        self.profile_id = saved.unwrap_or_else(|| PRIMARY_PROFILE_ID.to_string());
+        Ok(())
+    }
+
+    pub fn active_profile_id(&self) -> &str {
+        &self.profile_id
+    }
+
+    fn profile_from_row(&self, row: &rusqlite::Row) -> SqlResult<Profile> {
+        let created_at_str: String = row.get(3)?;
+        let id: String = row.get(0)?;
+
+        Ok(Profile {
+            is_active: id == self.profile_id,
+            id,
+            name: row.get(1)?,
+            daily_allowance_minutes: row.get(2)?,
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .map_err(|_| rusqlite::Error::InvalidColumnType(3, "created_at".to_string(), rusqlite::types::Type::Text))?
+                .with_timezone(&Utc),
+        })
+    }
+
+    pub fn get_profiles(&self) -> SqlResult<Vec<Profile>> {
         let mut stmt = self.conn.prepare(
-            "SELECT start_time, end_time, duration_seconds, is_concurrent, concurrent_session_ids
-             FROM sessions
-             WHERE start_time >= ?1 AND duration_seconds IS NOT NULL
-             ORDER BY start_time"
+            "SELECT id, name, daily_allowance_minutes, created_at FROM profiles
+             ORDER BY created_at"
         )?;
 
-        let sessions_iter = stmt.query_map([today_start.to_rfc3339()], |row| {
-            let start_time_str: String = row.get(0)?;
-            let end_time_str: Option<String> = row.get(1)?;
-            let duration_seconds: i64 = row.get(2)?;
-            let is_concurrent: bool = row.get(3).unwrap_or(false);
-
-            Ok((
-                DateTime::parse_from_rfc3339(&start_time_str).unwrap().with_timezone(&Utc),
-                end_time_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
-                    .map(|dt| dt.with_timezone(&Utc)),
-                duration_seconds,
-                is_concurrent
-            ))
-        })?;
+        let profile_iter = stmt.query_map([], |row| self.profile_from_row(row))?;
 
-        let mut time_periods = Vec::new();
-          for session_result in sessions_iter {
-              let (start_time, end_time, _duration_seconds, is_concurrent) = session_result?;
-              if let Some(end_time) = end_time {
-                  time_periods.push((start_time, end_time, is_concurrent));
-              }
-          }
+        let mut profiles = Vec::new();
+        for profile in profile_iter {
+            profiles.push(profile?);
+        }
 
-        // Calculate total unique time (handling overlaps for concurrent sessions)
-        let total_seconds = self.calculate_unique_time_periods(&time_periods);
-        Ok((total_seconds / 60) as i32)
+        Ok(profiles)
     }
 
-    // Helper method to calculate unique time periods, handling concurrent sessions
-    fn calculate_unique_time_periods(&self, periods: &[(DateTime<Utc>, DateTime<Utc>, bool)]) -> i64 {
-        if periods.is_empty() {
-            return 0;
+    // Names are unique regardless of case. The new profile starts with no
+    // history, so yesterday is marked as already finalized.
+    pub fn create_profile(&self, name: &str, daily_allowance_minutes: i32) -> Result<Profile, String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 50 {
+            return Err("Profile names must be 1 to 50 characters".to_string());
         }
+        Self::validate_setting("daily_allowance_minutes", &daily_allowance_minutes.to_string())?;
 
-        // Sort periods by start time
-        let mut sorted_periods = periods.to_vec();
-        sorted_periods.sort_by_key(|(start, _, _)| *start);
-
-        let mut total_seconds = 0i64;
-        let mut current_end: Option<DateTime<Utc>> = None;
+        let taken: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM profiles WHERE name = ?1)",
+            [name],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+        if taken {
+            return Err(format!("A profile named {} already exists", name));
+        }
+
+        let profile = Profile {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            daily_allowance_minutes,
+            created_at: Utc::now(),
+            is_active: false,
+        };
+        let yesterday = self.current_day() - chrono::Duration::days(1);
+        self.conn.execute(
+            "INSERT INTO profiles (id, name, daily_allowance_minutes, last_rollover_date, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![profile.id, profile.name, profile.daily_allowance_minutes, yesterday.to_string(), profile.created_at.to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+
+        info!("Profile created: {}", profile.name);
+        Ok(profile)
+    }
+
+    // Callers end open sessions first, so they are saved under the old profile
+    pub fn set_active_profile(&mut self, id: &str) -> Result<(), String> {
+        let exists: bool = self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM profiles WHERE id = ?1)",
+            [id],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+        if !exists {
+            return Err(format!("No profile with id {}", id));
+        }
+
+        self.update_setting("active_profile_id", id).map_err(|e| e.to_string())?;
+        self.profile_id = id.to_string();
+        info!("Active profile is now {}", id);
+        Ok(())
+    }
+
+    // Removes the profile together with everything recorded for it. The active
+    // profile can't be deleted, which also keeps at least one profile around.
+    pub fn delete_profile(&self, id: &str) -> Result<(), String> {
+        if id == self.profile_id {
+            return Err("The active profile can't be deleted; switch to another profile first".to_string());
+        }
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let mut result = Ok(0);
+        for table in [
+            "sessions", "sessions_trash", "learning_activities", "temporary_grants", "budget_rollover", "budget_debt",
+            "recurring_grants", "time_requests", "profiles",
+        ] {
+            let column = if table == "profiles" { "id" } else { "profile_id" };
+            result = self.conn.execute(&format!("DELETE FROM {} WHERE {} = ?1", table, column), [id]);
+            if result.is_err() {
+                break;
+            }
+        }
+
+        match result {
+            Ok(0) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(format!("No profile with id {}", id))
+            }
+            Ok(_) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Profile deleted: {}", id);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Copies the live database with SQLite's online backup, so it's safe while
+    // sessions are being saved
+    pub fn backup_to(&self, dest_path: &Path) -> Result<(), String> {
+        // SQLCipher can't back up between an encrypted and a plaintext file
+        if self.encrypted {
+            return Err("Backups aren't available while the database is encrypted".to_string());
+        }
+        self.conn.backup(DatabaseName::Main, dest_path, None)
+            .map_err(|e| format!("Backup failed: {}", e))?;
+        info!("Database backed up to {:?}", dest_path);
+        Ok(())
+    }
+
+    // Checks the backup before anything is touched, then copies it over the live
+    // database in one backup step. The connection stays open, so callers holding
+    // the mutex never see a half-restored database.
+    pub fn restore_from(&mut self, src_path: &Path) -> Result<(), String> {
+        if self.encrypted {
+            return Err("Backups can't be restored while the database is encrypted".to_string());
+        }
+        Self::validate_backup(src_path)?;
+
+        self.conn.restore(DatabaseName::Main, src_path, None::<fn(rusqlite::backup::Progress)>)
+            .map_err(|e| format!("Restore failed: {}", e))?;
+        // Backups from older versions are brought up to date
+        self.run_migrations().map_err(|e| e.to_string())?;
+        self.insert_default_settings().map_err(|e| e.to_string())?;
+        self.load_active_profile().map_err(|e| e.to_string())?;
+
+        info!("Database restored from {:?}", src_path);
+        Ok(())
+    }
+
+    fn validate_backup(src_path: &Path) -> Result<(), String> {
+        let conn = Connection::open_with_flags(src_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| format!("Can't open backup: {}", e))?;
+
+        let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))
+            .map_err(|e| format!("Not a readable database: {}", e))?;
+        if check != "ok" {
+            return Err(format!("Backup is damaged: {}", check));
+        }
+
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))
+            .map_err(|e| e.to_string())?;
+        if version > SCHEMA_VERSION {
+            return Err(format!("Backup schema version {} is newer than this app's version {}", version, SCHEMA_VERSION));
+        }
+
+        for table in REQUIRED_TABLES {
+            let exists: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+                [table],
+                |row| row.get(0),
+            ).map_err(|e| e.to_string())?;
+            if !exists {
+                return Err(format!("Backup is missing the {} table", table));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert_default_settings(&self) -> SqlResult<()> {
+        // Insert default settings if they don't exist
+        self.conn.execute(
+            "INSERT OR IGNORE INTO settings (key, value) VALUES
+             ('rollover_days', '3'),
+             ('notifications_enabled', 'true'),
+             ('warning_threshold_minutes', '15'),
+             ('web_game_detection_enabled', 'false'),
+             ('cpu_idle_threshold', '0'),
+             ('cpu_idle_seconds', '300'),
+             ('nag_interval_minutes', '10'),
+             ('quiet_hours', ''),
+             ('max_debt_minutes', '60'),
+             ('log_format', 'text'),
+             ('detection_mode', 'auto'),
+             ('confirm_steam_names', 'false'),
+             ('personal_best_notifications', 'false'),
+             ('countdown_minutes', '0'),
+             ('trust_startup_detections', 'false'),
+             ('full_scan_interval_seconds', '1'),
+             ('max_distinct_games_per_day', '0'),
+             ('enforcement_enabled', 'true'),
+             ('kill_grace_seconds', '120'),
+             ('idle_threshold_minutes', '10'),
+             ('max_earned_minutes_per_day', '60'),
+             ('notification_cooldown_seconds', '300'),
+             ('warning_thresholds', '30,15,5,0'),
+             ('social_session_discount_percent', '0'),
+             ('allow_time_requests', 'false'),
+             ('encryption_enabled', 'false'),
+             ('session_merge_seconds', '60'),
+             ('monitor_interval_secs', '1'),
+             ('count_foreground_only', 'false'),
+             ('day_reset_hour', '0'),
+             ('notification_style', 'overlay'),
+             ('active_profile_id', 'primary')",
+            [],
+        )?;
+        Ok(())
+    }
+
+    pub fn save_session(&self, session: &GameSession) -> SqlResult<()> {
+        let concurrent_ids_json = serde_json::to_string(&session.concurrent_session_ids)
+            .unwrap_or_else(|_| "[]".to_string());
+        self.insert_session(session, &concurrent_ids_json)
+    }
+
+    // Saves sessions that ended together (e.g. after closing every game) in one
+    // transaction. Nothing is saved if any of them fails, and the error names
+    // the session that did.
+    pub fn save_sessions(&self, sessions: &[GameSession]) -> Result<(), String> {
+        if sessions.is_empty() {
+            return Ok(());
+        }
+
+        let mut rows = Vec::new();
+        for session in sessions {
+            let concurrent_ids_json = serde_json::to_string(&session.concurrent_session_ids)
+                .map_err(|e| format!("Failed to serialize session {} ({}): {}", session.game_name, session.id.as_deref().unwrap_or("no id"), e))?;
+            rows.push((session, concurrent_ids_json));
+        }
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let mut result = Ok(());
+        for (session, concurrent_ids_json) in &rows {
+            if let Err(e) = self.insert_session(session, concurrent_ids_json) {
+                result = Err(format!("Failed to save session {} ({}): {}", session.game_name, session.id.as_deref().unwrap_or("no id"), e));
+                break;
+            }
+        }
+
+        match result {
+            Ok(()) => self.conn.execute_batch("COMMIT").map_err(|e| e.to_string()),
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
+    fn insert_session(&self, session: &GameSession, concurrent_ids_json: &str) -> SqlResult<()> {
+        let end_time_str = session.end_time.map(|dt| dt.to_rfc3339());
+
+        self.conn.execute(
+            "INSERT INTO sessions (id, game_name, process_name, start_time, end_time, duration_seconds, is_social_session, is_concurrent, concurrent_session_ids, is_web_game, idle_seconds, is_imported, end_reason, locked_seconds, detection_source, profile_id, is_manual)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+            params![
+                session.id,
+                session.game_name,
+                session.process_name,
+                session.start_time.to_rfc3339(),
+                end_time_str,
+                session.duration_seconds,
+                session.is_social_session,
+                session.is_concurrent,
+                concurrent_ids_json,
+                session.is_web_game,
+                session.idle_seconds,
+                session.is_imported,
+                session.end_reason.map(|reason| reason.as_str()),
+                session.locked_seconds,
+                session.detection_source,
+                if session.profile_id.is_empty() { &self.profile_id } else { &session.profile_id },
+                session.is_manual
+            ],
+        )?;
+
+        info!("Session saved: {} [via {}]{}", session.game_name, session.detection_source,
+              if session.is_concurrent { " [CONCURRENT]" } else { "" });
+        Ok(())
+    }
+
+    // Replaces the snapshot of in-progress sessions, stamping them as seen now
+    pub fn save_active_sessions(&self, sessions: &[GameSession]) -> Result<(), String> {
+        let now = Utc::now().to_rfc3339();
+        let mut rows = Vec::new();
+        for session in sessions {
+            rows.push((session.id.clone(), serde_json::to_string(session).map_err(|e| e.to_string())?));
+        }
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let mut result = self.conn.execute("DELETE FROM active_sessions", []).map(|_| ());
+        for (id, data) in &rows {
+            if result.is_err() {
+                break;
+            }
+            result = self.conn.execute(
+                "INSERT INTO active_sessions (id, data, last_seen) VALUES (?1, ?2, ?3)",
+                params![id, data, now],
+            ).map(|_| ());
+        }
+
+        match result {
+            Ok(()) => self.conn.execute_batch("COMMIT").map_err(|e| e.to_string()),
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Turns snapshots left behind by a crash into finished sessions that end when
+    // they were last seen. A game still running after the restart is detected
+    // again as a new session starting now, so no time is counted twice.
+    pub fn recover_orphaned_sessions(&self) -> Result<usize, String> {
+        let orphans = {
+            let mut stmt = self.conn.prepare("SELECT data, last_seen FROM active_sessions")
+                .map_err(|e| e.to_string())?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+                .map_err(|e| e.to_string())?;
+            rows.collect::<SqlResult<Vec<_>>>().map_err(|e| e.to_string())?
+        };
+
+        if orphans.is_empty() {
+            return Ok(0);
+        }
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let mut recovered = 0;
+        let mut result = Ok(());
+        for (data, last_seen) in orphans {
+            let mut session: GameSession = match serde_json::from_str(&data) {
+                Ok(session) => session,
+                Err(e) => {
+                    error!("Dropping unreadable orphaned session: {}", e);
+                    continue;
+                }
+            };
+            let last_seen = match DateTime::parse_from_rfc3339(&last_seen) {
+                Ok(time) => time.with_timezone(&Utc),
+                Err(e) => {
+                    error!("Dropping orphaned session {} with a bad timestamp: {}", session.game_name, e);
+                    continue;
+                }
+            };
+
+            // Already saved if the crash came between recording it and clearing the snapshot
+            let id = session.id.clone().unwrap_or_default();
+            match self.get_session(&id) {
+                Ok(Some(_)) => continue,
+                Ok(None) => {}
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+
+            session.end_time = Some(last_seen);
+            session.duration_seconds = Some(((last_seen - session.start_time).num_seconds() - session.excluded_seconds()).max(0));
+            session.end_reason = Some(SessionEndReason::Recovered);
+            if let Err(e) = self.save_session(&session) {
+                result = Err(e);
+                break;
+            }
+            recovered += 1;
+        }
+
+        let result = result.and_then(|_| self.conn.execute("DELETE FROM active_sessions", []).map(|_| ()));
+        match result {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Recovered {} sessions left open by an unexpected shutdown", recovered);
+                Ok(recovered)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    fn session_from_row(row: &rusqlite::Row) -> SqlResult<GameSession> {
+        let start_time_str: String = row.get(3)?;
+        let end_time_str: Option<String> = row.get(4)?;
+        let concurrent_ids_json: String = row.get(8).unwrap_or_else(|_| "[]".to_string());
+        let concurrent_session_ids: Vec<String> = serde_json::from_str(&concurrent_ids_json)
+            .unwrap_or_else(|_| Vec::new());
+
+        let start_time = DateTime::parse_from_rfc3339(&start_time_str)
+            .map_err(|_| rusqlite::Error::InvalidColumnType(3, "start_time".to_string(), rusqlite::types::Type::Text))?
+            .with_timezone(&Utc);
+        let end_time = end_time_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+            .map(|dt| dt.with_timezone(&Utc));
+
+        Ok(GameSession {
+            id: row.get(0)?,
+            game_name: row.get(1)?,
+            process_name: row.get(2)?,
+            start_time,
+            end_time,
+            duration_seconds: row.get(5)?,
+            is_social_session: row.get(6)?,
+            is_concurrent: row.get(7).unwrap_or(false),
+            concurrent_session_ids,
+            is_web_game: row.get(9).unwrap_or(false),
+            idle_seconds: row.get(10).unwrap_or(0),
+            locked_seconds: row.get(13).unwrap_or(0),
+            is_imported: row.get(11).unwrap_or(false),
+            end_reason: row.get::<_, Option<String>>(12).unwrap_or(None)
+                .and_then(|reason| reason.parse().ok()),
+            detection_source: row.get::<_, Option<String>>(14).unwrap_or(None).unwrap_or_default(),
+            pids: Vec::new(),
+            profile_id: row.get::<_, Option<String>>(15).unwrap_or(None).unwrap_or_default(),
+            last_seen: end_time.unwrap_or(start_time),
+            is_manual: row.get::<_, Option<bool>>(16).unwrap_or(None).unwrap_or(false),
+        })
+    }
+
+    pub fn get_recent_sessions(&self, limit: usize) -> SqlResult<Vec<GameSession>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions
+             WHERE profile_id = ?2
+             ORDER BY start_time DESC
+             LIMIT ?1",
+            SESSION_COLUMNS
+        ))?;
+
+        let session_iter = stmt.query_map(params![limit, self.profile_id], Self::session_from_row)?;
+
+        let mut sessions = Vec::new();
+        for session in session_iter {
+            sessions.push(session?);
+        }
+
+        Ok(sessions)
+    }
+
+    // Filters are added as bound parameters only; user text never reaches the SQL
+    pub fn query_sessions(&self, filter: &SessionFilter) -> SqlResult<SessionPage> {
+        let mut conditions: Vec<&str> = vec!["profile_id = ?"];
+        let mut values: Vec<Value> = vec![Value::Text(self.profile_id.clone())];
+
+        if let Some(name) = filter.game_name.as_deref().map(str::trim).filter(|name| !name.is_empty()) {
+            let escaped = name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+            conditions.push("game_name LIKE ? ESCAPE '\\'");
+            values.push(Value::Text(format!("%{}%", escaped)));
+        }
+        if let Some(start) = filter.start {
+            conditions.push("start_time >= ?");
+            values.push(Value::Text(start.to_rfc3339()));
+        }
+        if let Some(end) = filter.end {
+            conditions.push("start_time <= ?");
+            values.push(Value::Text(end.to_rfc3339()));
+        }
+        if let Some(min_duration) = filter.min_duration_seconds {
+            conditions.push("duration_seconds >= ?");
+            values.push(Value::Integer(min_duration));
+        }
+
+        let where_clause = format!("WHERE {}", conditions.join(" AND "));
+
+        let total: i64 = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM sessions {}", where_clause),
+            params_from_iter(values.iter()),
+            |row| row.get(0),
+        )?;
+
+        let limit = filter.limit.unwrap_or(20).min(500);
+        let offset = filter.offset.unwrap_or(0);
+        values.push(Value::Integer(limit as i64));
+        values.push(Value::Integer(offset as i64));
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions {}
+             ORDER BY start_time DESC
+             LIMIT ? OFFSET ?",
+            SESSION_COLUMNS, where_clause
+        ))?;
+
+        let session_iter = stmt.query_map(params_from_iter(values.iter()), Self::session_from_row)?;
+
+        let mut sessions = Vec::new();
+        for session in session_iter {
+            sessions.push(session?);
+        }
+
+        Ok(SessionPage { sessions, total })
+    }
+
+    pub fn get_session(&self, id: &str) -> SqlResult<Option<GameSession>> {
+        self.conn.query_row(
+            &format!("SELECT {} FROM sessions WHERE id = ?1", SESSION_COLUMNS),
+            [id],
+            Self::session_from_row,
+        ).optional()
+    }
+
+    // Divides a finished session in two at the given time. Excluded (idle and
+    // locked) time is shared in proportion to each half's length, and the halves
+    // add back up to the original duration.
+    pub fn split_session_at(&self, id: &str, at: DateTime<Utc>) -> Result<(GameSession, GameSession), String> {
+        let original = self.get_session(id).map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("No session with id {}", id))?;
+
+        let end = original.end_time.ok_or("Only finished sessions can be split")?;
+        if original.is_imported {
+            return Err("Imported sessions can't be split".to_string());
+        }
+        if at <= original.start_time || at >= end {
+            return Err(format!("Split time must fall between {} and {}",
+                               original.start_time.to_rfc3339(), end.to_rfc3339()));
+        }
+
+        let total_seconds = (end - original.start_time).num_seconds().max(1);
+        let first_seconds = (at - original.start_time).num_seconds();
+        let share = |seconds: i64| seconds * first_seconds / total_seconds;
+
+        let mut first = original.clone();
+        first.id = Some(uuid::Uuid::new_v4().to_string());
+        first.end_time = Some(at);
+        first.idle_seconds = share(original.idle_seconds);
+        first.locked_seconds = share(original.locked_seconds);
+        first.duration_seconds = Some((first_seconds - first.excluded_seconds()).max(0));
+
+        let mut second = original.clone();
+        second.id = Some(uuid::Uuid::new_v4().to_string());
+        second.start_time = at;
+        second.idle_seconds = original.idle_seconds - first.idle_seconds;
+        second.locked_seconds = original.locked_seconds - first.locked_seconds;
+        second.duration_seconds = Some((original.duration_seconds.unwrap_or(0) - first.duration_seconds.unwrap_or(0)).max(0));
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result = self.conn.execute("DELETE FROM sessions WHERE id = ?1", [id])
+            .and_then(|_| self.save_session(&first))
+            .and_then(|_| self.save_session(&second));
+
+        match result {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Split session {} ({}) at {}", id, original.game_name, at.to_rfc3339());
+                Ok((first, second))
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Records play the app missed (e.g. an unlisted game). It's attributed to a
+    // known game's process when the name matches one, so per-game limits and
+    // exemptions still apply.
+    pub fn add_manual_session(&self, game_name: &str, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<GameSession, String> {
+        let game_name = game_name.trim();
+        if game_name.is_empty() {
+            return Err("A game name is required".to_string());
+        }
+        if end <= start {
+            return Err("The session must end after it starts".to_string());
+        }
+        if end > Utc::now() {
+            return Err("Sessions can't end in the future".to_string());
+        }
+
+        let process_name = self.get_custom_games().map_err(|e| e.to_string())?
+            .into_iter()
+            .find(|game| game.kind == GameMatchKind::Exact && game.display_name.eq_ignore_ascii_case(game_name))
+            .map(|game| game.process_name)
+            .unwrap_or_default();
+
+        let mut session = GameSession::new(game_name.to_string(), process_name);
+        session.start_time = start;
+        session.end_time = Some(end);
+        session.duration_seconds = Some((end - start).num_seconds());
+        session.last_seen = end;
+        session.detection_source = "manual".to_string();
+        session.profile_id = self.profile_id.clone();
+        session.is_manual = true;
+
+        self.save_session(&session).map_err(|e| e.to_string())?;
+        info!("Added a manual {}-minute session of {}", (end - start).num_minutes(), game_name);
+        Ok(session)
+    }
+
+    // Moves one session to the trash, where undo_reset_today can bring it back.
+    // Returns false when there's no such session.
+    pub fn delete_session(&self, id: &str) -> Result<bool, String> {
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result = self.conn.execute(
+            &format!(
+                "INSERT INTO sessions_trash ({0}, reset_id, deleted_at)
+                 SELECT {0}, ?2, ?3 FROM sessions WHERE id = ?1",
+                SESSION_COLUMNS
+            ),
+            params![id, uuid::Uuid::new_v4().to_string(), Utc::now().to_rfc3339()],
+        ).and_then(|_| self.conn.execute("DELETE FROM sessions WHERE id = ?1", [id]));
+
+        match result {
+            Ok(deleted) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                if deleted > 0 {
+                    info!("Session {} moved to the trash", id);
+                }
+                Ok(deleted > 0)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Different games recorded today, in the order they were first played
+    pub fn get_games_played_today(&self) -> SqlResult<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT game_name FROM sessions
+             WHERE start_time >= ?1 AND COALESCE(is_imported, 0) = 0 AND profile_id = ?2
+             GROUP BY game_name
+             ORDER BY MIN(start_time)"
+        )?;
+
+        let name_iter = stmt.query_map(params![self.current_day_start().to_rfc3339(), self.profile_id], |row| row.get(0))?;
+
+        let mut names = Vec::new();
+        for name in name_iter {
+            names.push(name?);
+        }
+
+        Ok(names)
+    }
+
+    pub fn get_sessions_since(&self, since: DateTime<Utc>) -> SqlResult<Vec<GameSession>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions
+             WHERE start_time >= ?1 AND profile_id = ?2
+             ORDER BY start_time",
+            SESSION_COLUMNS
+        ))?;
+
+        let session_iter = stmt.query_map(params![since.to_rfc3339(), self.profile_id], Self::session_from_row)?;
+
+        let mut sessions = Vec::new();
+        for session in session_iter {
+            sessions.push(session?);
+        }
+
+        Ok(sessions)
+    }
+
+    // Sessions started within [start, end); either bound may be left open
+    pub fn get_sessions_between(&self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> SqlResult<Vec<GameSession>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM sessions
+             WHERE (?1 IS NULL OR start_time >= ?1) AND (?2 IS NULL OR start_time < ?2) AND profile_id = ?3
+             ORDER BY start_time",
+            SESSION_COLUMNS
+        ))?;
+
+        let session_iter = stmt.query_map(
+            params![start.map(|t| t.to_rfc3339()), end.map(|t| t.to_rfc3339()), self.profile_id],
+            Self::session_from_row,
+        )?;
+
+        let mut sessions = Vec::new();
+        for session in session_iter {
+            sessions.push(session?);
+        }
+
+        Ok(sessions)
+    }
+
+    // Calendar days (charts, history) start at local midnight
+    pub fn local_midnight(date: NaiveDate) -> DateTime<Utc> {
+        Self::local_time_on(date, 0)
+    }
+
+    fn local_time_on(date: NaiveDate, hour: u32) -> DateTime<Utc> {
+        Self::local_to_utc(date.and_hms_opt(hour, 0, 0).unwrap())
+    }
+
+    // When a DST change skips the time, it's taken once the clock resumes; when
+    // the time happens twice, the first one counts
+    fn local_to_utc(time: NaiveDateTime) -> DateTime<Utc> {
+        time.and_local_timezone(Local).earliest()
+            .or_else(|| (time + chrono::Duration::hours(1)).and_local_timezone(Local).earliest())
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    // The budget day a local time falls in. With a reset hour of 4, 3 AM still
+    // belongs to the day before.
+    pub fn budget_day_of(time: NaiveDateTime, day_reset_hour: u32) -> NaiveDate {
+        (time - chrono::Duration::hours(day_reset_hour as i64)).date()
+    }
+
+    // Read on its own rather than through get_settings, since every budget
+    // query needs it
+    fn day_reset_hour(&self) -> u32 {
+        self.conn.query_row(
+            "SELECT value FROM settings WHERE key = 'day_reset_hour'",
+            [],
+            |row| row.get::<_, String>(0),
+        ).map(|value| Self::parse_day_reset_hour(&value)).unwrap_or(0)
+    }
+
+    // Out-of-range values fall back to midnight or are capped at noon
+    fn parse_day_reset_hour(value: &str) -> u32 {
+        value.parse::<u32>().unwrap_or(0).min(12)
+    }
+
+    // Today's date as far as the budget is concerned
+    pub fn current_day(&self) -> NaiveDate {
+        Self::budget_day_of(Local::now().naive_local(), self.day_reset_hour())
+    }
+
+    // Start of a budget day, at day_reset_hour local time
+    pub fn day_start(&self, day: NaiveDate) -> DateTime<Utc> {
+        Self::local_time_on(day, self.day_reset_hour())
+    }
+
+    // Start of the current budget day, when usage and earnings reset
+    pub fn current_day_start(&self) -> DateTime<Utc> {
+        self.day_start(self.current_day())
+    }
+
+    pub fn get_today_usage_minutes(&self) -> SqlResult<i32> {
+        Ok((self.get_today_usage_seconds_with_active(&[])? / 60) as i32)
+    }
+
+    // Today's usage including the sessions still open. Open sessions count up to
+    // now, minus their idle and locked time, and are merged with the finished ones
+    // so overlapping play is only counted once.
+    pub fn get_today_usage_with_active(&self, active_sessions: &[GameSession]) -> SqlResult<i32> {
+        Ok((self.get_today_usage_seconds_with_active(active_sessions)? / 60) as i32)
+    }
+
+    pub fn get_today_usage_seconds_with_active(&self, active_sessions: &[GameSession]) -> SqlResult<i64> {
+        let today_start = self.current_day_start();
+        let now = Utc::now();
+        let mut time_periods = self.get_billable_periods_since(today_start, None)?;
+        let mut active_periods = Vec::new();
+        let exempt = self.exempt_process_names()?;
+
+        // Same rule as finished sessions: only the part after the day started counts
+        for session in active_sessions {
+            if exempt.contains(&normalize_process_name(&session.process_name)) {
+                continue;
+            }
+            let billable_end = (now - chrono::Duration::seconds(session.excluded_seconds())).max(session.start_time);
+            if billable_end > today_start {
+                active_periods.push(((session.start_time.max(today_start), billable_end, session.is_concurrent), session.is_social_session));
+            }
+        }
+        time_periods.extend(active_periods.iter().map(|(period, _)| *period));
+        let total_seconds = self.calculate_unique_time_periods(&time_periods);
+
+        let discount = self.get_settings()?.social_session_discount_percent.clamp(0, 100) as i64;
+        if discount == 0 {
+            return Ok(total_seconds);
+        }
+
+        // Time covered only by social play is billed at the discounted rate;
+        // overlapping solo play is still billed in full
+        let mut full_rate_periods = self.get_billable_periods_matching(today_start, None, None, Some(false))?;
+        full_rate_periods.extend(active_periods.iter().filter(|(_, is_social)| !is_social).map(|(period, _)| *period));
+        let social_only_seconds = total_seconds - self.calculate_unique_time_periods(&full_rate_periods);
+
+        Ok(total_seconds - social_only_seconds * discount / 100)
+    }
+
+    // Returns false when no saved session has this id
+    pub fn set_session_social(&self, id: &str, is_social: bool) -> SqlResult<bool> {
+        let updated = self.conn.execute(
+            "UPDATE sessions SET is_social_session = ?1 WHERE id = ?2",
+            params![is_social, id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Today's finished play of one game, by process
+    pub fn get_today_usage_for_game(&self, process_name: &str) -> SqlResult<i32> {
+        let time_periods = self.get_billable_periods_matching(self.current_day_start(), None, Some(process_name), None)?;
+        Ok((self.calculate_unique_time_periods(&time_periods) / 60) as i32)
+    }
+
+    // A limit of 0 removes the game's limit
+    pub fn set_game_limit(&self, process_name: &str, daily_limit_minutes: i32) -> SqlResult<()> {
+        if daily_limit_minutes <= 0 {
+            self.conn.execute("DELETE FROM game_limits WHERE process_name = ?1", [process_name])?;
+            info!("Removed daily limit for {}", process_name);
+        } else {
+            self.conn.execute(
+                "INSERT INTO game_limits (process_name, daily_limit_minutes) VALUES (?1, ?2)
+                 ON CONFLICT(process_name) DO UPDATE SET daily_limit_minutes = excluded.daily_limit_minutes",
+                params![process_name, daily_limit_minutes],
+            )?;
+            info!("Daily limit for {} set to {} minutes", process_name, daily_limit_minutes);
+        }
+        Ok(())
+    }
+
+    pub fn get_game_limits(&self) -> SqlResult<Vec<GameLimit>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT process_name, daily_limit_minutes FROM game_limits ORDER BY process_name"
+        )?;
+
+        let limit_iter = stmt.query_map([], |row| {
+            Ok(GameLimit {
+                process_name: row.get(0)?,
+                daily_limit_minutes: row.get(1)?,
+            })
+        })?;
+
+        let mut limits = Vec::new();
+        for limit in limit_iter {
+            limits.push(limit?);
+        }
+
+        Ok(limits)
+    }
+
+    pub fn log_enforcement(&self, game_name: &str, reason: EnforcementReason) -> SqlResult<EnforcementEntry> {
+        let entry = EnforcementEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            game_name: game_name.to_string(),
+            reason,
+        };
+        self.conn.execute(
+            "INSERT INTO enforcement_log (id, timestamp, game_name, reason) VALUES (?1, ?2, ?3, ?4)",
+            params![entry.id, entry.timestamp.to_rfc3339(), entry.game_name, reason.as_str()],
+        )?;
+        Ok(entry)
+    }
+
+    // Newest first
+    pub fn get_enforcement_log(&self, limit: usize) -> SqlResult<Vec<EnforcementEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, game_name, reason FROM enforcement_log
+             ORDER BY timestamp DESC
+             LIMIT ?1"
+        )?;
+
+        let entry_iter = stmt.query_map([limit], Self::enforcement_entry_from_row)?;
+
+        let mut entries = Vec::new();
+        for entry in entry_iter {
+            entries.push(entry?);
+        }
+
+        Ok(entries)
+    }
+
+    // Newest first
+    pub fn get_enforcement_log_since(&self, since: DateTime<Utc>) -> SqlResult<Vec<EnforcementEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, game_name, reason FROM enforcement_log
+             WHERE timestamp >= ?1
+             ORDER BY timestamp DESC"
+        )?;
+
+        let entry_iter = stmt.query_map([since.to_rfc3339()], Self::enforcement_entry_from_row)?;
+
+        let mut entries = Vec::new();
+        for entry in entry_iter {
+            entries.push(entry?);
+        }
+
+        Ok(entries)
+    }
+
+    fn enforcement_entry_from_row(row: &rusqlite::Row) -> SqlResult<EnforcementEntry> {
+        let timestamp_str: String = row.get(1)?;
+        let reason_str: String = row.get(3)?;
+
+        Ok(EnforcementEntry {
+            id: row.get(0)?,
+            timestamp: DateTime::parse_from_rfc3339(&timestamp_str)
+                .map_err(|_| rusqlite::Error::InvalidColumnType(1, "timestamp".to_string(), rusqlite::types::Type::Text))?
+                .with_timezone(&Utc),
+            game_name: row.get(2)?,
+            reason: reason_str.parse()
+                .map_err(|_| rusqlite::Error::InvalidColumnType(3, "reason".to_string(), rusqlite::types::Type::Text))?,
+        })
+    }
+
+    // Usage since the given time, optionally for a single game
+    pub fn get_usage_minutes_since(&self, since: DateTime<Utc>, game_name: Option<&str>) -> SqlResult<i32> {
+        // For concurrent sessions, we need to calculate overlapping time periods
+        // instead of just summing durations
+        let time_periods = self.get_billable_periods_since(since, game_name)?;
+
+        // Calculate total unique time (handling overlaps for concurrent sessions)
+        let total_seconds = self.calculate_unique_time_periods(&time_periods);
+        Ok((total_seconds / 60) as i32)
+    }
+
+    // Played intervals of finished sessions since the given time. Sessions that
+    // began earlier but ran past it (e.g. across midnight) contribute only the
+    // part after it.
+    fn get_billable_periods_since(&self, since: DateTime<Utc>, game_name: Option<&str>) -> SqlResult<Vec<BillablePeriod>> {
+        self.get_billable_periods_matching(since, game_name, None, None)
+    }
+
+    // Same as get_billable_periods_since, optionally narrowed to one process
+    // and to social or non-social sessions. Exempt games are never billable.
+    fn get_billable_periods_matching(
+        &self,
+        since: DateTime<Utc>,
+        game_name: Option<&str>,
+        process_name: Option<&str>,
+        is_social: Option<bool>,
+    ) -> SqlResult<Vec<BillablePeriod>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT start_time, end_time, duration_seconds, is_concurrent, concurrent_session_ids,
+                    COALESCE(idle_seconds, 0) + COALESCE(locked_seconds, 0), process_name
+             FROM sessions
+             WHERE (start_time >= ?1 OR end_time >= ?1) AND duration_seconds IS NOT NULL
+               AND COALESCE(is_imported, 0) = 0
+               AND (?2 IS NULL OR game_name = ?2)
+               AND (?3 IS NULL OR process_name = ?3)
+               AND (?4 IS NULL OR COALESCE(is_social_session, 0) = ?4)
+               AND profile_id = ?5
+             ORDER BY start_time"
+        )?;
+
+        let sessions_iter = stmt.query_map(params![since.to_rfc3339(), game_name, process_name, is_social, self.profile_id], |row| {
+            let start_time_str: String = row.get(0)?;
+            let end_time_str: Option<String> = row.get(1)?;
+            let duration_seconds: i64 = row.get(2)?;
+            let is_concurrent: bool = row.get(3).unwrap_or(false);
+            let idle_seconds: i64 = row.get(5).unwrap_or(0);
+            let process_name: String = row.get(6)?;
+
+            Ok((
+                DateTime::parse_from_rfc3339(&start_time_str).unwrap().with_timezone(&Utc),
+                end_time_str.and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                    .map(|dt| dt.with_timezone(&Utc)),
+                duration_seconds,
+                is_concurrent,
+                idle_seconds,
+                process_name
+            ))
+        })?;
+
+        let exempt = self.exempt_process_names()?;
+        let mut time_periods = Vec::new();
+          for session_result in sessions_iter {
+              let (start_time, end_time, _duration_seconds, is_concurrent, idle_seconds, process_name) = session_result?;
+              if exempt.contains(&normalize_process_name(&process_name)) {
+                  continue;
+              }
+              if let Some(end_time) = end_time {
+                  // Idle and locked time is trimmed from the end of the period so it isn't billed
+                  let billable_end = (end_time - chrono::Duration::seconds(idle_seconds)).max(start_time);
+                  if billable_end > since {
+                      time_periods.push((start_time.max(since), billable_end, is_concurrent));
+                  }
+              }
+          }
+
+        Ok(time_periods)
+    }
+
+    // How many buckets timeseries_buckets would return, at most, worked out
+    // without building them so an oversized range is refused cheaply
+    pub fn timeseries_bucket_count(granularity: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Option<i64> {
+        let step = match granularity {
+            "hour" => chrono::Duration::hours(1),
+            "day" => chrono::Duration::days(1),
+            "week" => chrono::Duration::weeks(1),
+            _ => return None,
+        };
+        let span = (to - from).num_seconds().max(0);
+        // One more for the first bucket being aligned down to the clock
+        Some((span + step.num_seconds() - 1) / step.num_seconds() + 1)
+    }
+
+    // Local-time bucket starts for a chart, covering [from, to). The first bucket
+    // is aligned down to the granularity so buckets line up with the clock.
+    pub fn timeseries_buckets(granularity: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Option<Vec<DateTime<Utc>>> {
+        let local_from = from.with_timezone(&Local).naive_local();
+        let (mut cursor, step) = match granularity {
+            "hour" => (local_from.date().and_hms_opt(local_from.hour(), 0, 0)?, chrono::Duration::hours(1)),
+            "day" => (local_from.date().and_hms_opt(0, 0, 0)?, chrono::Duration::days(1)),
+            "week" => {
+                let monday = local_from.date() - chrono::Duration::days(local_from.weekday().num_days_from_monday() as i64);
+                (monday.and_hms_opt(0, 0, 0)?, chrono::Duration::weeks(1))
+            },
+            _ => return None,
+        };
+
+        let mut buckets = Vec::new();
+        loop {
+            let start = cursor.and_local_timezone(Local).earliest()?.with_timezone(&Utc);
+            if start >= to {
+                break;
+            }
+            buckets.push(start);
+            cursor += step;
+        }
+
+        Some(buckets)
+    }
+
+    // Zero-filled chart data for one metric over uniform buckets
+    pub fn get_timeseries(&self, metric: &str, granularity: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> SqlResult<Vec<TimeseriesPoint>> {
+        let starts = Self::timeseries_buckets(granularity, from, to).unwrap_or_default();
+        let first = match starts.first() {
+            Some(&first) => first,
+            None => return Ok(Vec::new()),
+        };
+        let ranges: Vec<(DateTime<Utc>, DateTime<Utc>)> = starts.iter().enumerate()
+            .map(|(i, &start)| (start, starts.get(i + 1).copied().unwrap_or(to)))
+            .collect();
+        let bucket_of = |time: DateTime<Utc>| ranges.iter().position(|(start, end)| time >= *start && time < *end);
+
+        let mut values = vec![0i64; ranges.len()];
+        match metric {
+            "used_minutes" => {
+                // Sessions running into the first bucket are already clipped to it
+                let periods = self.get_billable_periods_since(first, None)?;
+                for (value, (start, end)) in values.iter_mut().zip(&ranges) {
+                    let sliced: Vec<_> = periods.iter()
+                        .filter(|(period_start, period_end, _)| period_start < end && period_end > start)
+                        .map(|(period_start, period_end, is_concurrent)| {
+                            ((*period_start).max(*start), (*period_end).min(*end), *is_concurrent)
+                        })
+                        .collect();
+                    *value = self.calculate_unique_time_periods(&sliced) / 60;
+                }
+            },
+            "earned_minutes" => {
+                for activity in self.get_learning_activities_since(first)? {
+                    if activity.is_voided {
+                        continue;
+                    }
+                    if let Some(index) = bucket_of(activity.timestamp) {
+                        values[index] += activity.earned_gaming_minutes as i64;
+                    }
+                }
+            },
+            "session_count" => {
+                for session in self.get_sessions_since(first)? {
+                    if session.is_imported {
+                        continue;
+                    }
+                    if let Some(index) = bucket_of(session.start_time) {
+                        values[index] += 1;
+                    }
+                }
+            },
+            _ => {}
+        }
+
+        Ok(ranges.into_iter().zip(values)
+            .map(|((bucket_start, _), value)| TimeseriesPoint { bucket_start, value })
+            .collect())
+    }
+
+    // Helper method to calculate unique time periods, handling concurrent sessions
+    fn calculate_unique_time_periods(&self, periods: &[BillablePeriod]) -> i64 {
+        if periods.is_empty() {
+            return 0;
+        }
+
+        // Sort periods by start time
+        let mut sorted_periods = periods.to_vec();
+        sorted_periods.sort_by_key(|(start, _, _)| *start);
+
+        let mut total_seconds = 0i64;
+        let mut current_end: Option<DateTime<Utc>> = None;
 
         for (start, end, _is_concurrent) in sorted_periods {
             match current_end {
@@ -237,97 +1834,1930 @@ impl Database {
                     total_seconds += (end - start).num_seconds();
                     current_end = Some(end);
                 }
-                Some(prev_end) => {
-                    if start >= prev_end {
-                        // No overlap, add full duration
-                        total_seconds += (end - start).num_seconds();
-                        current_end = Some(end);
-                    } else if end > prev_end {
-                        // Partial overlap, add only the non-overlapping part
-                        total_seconds += (end - prev_end).num_seconds();
-                        current_end = Some(end);
-                    }
-                    // If end <= prev_end, this period is completely contained, add nothing
+                Some(prev_end) => {
+                    if start >= prev_end {
+                        // No overlap, add full duration
+                        total_seconds += (end - start).num_seconds();
+                        current_end = Some(end);
+                    } else if end > prev_end {
+                        // Partial overlap, add only the non-overlapping part
+                        total_seconds += (end - prev_end).num_seconds();
+                        current_end = Some(end);
+                    }
+                    // If end <= prev_end, this period is completely contained, add nothing
+                }
+            }
+        }
+
+        total_seconds
+    }
+
+    pub fn get_budget_status(&self) -> SqlResult<BudgetStatus> {
+        let settings = self.get_settings()?;
+        let used_today = self.get_today_usage_minutes()?;
+        let rollover = self.get_rollover_minutes()?;
+        let earned = self.get_earned_minutes_today()?;
+        let bonus = self.get_grant_minutes(false)?;
+        let recurring_bonus = self.get_grant_minutes(true)?;
+
+        let (debt_repayment, debt) = self.get_debt_minutes()?;
+        let allowance = self.get_allowance_for(self.current_day())?;
+
+        let mut budget = BudgetStatus::new(allowance);
+        budget.rollover_minutes = rollover;
+        budget.earned_minutes = earned;
+        budget.bonus_minutes = bonus;
+        budget.recurring_bonus_minutes = recurring_bonus;
+        // Repayment can't take more than the day's base allowance
+        budget.debt_repayment_minutes = debt_repayment.min(allowance);
+        budget.debt_minutes = debt;
+        budget.debt_capacity_minutes = (settings.max_debt_minutes - debt).max(0);
+        budget.update_usage(used_today);
+
+        Ok(budget)
+    }
+
+    // How many of the requested gaming minutes today's earning cap still allows
+    pub fn cap_earned_minutes(&self, requested_minutes: i32) -> SqlResult<i32> {
+        self.cap_earned_minutes_on(self.current_day(), requested_minutes, None)
+    }
+
+    // Same as cap_earned_minutes for any budget day, leaving out one activity
+    // so an edited entry isn't counted against itself
+    fn cap_earned_minutes_on(&self, day: NaiveDate, requested_minutes: i32, excluding_id: Option<&str>) -> SqlResult<i32> {
+        let cap = self.get_settings()?.max_earned_minutes_per_day;
+        if cap <= 0 || requested_minutes <= 0 {
+            return Ok(requested_minutes);
+        }
+
+        let earned: i32 = self.conn.query_row(
+            "SELECT COALESCE(SUM(earned_gaming_minutes), 0) FROM learning_activities
+             WHERE timestamp >= ?1 AND timestamp < ?2 AND COALESCE(is_voided, 0) = 0
+               AND (?3 IS NULL OR id != ?3) AND profile_id = ?4",
+            params![self.day_start(day).to_rfc3339(), self.day_start(day + chrono::Duration::days(1)).to_rfc3339(), excluding_id, self.profile_id],
+            |row| row.get(0),
+        )?;
+
+        Ok(requested_minutes.min((cap - earned).max(0)))
+    }
+
+    // Learning time needed to earn a gaming goal today, flagged when the goal is
+    // more than today's earning cap has left
+    pub fn learning_plan_for(&self, game_minutes: i32, activity_type: &str) -> SqlResult<LearningPlan> {
+        let game_minutes = game_minutes.max(0);
+        let earn_ratio = self.get_earn_ratio(activity_type)?;
+        let earnable_today_minutes = self.cap_earned_minutes(game_minutes)?;
+        Ok(LearningPlan {
+            game_minutes,
+            learning_minutes: LearningActivity::minutes_needed_to_play(game_minutes, earn_ratio),
+            earnable_today_minutes,
+            exceeds_cap: earnable_today_minutes < game_minutes,
+        })
+    }
+
+    // What the requested minutes would be worth if logged now. Only reads, and
+    // add_learning_activity goes through it so a preview matches what's saved.
+    pub fn learning_credit_for(&self, requested_minutes: i32) -> SqlResult<LearningCredit> {
+        let granted_minutes = self.cap_earned_minutes(requested_minutes)?;
+        Ok(LearningCredit {
+            requested_minutes,
+            granted_minutes,
+            capped: granted_minutes < requested_minutes,
+        })
+    }
+
+    // Records a learning activity, trimming its earned minutes to the daily cap
+    pub fn add_learning_activity(&self, activity: &mut LearningActivity) -> SqlResult<LearningCredit> {
+        let credit = self.learning_credit_for(activity.earned_gaming_minutes)?;
+        activity.earned_gaming_minutes = credit.granted_minutes;
+        self.insert_learning_activity(activity, &self.profile_id)?;
+
+        if credit.capped {
+            info!("Daily earning cap reached: granted {} of {} minutes", credit.granted_minutes, credit.requested_minutes);
+        }
+
+        Ok(credit)
+    }
+
+    fn insert_learning_activity(&self, activity: &LearningActivity, profile_id: &str) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT INTO learning_activities (id, activity_type, description, duration_minutes, earned_gaming_minutes, timestamp, profile_id, is_voided)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                activity.id,
+                activity.activity_type,
+                activity.description,
+                activity.duration_minutes,
+                activity.earned_gaming_minutes,
+                activity.timestamp.to_rfc3339(),
+                profile_id,
+                activity.is_voided
+            ],
+        )?;
+
+        info!("Learning activity added: {} minutes of {}", activity.duration_minutes, activity.activity_type);
+        Ok(())
+    }
+
+    fn learning_activity_from_row(row: &rusqlite::Row) -> SqlResult<LearningActivity> {
+        let timestamp_str: String = row.get(5)?;
+
+        Ok(LearningActivity {
+            id: row.get(0)?,
+            activity_type: row.get(1)?,
+            description: row.get(2)?,
+            duration_minutes: row.get(3)?,
+            earned_gaming_minutes: row.get(4)?,
+            timestamp: DateTime::parse_from_rfc3339(&timestamp_str)
+                .map_err(|_| rusqlite::Error::InvalidColumnType(5, "timestamp".to_string(), rusqlite::types::Type::Text))?
+                .with_timezone(&Utc),
+            is_voided: row.get(6).unwrap_or(false),
+        })
+    }
+
+    pub fn get_learning_activities_since(&self, since: DateTime<Utc>) -> SqlResult<Vec<LearningActivity>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM learning_activities
+             WHERE timestamp >= ?1 AND profile_id = ?2
+             ORDER BY timestamp",
+            LEARNING_COLUMNS
+        ))?;
+
+        let activity_iter = stmt.query_map(params![since.to_rfc3339(), self.profile_id], Self::learning_activity_from_row)?;
+
+        let mut activities = Vec::new();
+        for activity in activity_iter {
+            activities.push(activity?);
+        }
+
+        Ok(activities)
+    }
+
+    // Learning activities logged within [start, end); either bound may be left open
+    pub fn get_learning_between(&self, start: Option<DateTime<Utc>>, end: Option<DateTime<Utc>>) -> SqlResult<Vec<LearningActivity>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM learning_activities
+             WHERE (?1 IS NULL OR timestamp >= ?1) AND (?2 IS NULL OR timestamp < ?2) AND profile_id = ?3
+             ORDER BY timestamp",
+            LEARNING_COLUMNS
+        ))?;
+
+        let activity_iter = stmt.query_map(
+            params![start.map(|t| t.to_rfc3339()), end.map(|t| t.to_rfc3339()), self.profile_id],
+            Self::learning_activity_from_row,
+        )?;
+
+        let mut activities = Vec::new();
+        for activity in activity_iter {
+            activities.push(activity?);
+        }
+
+        Ok(activities)
+    }
+
+    // Most recent activities first, for reviewing and correcting entries
+    pub fn get_learning_activities(&self, limit: usize) -> SqlResult<Vec<LearningActivity>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM learning_activities
+             WHERE profile_id = ?2
+             ORDER BY timestamp DESC
+             LIMIT ?1",
+            LEARNING_COLUMNS
+        ))?;
+
+        let activity_iter = stmt.query_map(params![limit, self.profile_id], Self::learning_activity_from_row)?;
+
+        let mut activities = Vec::new();
+        for activity in activity_iter {
+            activities.push(activity?);
+        }
+
+        Ok(activities)
+    }
+
+    pub fn get_learning_activity(&self, id: &str) -> SqlResult<Option<LearningActivity>> {
+        self.conn.query_row(
+            &format!("SELECT {} FROM learning_activities WHERE id = ?1 AND profile_id = ?2", LEARNING_COLUMNS),
+            [id, &self.profile_id],
+            Self::learning_activity_from_row,
+        ).optional()
+    }
+
+    // Corrects an entry's duration and description. Earned minutes are worked out
+    // again from the activity type's current ratio and the cap for its day.
+    pub fn update_learning_activity(&self, id: &str, duration_minutes: i32, description: &str) -> Result<LearningActivity, String> {
+        if duration_minutes <= 0 {
+            return Err("Duration must be positive".to_string());
+        }
+
+        let mut activity = self.get_learning_activity(id).map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("No learning activity with id {}", id))?;
+
+        let earn_ratio = self.get_earn_ratio(&activity.activity_type).map_err(|e| e.to_string())?;
+        let day = Self::budget_day_of(activity.timestamp.with_timezone(&Local).naive_local(), self.day_reset_hour());
+        activity.duration_minutes = duration_minutes;
+        activity.description = description.to_string();
+        activity.earned_gaming_minutes = self.cap_earned_minutes_on(day, LearningActivity::earned_minutes_for(duration_minutes, earn_ratio), Some(id))
+            .map_err(|e| e.to_string())?;
+
+        self.conn.execute(
+            "UPDATE learning_activities SET duration_minutes = ?1, description = ?2, earned_gaming_minutes = ?3
+             WHERE id = ?4 AND profile_id = ?5",
+            params![activity.duration_minutes, activity.description, activity.earned_gaming_minutes, id, self.profile_id],
+        ).map_err(|e| e.to_string())?;
+
+        info!("Learning activity {} updated: {} minutes now earn {}", id, duration_minutes, activity.earned_gaming_minutes);
+        Ok(activity)
+    }
+
+    pub fn delete_learning_activity(&self, id: &str) -> Result<(), String> {
+        let deleted = self.conn.execute("DELETE FROM learning_activities WHERE id = ?1 AND profile_id = ?2", [id, &self.profile_id])
+            .map_err(|e| e.to_string())?;
+
+        if deleted == 0 {
+            return Err(format!("No learning activity with id {}", id));
+        }
+
+        info!("Learning activity {} deleted", id);
+        Ok(())
+    }
+
+    // Compact, self-explanatory summary of today for sharing
+    pub fn export_today_report(&self) -> SqlResult<serde_json::Value> {
+        let today_start = self.current_day_start();
+        let sessions = self.get_sessions_since(today_start)?;
+        let learning = self.get_learning_activities_since(today_start)?;
+        let budget = self.get_budget_status()?;
+
+        let mut game_names: Vec<&str> = sessions.iter()
+            .filter(|session| !session.is_imported)
+            .map(|session| session.game_name.as_str())
+            .collect();
+        game_names.sort();
+        game_names.dedup();
+
+        let mut per_game = Vec::new();
+        for game_name in game_names {
+            let session_count = sessions.iter().filter(|session| session.game_name == game_name).count();
+            per_game.push(serde_json::json!({
+                "game_name": game_name,
+                "minutes": self.get_usage_minutes_since(today_start, Some(game_name))?,
+                "sessions": session_count,
+            }));
+        }
+
+        Ok(serde_json::json!({
+            "date": self.current_day().to_string(),
+            "generated_at": Utc::now().to_rfc3339(),
+            "allowance": {
+                "base_minutes": budget.daily_allowance_minutes,
+                "rollover_minutes": budget.rollover_minutes,
+                "earned_minutes": budget.earned_minutes,
+                "bonus_minutes": budget.bonus_minutes,
+                "recurring_bonus_minutes": budget.recurring_bonus_minutes,
+                "debt_repayment_minutes": budget.debt_repayment_minutes,
+                "total_available_minutes": budget.total_available_minutes,
+            },
+            "budget": budget,
+            "per_game": per_game,
+            "sessions": sessions,
+            "learning": learning,
+        }))
+    }
+
+    // Everything the weekly report shows, for the last seven days with today last
+    pub fn get_weekly_report(&self) -> SqlResult<WeeklyReport> {
+        let daily_totals = self.get_daily_totals(7)?;
+        let from = daily_totals.first()
+            .map(|(date, _)| Self::local_midnight(*date))
+            .unwrap_or_else(|| Self::local_midnight(Local::now().date_naive()));
+
+        let earned_by_day: HashMap<NaiveDate, i32> = self.get_timeseries("earned_minutes", "day", from, Utc::now())?
+            .into_iter()
+            .map(|point| (point.bucket_start.with_timezone(&Local).date_naive(), point.value as i32))
+            .collect();
+        let within_budget: HashMap<NaiveDate, bool> = self.days_within_budget(daily_totals.len() as i64 - 1)?
+            .into_iter()
+            .collect();
+
+        let weekday_allowances = self.get_weekday_allowances()?;
+        let daily_allowance = self.get_settings()?.daily_allowance_minutes;
+        let days = daily_totals.into_iter()
+            .map(|(date, used_minutes)| ReportDay {
+                date,
+                used_minutes,
+                allowance_minutes: weekday_allowances.get(&date.weekday().num_days_from_sunday())
+                    .copied()
+                    .unwrap_or(daily_allowance),
+                earned_minutes: earned_by_day.get(&date).copied().unwrap_or(0),
+                within_budget: within_budget.get(&date).copied(),
+            })
+            .collect();
+
+        let top_games = self.get_report_games_since(from)?;
+
+        let profile_name = self.get_profiles()?.into_iter()
+            .find(|profile| profile.is_active)
+            .map(|profile| profile.name)
+            .unwrap_or_default();
+
+        Ok(WeeklyReport {
+            generated_at: Utc::now(),
+            profile_name,
+            earned_minutes: self.get_earned_minutes_since(from)?,
+            enforcement_events: self.get_enforcement_log_since(from)?,
+            days_within_budget: within_budget.values().filter(|within| **within).count() as i32,
+            finished_days: within_budget.len() as i32,
+            days,
+            top_games,
+        })
+    }
+
+    // Minutes and session counts per game since the given time, most played
+    // first. One pass over the sessions, with overlaps merged within each game.
+    fn get_report_games_since(&self, since: DateTime<Utc>) -> SqlResult<Vec<ReportGame>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT game_name, start_time, end_time, duration_seconds,
+                    COALESCE(idle_seconds, 0) + COALESCE(locked_seconds, 0), process_name
+             FROM sessions
+             WHERE (start_time >= ?1 OR end_time >= ?1)
+               AND COALESCE(is_imported, 0) = 0
+               AND profile_id = ?2
+             ORDER BY game_name, start_time"
+        )?;
+        let rows = stmt.query_map(params![since.to_rfc3339(), self.profile_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+                row.get::<_, Option<i64>>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?;
+
+        let exempt = self.exempt_process_names()?;
+        let mut by_game: BTreeMap<String, (Vec<BillablePeriod>, i32)> = BTreeMap::new();
+        for row in rows {
+            let (game_name, start_time, end_time, duration_seconds, excluded_seconds, process_name) = row?;
+            let start_time = match DateTime::parse_from_rfc3339(&start_time) {
+                Ok(time) => time.with_timezone(&Utc),
+                Err(_) => continue,
+            };
+            let (periods, session_count) = by_game.entry(game_name).or_default();
+            if start_time >= since {
+                *session_count += 1;
+            }
+
+            // Same billing rules as get_billable_periods_matching
+            let end_time = end_time.and_then(|time| DateTime::parse_from_rfc3339(&time).ok());
+            if let (Some(end_time), Some(_)) = (end_time, duration_seconds) {
+                if exempt.contains(&normalize_process_name(&process_name)) {
+                    continue;
+                }
+                let billable_end = (end_time.with_timezone(&Utc) - chrono::Duration::seconds(excluded_seconds)).max(start_time);
+                if billable_end > since {
+                    periods.push((start_time.max(since), billable_end, false));
+                }
+            }
+        }
+
+        let mut games: Vec<ReportGame> = by_game.into_iter()
+            .filter(|(_, (_, session_count))| *session_count > 0)
+            .map(|(game_name, (periods, session_count))| ReportGame {
+                game_name,
+                minutes: (self.calculate_unique_time_periods(&periods) / 60) as i32,
+                session_count,
+            })
+            .collect();
+        games.sort_by(|a, b| b.minutes.cmp(&a.minutes).then_with(|| a.game_name.cmp(&b.game_name)));
+        Ok(games)
+    }
+
+    fn get_earned_minutes_today(&self) -> SqlResult<i32> {
+        self.get_earned_minutes_since(self.current_day_start())
+    }
+
+    fn get_earned_minutes_since(&self, since: DateTime<Utc>) -> SqlResult<i32> {
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(SUM(earned_gaming_minutes), 0) FROM learning_activities
+             WHERE timestamp >= ?1 AND COALESCE(is_voided, 0) = 0 AND profile_id = ?2"
+        )?;
+
+        stmt.query_row(params![since.to_rfc3339(), self.profile_id], |row| row.get(0))
+    }
+
+    // Start of the current "day", "week" (from Monday) or "month"
+    pub fn period_start(period: &str) -> Option<DateTime<Utc>> {
+        let today = Local::now().date_naive();
+        let start = match period {
+            "day" => today,
+            "week" => today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64),
+            "month" => today.with_day(1)?,
+            _ => return None,
+        };
+
+        Some(Self::local_midnight(start))
+    }
+
+    // Whether learning is "paying for" gaming over the period
+    pub fn get_earn_vs_use(&self, period: &str) -> SqlResult<EarnUseStats> {
+        let since = Self::period_start(period).unwrap_or_else(|| Self::local_midnight(Local::now().date_naive()));
+        let earned = self.get_earned_minutes_since(since)?;
+        let used = self.get_usage_minutes_since(since, None)?;
+
+        Ok(EarnUseStats::new(period.to_string(), earned, used))
+    }
+
+    // Minutes played on each of the last `days` local days, today last. Each day
+    // merges overlapping sessions, and days without play are zero.
+    pub fn get_daily_totals(&self, days: i32) -> SqlResult<Vec<(NaiveDate, i32)>> {
+        let today = Local::now().date_naive();
+        let first = today - chrono::Duration::days(days.max(1) as i64 - 1);
+        let from = Self::local_midnight(first);
+
+        let points = self.get_timeseries("used_minutes", "day", from, Utc::now())?;
+        Ok(points.into_iter()
+            .map(|point| (point.bucket_start.with_timezone(&Local).date_naive(), point.value as i32))
+            .collect())
+    }
+
+    // Daily rollup over the last week (7 days) or month (30 days)
+    pub fn get_usage_stats(&self, period: &str) -> SqlResult<UsageStats> {
+        let days = if period == "month" { 30 } else { 7 };
+        let daily_totals = self.get_daily_totals(days)?;
+        let since = daily_totals.first()
+            .map(|(date, _)| Self::local_midnight(*date))
+            .unwrap_or_else(|| Self::local_midnight(Local::now().date_naive()));
+        let earned = self.get_earned_minutes_since(since)?;
+
+        Ok(UsageStats::new(period.to_string(), daily_totals, earned))
+    }
+
+    // Lifetime totals per game, most played first. Sessions from the debug
+    // tools can be left out so they don't end up on the leaderboard.
+    pub fn get_game_totals(&self, exclude_debug: bool) -> SqlResult<Vec<GameTotal>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT game_name, COUNT(*), MIN(start_time), MAX(end_time), AVG(duration_seconds)
+             FROM sessions
+             WHERE duration_seconds IS NOT NULL AND end_time IS NOT NULL
+               AND COALESCE(is_imported, 0) = 0
+               AND (?1 = 0 OR COALESCE(detection_source, '') != 'debug')
+               AND profile_id = ?2
+             GROUP BY game_name"
+        )?;
+        let rows = stmt.query_map(params![exclude_debug, self.profile_id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, f64>(4)?,
+            ))
+        })?;
+
+        let mut totals = Vec::new();
+        for row in rows {
+            let (game_name, session_count, first_played, last_played, average_seconds) = row?;
+            let first_played = match DateTime::parse_from_rfc3339(&first_played) {
+                Ok(time) => time.with_timezone(&Utc),
+                Err(_) => continue,
+            };
+            let last_played = DateTime::parse_from_rfc3339(&last_played)
+                .map(|time| time.with_timezone(&Utc))
+                .unwrap_or(first_played);
+
+            let periods = self.get_billable_periods_since(first_played, Some(&game_name))?;
+            totals.push(GameTotal {
+                total_minutes: self.calculate_unique_time_periods(&periods) / 60,
+                game_name,
+                session_count,
+                first_played,
+                last_played,
+                average_session_minutes: (average_seconds / 60.0).round() as i64,
+            });
+        }
+
+        totals.sort_by(|a, b| b.total_minutes.cmp(&a.total_minutes).then_with(|| a.game_name.cmp(&b.game_name)));
+        Ok(totals)
+    }
+
+    // Counts sessions in the period by length, using bucket edges in minutes
+    pub fn get_session_length_histogram(&self, period: &str, edges: &[i32]) -> SqlResult<Vec<HistogramBin>> {
+        let since = Self::period_start(period).unwrap_or_else(|| Self::local_midnight(Local::now().date_naive()));
+        let mut bins = HistogramBin::from_edges(edges);
+
+        for session in self.get_sessions_since(since)? {
+            if session.is_imported {
+                continue;
+            }
+
+            let minutes = (session.duration_seconds.unwrap_or(0) / 60) as i32;
+            if let Some(bin) = bins.iter_mut().find(|bin| bin.max_minutes.is_none_or(|max| minutes < max)) {
+                bin.count += 1;
+            }
+        }
+
+        Ok(bins)
+    }
+
+    fn get_rollover_minutes(&self) -> SqlResult<i32> {
+        let now = Utc::now();
+
+        // Clean up expired rollover entries
+        self.conn.execute(
+            "DELETE FROM budget_rollover WHERE expires_at < ?1",
+            [now.to_rfc3339()],
+        )?;
+
+        // Sum remaining rollover minutes
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(SUM(unused_minutes), 0) FROM budget_rollover
+             WHERE expires_at >= ?1 AND profile_id = ?2"
+        )?;
+
+        stmt.query_row(params![now.to_rfc3339(), self.profile_id], |row| row.get(0))
+    }
+
+    pub fn add_rollover(&self, date: &str, unused_minutes: i32, expires_at: DateTime<Utc>) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO budget_rollover (profile_id, date, unused_minutes, expires_at)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![self.profile_id, date, unused_minutes, expires_at.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    // Carries yesterday's unused base allowance forward for `rollover_days`.
+    // Earned, bonus and rolled-over minutes never carry over, and each day is
+    // only finalized once per profile. Only the active profile is finalized;
+    // the others catch up when they're next made active. Returns the minutes rolled over, or None when
+    // yesterday was already handled.
+    pub fn finalize_day_rollover(&self) -> SqlResult<Option<i32>> {
+        let today_start = self.current_day_start();
+        let yesterday = self.current_day() - chrono::Duration::days(1);
+        let date = yesterday.to_string();
+
+        let last_date: Option<String> = self.conn.query_row(
+            "SELECT last_rollover_date FROM profiles WHERE id = ?1",
+            [&self.profile_id],
+            |row| row.get(0),
+        ).optional()?.flatten();
+        if last_date.as_deref() >= Some(date.as_str()) {
+            return Ok(None);
+        }
+
+        // Debt dated before today has been repaid
+        self.conn.execute(
+            "DELETE FROM budget_debt WHERE date < ?1 AND profile_id = ?2",
+            params![self.current_day().to_string(), self.profile_id],
+        )?;
+
+        let settings = self.get_settings()?;
+        let yesterday_start = self.day_start(yesterday);
+
+        // Only yesterday's share of each period, up to today's start
+        let periods: Vec<_> = self.get_billable_periods_since(yesterday_start, None)?.into_iter()
+            .filter(|(start, _, _)| *start < today_start)
+            .map(|(start, end, is_concurrent)| (start, end.min(today_start), is_concurrent))
+            .collect();
+        let used = (self.calculate_unique_time_periods(&periods) / 60) as i32;
+        let allowance = self.get_allowance_for(yesterday)?;
+
+        // The day before was finalized while the app ran yesterday. Without that,
+        // or any play recorded yesterday, nobody was tracking: on a first run or
+        // after days with the app closed, there is no unused time to carry over.
+        let day_before = (yesterday - chrono::Duration::days(1)).to_string();
+        let tracked = last_date.is_some() && (last_date.as_deref() == Some(day_before.as_str()) || !periods.is_empty());
+        let rolled_over = if settings.rollover_days > 0 && tracked {
+            (allowance - used).max(0)
+        } else {
+            0
+        };
+
+        if rolled_over > 0 {
+            let expires_at = Utc::now() + chrono::Duration::days(settings.rollover_days as i64);
+            self.add_rollover(&date, rolled_over, expires_at)?;
+        }
+        self.conn.execute(
+            "UPDATE profiles SET last_rollover_date = ?1 WHERE id = ?2",
+            params![date, self.profile_id],
+        )?;
+
+        info!("Finalized {}: {} of {} minutes used, {} rolled over", date, used,
+              allowance, rolled_over);
+        Ok(Some(rolled_over))
+    }
+
+    pub fn add_grant(&self, grant: &Grant) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT INTO temporary_grants (id, grant_type, minutes, reason, created_at, expires_at, profile_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                grant.id,
+                grant.grant_type,
+                grant.minutes,
+                grant.reason,
+                grant.created_at.to_rfc3339(),
+                grant.expires_at.to_rfc3339(),
+                self.profile_id
+            ],
+        )?;
+
+        info!("Grant added: {} minutes ({})", grant.minutes, grant.grant_type);
+        Ok(())
+    }
+
+    fn purge_expired_grants(&self, now: DateTime<Utc>) -> SqlResult<()> {
+        self.conn.execute(
+            "DELETE FROM temporary_grants WHERE expires_at < ?1",
+            [now.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    // Sums active grants, either only those from recurring boosts or all others
+    fn get_grant_minutes(&self, recurring: bool) -> SqlResult<i32> {
+        let now = Utc::now();
+        self.purge_expired_grants(now)?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT COALESCE(SUM(minutes), 0) FROM temporary_grants
+             WHERE expires_at >= ?1 AND (grant_type = 'recurring') = ?2 AND profile_id = ?3"
+        )?;
+
+        stmt.query_row(params![now.to_rfc3339(), recurring, self.profile_id], |row| row.get(0))
+    }
+
+    pub fn add_recurring_grant(&self, rule: &RecurringGrant) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT INTO recurring_grants (id, weekday, minutes, expires_at_time, reason, profile_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![rule.id, rule.weekday, rule.minutes, rule.expires_at_time, rule.reason, self.profile_id],
+        )?;
+
+        info!("Recurring grant added: {} minutes on weekday {}", rule.minutes, rule.weekday);
+        Ok(())
+    }
+
+    pub fn remove_recurring_grant(&self, id: &str) -> SqlResult<bool> {
+        let removed = self.conn.execute("DELETE FROM recurring_grants WHERE id = ?1 AND profile_id = ?2", [id, &self.profile_id])?;
+        Ok(removed > 0)
+    }
+
+    pub fn get_recurring_grants(&self) -> SqlResult<Vec<RecurringGrant>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, weekday, minutes, expires_at_time, reason FROM recurring_grants
+             WHERE profile_id = ?1
+             ORDER BY weekday"
+        )?;
+
+        let rule_iter = stmt.query_map([&self.profile_id], |row| {
+            Ok(RecurringGrant {
+                id: row.get(0)?,
+                weekday: row.get(1)?,
+                minutes: row.get(2)?,
+                expires_at_time: row.get(3)?,
+                reason: row.get(4)?,
+            })
+        })?;
+
+        let mut rules = Vec::new();
+        for rule in rule_iter {
+            rules.push(rule?);
+        }
+
+        Ok(rules)
+    }
+
+    // Turns the active profile's recurring rules for today into grants. Each rule
+    // records the date it was last applied so restarts don't grant it twice.
+    pub fn apply_recurring_grants(&self) -> SqlResult<()> {
+        let today = self.current_day();
+        let weekday = today.weekday().num_days_from_sunday();
+        let end_of_day = self.day_start(today + chrono::Duration::days(1));
+
+        for rule in self.get_recurring_grants()? {
+            if rule.weekday != weekday {
+                continue;
+            }
+
+            let expires_at = rule.expires_at_time.as_deref()
+                .and_then(|time| NaiveTime::parse_from_str(time, "%H:%M").ok())
+                .map(|time| Self::local_to_utc(today.and_time(time)))
+                .unwrap_or(end_of_day);
+
+            // Claiming the day and adding the grant happen together, so a failed
+            // insert leaves the rule to be applied again on the next tick
+            self.conn.execute_batch("BEGIN")?;
+            let result = self.conn.execute(
+                "UPDATE recurring_grants SET last_applied_date = ?1
+                 WHERE id = ?2 AND COALESCE(last_applied_date, '') != ?1",
+                params![today.to_string(), rule.id],
+            ).and_then(|claimed| {
+                if claimed == 0 {
+                    return Ok(());
+                }
+                self.add_grant(&Grant::new("recurring".to_string(), rule.minutes, rule.reason.clone(), expires_at))
+            });
+            match result {
+                Ok(()) => self.conn.execute_batch("COMMIT")?,
+                Err(e) => {
+                    let _ = self.conn.execute_batch("ROLLBACK");
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn get_active_grants(&self) -> SqlResult<Vec<Grant>> {
+        let now = Utc::now();
+        self.purge_expired_grants(now)?;
+
+        let mut stmt = self.conn.prepare(
+            "SELECT id, grant_type, minutes, reason, created_at, expires_at
+             FROM temporary_grants
+             WHERE expires_at >= ?1 AND profile_id = ?2
+             ORDER BY expires_at"
+        )?;
+
+        let grant_iter = stmt.query_map(params![now.to_rfc3339(), self.profile_id], |row| {
+            let created_at_str: String = row.get(4)?;
+            let expires_at_str: String = row.get(5)?;
+
+            Ok(Grant {
+                id: row.get(0)?,
+                grant_type: row.get(1)?,
+                minutes: row.get(2)?,
+                reason: row.get(3)?,
+                created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(4, "created_at".to_string(), rusqlite::types::Type::Text))?
+                    .with_timezone(&Utc),
+                expires_at: DateTime::parse_from_rfc3339(&expires_at_str)
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(5, "expires_at".to_string(), rusqlite::types::Type::Text))?
+                    .with_timezone(&Utc),
+            })
+        })?;
+
+        let mut grants = Vec::new();
+        for grant in grant_iter {
+            grants.push(grant?);
+        }
+
+        Ok(grants)
+    }
+
+    // Returns false when no grant with the given id exists
+    pub fn revoke_grant(&self, id: &str) -> SqlResult<bool> {
+        let borrowed: Option<i32> = self.conn.query_row(
+            "SELECT minutes FROM temporary_grants WHERE id = ?1 AND grant_type IN ('borrow', 'overtime') AND profile_id = ?2",
+            params![id, self.profile_id],
+            |row| row.get(0),
+        ).ok();
+
+        let removed = self.conn.execute(
+            "DELETE FROM temporary_grants WHERE id = ?1 AND profile_id = ?2",
+            [id, &self.profile_id],
+        )?;
+
+        // Revoking borrowed or overtime minutes also cancels the matching repayment
+        if let Some(minutes) = borrowed {
+            self.conn.execute(
+                "UPDATE budget_debt SET minutes = MAX(minutes - ?1, 0) WHERE date = ?2 AND profile_id = ?3",
+                params![minutes, self.tomorrow().to_string(), self.profile_id],
+            )?;
+        }
+
+        if removed > 0 {
+            info!("Grant revoked: {}", id);
+        }
+        Ok(removed > 0)
+    }
+
+    fn tomorrow(&self) -> NaiveDate {
+        self.current_day() + chrono::Duration::days(1)
+    }
+
+    // Returns (minutes being repaid today, minutes still owed on future days)
+    fn get_debt_minutes(&self) -> SqlResult<(i32, i32)> {
+        let today = self.current_day().to_string();
+
+        let repayment: i32 = self.conn.query_row(
+            "SELECT COALESCE(SUM(minutes), 0) FROM budget_debt WHERE date = ?1 AND profile_id = ?2",
+            [&today, &self.profile_id],
+            |row| row.get(0),
+        )?;
+        let owed: i32 = self.conn.query_row(
+            "SELECT COALESCE(SUM(minutes), 0) FROM budget_debt WHERE date > ?1 AND profile_id = ?2",
+            [&today, &self.profile_id],
+            |row| row.get(0),
+        )?;
+
+        Ok((repayment, owed))
+    }
+
+    // Grants time today that is deducted from tomorrow's allowance. Fails with
+    // an explanatory message when it would exceed max_debt_minutes.
+    pub fn borrow_from_tomorrow(&self, minutes: i32) -> Result<Grant, String> {
+        self.add_debt_grant("borrow", "Borrowed from tomorrow", minutes)
+    }
+
+    // Lets play go on past zero once today's budget is used up. The overtime is
+    // owed like borrowed time and counts toward the same debt limit.
+    pub fn grant_overtime(&self, minutes: i32) -> Result<Grant, String> {
+        let remaining = self.get_budget_status().map_err(|e| e.to_string())?.remaining_today_minutes;
+        if remaining > 0 {
+            return Err(format!("Overtime is only available once today's time is used up ({} minutes left)", remaining));
+        }
+        self.add_debt_grant("overtime", "Overtime", minutes)
+    }
+
+    fn add_debt_grant(&self, grant_type: &str, reason: &str, minutes: i32) -> Result<Grant, String> {
+        let settings = self.get_settings().map_err(|e| e.to_string())?;
+        let (_, owed) = self.get_debt_minutes().map_err(|e| e.to_string())?;
+
+        if owed + minutes > settings.max_debt_minutes {
+            return Err(format!(
+                "Another {} minutes of debt would exceed the limit of {} minutes ({} available)",
+                minutes, settings.max_debt_minutes, (settings.max_debt_minutes - owed).max(0)
+            ));
+        }
+
+        let tomorrow = self.tomorrow();
+        let expires_at = self.day_start(tomorrow);
+        let grant = Grant::new(grant_type.to_string(), minutes, reason.to_string(), expires_at);
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result = self.add_grant(&grant).and_then(|_| {
+            self.conn.execute(
+                "INSERT INTO budget_debt (profile_id, date, minutes) VALUES (?1, ?2, ?3)
+                 ON CONFLICT(profile_id, date) DO UPDATE SET minutes = minutes + excluded.minutes",
+                params![self.profile_id, tomorrow.to_string(), minutes],
+            )
+        });
+
+        match result {
+            Ok(_) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("{}: {} minutes owed from tomorrow", reason, minutes);
+                Ok(grant)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Only one request waits at a time, so the overlay can't be used to flood a
+    // parent with them
+    pub fn add_time_request(&self, minutes: i32, reason: &str) -> Result<TimeRequest, String> {
+        if !(1..=240).contains(&minutes) {
+            return Err("Requests must be for 1 to 240 minutes".to_string());
+        }
+        let pending: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM time_requests WHERE status = 'pending' AND profile_id = ?1",
+            [&self.profile_id],
+            |row| row.get(0),
+        ).map_err(|e| e.to_string())?;
+        if pending > 0 {
+            return Err("A request is already waiting for a parent".to_string());
+        }
+
+        let request = TimeRequest {
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now(),
+            minutes,
+            reason: reason.trim().to_string(),
+            status: "pending".to_string(),
+            profile_id: self.profile_id.clone(),
+        };
+        self.conn.execute(
+            "INSERT INTO time_requests (id, timestamp, minutes, reason, status, profile_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![request.id, request.timestamp.to_rfc3339(), request.minutes, request.reason, request.status, request.profile_id],
+        ).map_err(|e| e.to_string())?;
+
+        info!("Extra time requested: {} minutes", minutes);
+        Ok(request)
+    }
+
+    fn time_request_from_row(row: &rusqlite::Row) -> SqlResult<TimeRequest> {
+        let timestamp_str: String = row.get(1)?;
+
+        Ok(TimeRequest {
+            id: row.get(0)?,
+            timestamp: DateTime::parse_from_rfc3339(&timestamp_str)
+                .map_err(|_| rusqlite::Error::InvalidColumnType(1, "timestamp".to_string(), rusqlite::types::Type::Text))?
+                .with_timezone(&Utc),
+            minutes: row.get(2)?,
+            reason: row.get(3)?,
+            status: row.get(4)?,
+            profile_id: row.get(5)?,
+        })
+    }
+
+    // Every profile's, oldest first, so a parent can answer them without switching
+    pub fn get_pending_requests(&self) -> SqlResult<Vec<TimeRequest>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, timestamp, minutes, reason, status, profile_id FROM time_requests
+             WHERE status = 'pending'
+             ORDER BY timestamp"
+        )?;
+
+        let request_iter = stmt.query_map([], Self::time_request_from_row)?;
+
+        let mut requests = Vec::new();
+        for request in request_iter {
+            requests.push(request?);
+        }
+
+        Ok(requests)
+    }
+
+    // Approved minutes are credited to the profile that asked, like earned time,
+    // but skip the daily earning cap since a parent chose to give them
+    pub fn resolve_time_request(&self, id: &str, approve: bool) -> Result<TimeRequest, String> {
+        let mut request = self.conn.query_row(
+            "SELECT id, timestamp, minutes, reason, status, profile_id FROM time_requests WHERE id = ?1 AND status = 'pending'",
+            [id],
+            Self::time_request_from_row,
+        ).optional().map_err(|e| e.to_string())?
+            .ok_or_else(|| format!("No pending request with id {}", id))?;
+        request.status = if approve { "approved" } else { "denied" }.to_string();
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result = self.conn.execute(
+            "UPDATE time_requests SET status = ?1 WHERE id = ?2",
+            params![request.status, id],
+        ).and_then(|_| {
+            if !approve {
+                return Ok(());
+            }
+            self.insert_learning_activity(&LearningActivity {
+                id: Some(uuid::Uuid::new_v4().to_string()),
+                activity_type: "time_request".to_string(),
+                description: if request.reason.is_empty() {
+                    "Approved extra time request".to_string()
+                } else {
+                    format!("Approved extra time request: {}", request.reason)
+                },
+                duration_minutes: 0,
+                earned_gaming_minutes: request.minutes,
+                timestamp: Utc::now(),
+                is_voided: false,
+            }, &request.profile_id)
+        });
+
+        match result {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Extra time request {} {}: {} minutes", id, request.status, request.minutes);
+                Ok(request)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Inserts the built-in games only on first run so user deletions stick
+    pub fn seed_default_games(&self, defaults: &[GameConfig]) -> SqlResult<()> {
+        let already_seeded: bool = self.conn.query_row(
+            "SELECT COUNT(*) FROM settings WHERE key = 'seeded_defaults' AND value = 'true'",
+            [],
+            |row| row.get::<_, i64>(0),
+        )? > 0;
+
+        if already_seeded {
+            return Ok(());
+        }
+
+        self.reseed_default_games(defaults)?;
+        self.update_setting("seeded_defaults", "true")?;
+        info!("Seeded {} default games", defaults.len());
+        Ok(())
+    }
+
+    // Restores any missing built-in games without overwriting user edits
+    pub fn reseed_default_games(&self, defaults: &[GameConfig]) -> SqlResult<()> {
+        for game in defaults {
+            self.conn.execute(
+                "INSERT OR IGNORE INTO custom_games (process_name, display_name, is_monitored, notify_on_limit, is_launcher)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![game.process_name, game.display_name, game.is_monitored, game.notify_on_limit, game.is_launcher],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_custom_games(&self) -> SqlResult<Vec<GameConfig>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT process_name, display_name, is_monitored, notify_on_limit, path_pattern, is_launcher, title_pattern, kind, is_exempt FROM custom_games
+             ORDER BY display_name"
+        )?;
+
+        let game_iter = stmt.query_map([], |row| {
+            let path_pattern: String = row.get(4)?;
+            let title_pattern: String = row.get(6)?;
+            Ok(GameConfig {
+                process_name: row.get(0)?,
+                display_name: row.get(1)?,
+                is_monitored: row.get(2)?,
+                notify_on_limit: row.get(3).unwrap_or(true),
+                path_pattern: if path_pattern.is_empty() { None } else { Some(path_pattern) },
+                is_launcher: row.get::<_, Option<bool>>(5)?.unwrap_or(false),
+                title_pattern: if title_pattern.is_empty() { None } else { Some(title_pattern) },
+                kind: row.get::<_, String>(7)?.parse().unwrap_or_default(),
+                is_exempt: row.get::<_, Option<bool>>(8)?.unwrap_or(false),
+            })
+        })?;
+
+        let mut games = Vec::new();
+        for game in game_iter {
+            games.push(game?);
+        }
+
+        Ok(games)
+    }
+
+    // Adds a game, or renames it when the process (and path) is already known
+    // Returns false when the same entry already exists with the other match
+    // kind; an exact name is never silently turned into a pattern or back
+    pub fn save_custom_game(&self, game: &GameConfig) -> SqlResult<bool> {
+        let saved = self.conn.execute(
+            "INSERT INTO custom_games (process_name, display_name, is_monitored, notify_on_limit, path_pattern, is_launcher, title_pattern, kind)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT(process_name, path_pattern, title_pattern) DO UPDATE SET
+                display_name = excluded.display_name,
+                is_monitored = excluded.is_monitored,
+                is_launcher = excluded.is_launcher
+             WHERE custom_games.kind = excluded.kind",
+            params![game.process_name, game.display_name, game.is_monitored, game.notify_on_limit,
+                    game.path_pattern.as_deref().unwrap_or(""), game.is_launcher,
+                    game.title_pattern.as_deref().unwrap_or(""), game.kind.as_str()],
+        )?;
+        if saved == 0 {
+            return Ok(false);
+        }
+        info!("Saved custom game: {} ({})", game.display_name, game.process_name);
+        Ok(true)
+    }
+
+    // Without a path or title pattern every entry for the process is removed
+    pub fn remove_custom_game(&self, process_name: &str, path_pattern: Option<&str>, title_pattern: Option<&str>) -> SqlResult<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM custom_games WHERE process_name = ?1 AND (?2 IS NULL OR path_pattern = ?2)
+               AND (?3 IS NULL OR title_pattern = ?3)",
+            params![process_name, path_pattern, title_pattern],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // Returns false when the game isn't a known game
+    pub fn set_game_notification_pref(&self, process_name: &str, notify_on_limit: bool) -> SqlResult<bool> {
+        let updated = self.conn.execute(
+            "UPDATE custom_games SET notify_on_limit = ?2 WHERE process_name = ?1",
+            params![process_name, notify_on_limit],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Only exact entries can be exempt, since billing matches sessions on their
+    // process name. Returns false when the game isn't a known exact entry.
+    pub fn set_game_exempt(&self, process_name: &str, exempt: bool) -> SqlResult<bool> {
+        let updated = self.conn.execute(
+            "UPDATE custom_games SET is_exempt = ?2 WHERE process_name = ?1 AND kind = 'exact'",
+            params![process_name, exempt],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // Normalized process names of games that don't count against the budget
+    fn exempt_process_names(&self) -> SqlResult<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT process_name FROM custom_games WHERE COALESCE(is_exempt, FALSE) AND kind = 'exact'"
+        )?;
+
+        let names = stmt.query_map([], |row| row.get::<_, String>(0))?
+            .map(|name| name.map(|name| normalize_process_name(&name)))
+            .collect::<SqlResult<HashSet<String>>>()?;
+        Ok(names)
+    }
+
+    // User-added blacklist entries, normalized
+    pub fn get_blacklist(&self) -> SqlResult<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT process_name FROM blacklisted_processes ORDER BY process_name"
+        )?;
+
+        let entry_iter = stmt.query_map([], |row| row.get(0))?;
+
+        let mut entries = Vec::new();
+        for entry in entry_iter {
+            entries.push(entry?);
+        }
+
+        Ok(entries)
+    }
+
+    // Stores the normalized name and returns it. The tracker itself can't be
+    // blacklisted.
+    pub fn add_blacklist_entry(&self, process_name: &str) -> Result<String, String> {
+        let key = normalize_process_name(process_name);
+        if key.is_empty() {
+            return Err("A process name is required".to_string());
+        }
+        if GameMonitor::own_process_name().as_deref() == Some(key.as_str()) {
+            return Err("The tracker's own process can't be blacklisted".to_string());
+        }
+
+        self.conn.execute(
+            "INSERT OR IGNORE INTO blacklisted_processes (process_name, created_at) VALUES (?1, ?2)",
+            params![key, Utc::now().to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+
+        info!("Blacklisted process: {}", key);
+        Ok(key)
+    }
+
+    // Returns false when the process wasn't on the user's blacklist
+    pub fn remove_blacklist_entry(&self, process_name: &str) -> SqlResult<bool> {
+        let removed = self.conn.execute(
+            "DELETE FROM blacklisted_processes WHERE process_name = ?1",
+            [normalize_process_name(process_name)],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // Steam games whose guessed name is still waiting for the user to confirm it
+    pub fn get_pending_game_names(&self) -> SqlResult<Vec<PendingGameName>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT process_name, game_name, COUNT(*) FROM sessions
+             WHERE game_name LIKE ?1
+             GROUP BY process_name, game_name
+             ORDER BY game_name"
+        )?;
+
+        let pending_iter = stmt.query_map([format!("{}%", UNCONFIRMED_NAME_PREFIX)], |row| {
+            Ok(PendingGameName {
+                process_name: row.get(0)?,
+                provisional_name: row.get(1)?,
+                session_count: row.get(2)?,
+            })
+        })?;
+
+        let mut pending = Vec::new();
+        for name in pending_iter {
+            pending.push(name?);
+        }
+
+        Ok(pending)
+    }
+
+    // Saves the confirmed name as a known game and back-fills every session
+    // recorded under the provisional name. Returns the number of sessions renamed.
+    pub fn confirm_game_name(&self, process_name: &str, display_name: &str) -> SqlResult<usize> {
+        self.conn.execute(
+            "INSERT INTO custom_games (process_name, display_name, is_monitored)
+             VALUES (?1, ?2, TRUE)
+             ON CONFLICT(process_name, path_pattern, title_pattern) DO UPDATE SET display_name = excluded.display_name",
+            params![process_name, display_name],
+        )?;
+
+        let renamed = self.conn.execute(
+            "UPDATE sessions SET game_name = ?2
+             WHERE process_name = ?1 AND game_name LIKE ?3",
+            params![process_name, display_name, format!("{}%", UNCONFIRMED_NAME_PREFIX)],
+        )?;
+
+        info!("Confirmed game name {} for {} ({} sessions renamed)", display_name, process_name, renamed);
+        Ok(renamed)
+    }
+
+    fn week_start() -> DateTime<Utc> {
+        Self::period_start("week").unwrap_or_else(|| Self::local_midnight(Local::now().date_naive()))
+    }
+
+    pub fn add_game_goal(&self, goal: &GameGoal) -> SqlResult<()> {
+        self.conn.execute(
+            "INSERT INTO game_goals (id, game_name, goal_type, target_minutes)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![goal.id, goal.game_name, goal.goal_type, goal.target_minutes],
+        )?;
+
+        info!("Game goal added: {} {} {} minutes/week", goal.game_name, goal.goal_type, goal.target_minutes);
+        Ok(())
+    }
+
+    pub fn remove_game_goal(&self, id: &str) -> SqlResult<bool> {
+        let removed = self.conn.execute("DELETE FROM game_goals WHERE id = ?1", [id])?;
+        Ok(removed > 0)
+    }
+
+    pub fn get_game_goals(&self) -> SqlResult<Vec<GameGoal>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, game_name, goal_type, target_minutes FROM game_goals
+             ORDER BY game_name"
+        )?;
+
+        let goal_iter = stmt.query_map([], |row| {
+            Ok(GameGoal {
+                id: row.get(0)?,
+                game_name: row.get(1)?,
+                goal_type: row.get(2)?,
+                target_minutes: row.get(3)?,
+            })
+        })?;
+
+        let mut goals = Vec::new();
+        for goal in goal_iter {
+            goals.push(goal?);
+        }
+
+        Ok(goals)
+    }
+
+    pub fn get_game_goal_progress(&self) -> SqlResult<Vec<GameGoalProgress>> {
+        let week_start = Self::week_start();
+
+        let mut progress = Vec::new();
+        for goal in self.get_game_goals()? {
+            let minutes = self.get_usage_minutes_since(week_start, Some(&goal.game_name))?;
+            progress.push(GameGoalProgress::new(goal, minutes));
+        }
+
+        Ok(progress)
+    }
+
+    // Returns goals that became met or breached since last checked, recording
+    // them so each outcome is only reported once per week
+    pub fn take_goal_alerts(&self) -> SqlResult<Vec<GameGoalProgress>> {
+        let week = Self::week_start().to_rfc3339();
+        let mut alerts = Vec::new();
+
+        for progress in self.get_game_goal_progress()? {
+            if progress.status != "met" && progress.status != "breached" {
+                continue;
+            }
+
+            let updated = self.conn.execute(
+                "UPDATE game_goals SET notified_status = ?1, notified_week = ?2
+                 WHERE id = ?3 AND NOT (COALESCE(notified_status, '') = ?1 AND COALESCE(notified_week, '') = ?2)",
+                params![progress.status, week, progress.goal.id],
+            )?;
+
+            if updated > 0 {
+                alerts.push(progress);
+            }
+        }
+
+        Ok(alerts)
+    }
+
+    // Record highs derived from the whole tracked history. Only finished days
+    // count toward the streak so today can't break it halfway through.
+    pub fn get_personal_bests(&self) -> SqlResult<PersonalBests> {
+        self.personal_bests(true)
+    }
+
+    // The break still running grows every minute, so alerts leave it out and
+    // only celebrate a break once play resumes
+    fn personal_bests(&self, include_running_break: bool) -> SqlResult<PersonalBests> {
+        let mut bests = PersonalBests::default();
+
+        let history_start = match self.history_start()? {
+            Some(start) => start,
+            None => return Ok(bests),
+        };
+
+        let (_, longest_streak) = Self::streaks(&self.days_within_budget(Self::history_days(history_start))?);
+        bests.longest_under_budget_streak_days = longest_streak;
+
+        let mut learning_by_day: HashMap<NaiveDate, i32> = HashMap::new();
+        for activity in self.get_learning_activities_since(history_start)? {
+            if activity.duration_minutes > 0 && !activity.is_voided {
+                *learning_by_day.entry(activity.timestamp.with_timezone(&Local).date_naive()).or_default()
+                    += activity.duration_minutes;
+            }
+        }
+        bests.most_learning_minutes_in_day = learning_by_day.values().copied().max().unwrap_or(0);
+
+        // Gaps between merged play periods, plus the break still running now
+        let mut periods = self.get_billable_periods_since(history_start, None)?;
+        periods.sort_by_key(|(start, _, _)| *start);
+        let mut last_end: Option<DateTime<Utc>> = None;
+        for (start, end, _) in periods {
+            if let Some(previous_end) = last_end {
+                bests.longest_break_minutes = bests.longest_break_minutes.max((start - previous_end).num_minutes());
+            }
+            last_end = Some(last_end.map_or(end, |previous_end| previous_end.max(end)));
+        }
+        if let Some(previous_end) = last_end.filter(|_| include_running_break) {
+            bests.longest_break_minutes = bests.longest_break_minutes.max((Utc::now() - previous_end).num_minutes());
+        }
+
+        Ok(bests)
+    }
+
+    // Finished days from the one history starts on up to yesterday
+    fn history_days(history_start: DateTime<Utc>) -> i64 {
+        (Local::now().date_naive() - history_start.with_timezone(&Local).date_naive()).num_days()
+    }
+
+    // Whether each of the last `days` finished days stayed within that day's
+    // allowance, weekday overrides included, plus what was earned on it. Oldest
+    // first; a day with no play is within budget.
+    pub fn days_within_budget(&self, days: i64) -> SqlResult<Vec<(NaiveDate, bool)>> {
+        if days <= 0 {
+            return Ok(Vec::new());
+        }
+
+        let today = Local::now().date_naive();
+        let today_start = Self::local_midnight(today);
+        let from = Self::local_midnight(today - chrono::Duration::days(days));
+        let used = self.get_timeseries("used_minutes", "day", from, today_start)?;
+        let earned = self.get_timeseries("earned_minutes", "day", from, today_start)?;
+        let weekday_allowances = self.get_weekday_allowances()?;
+        let default_allowance = self.get_settings()?.daily_allowance_minutes;
+
+        Ok(used.iter().zip(&earned)
+            .map(|(used_day, earned_day)| {
+                let date = used_day.bucket_start.with_timezone(&Local).date_naive();
+                let allowance = weekday_allowances.get(&date.weekday().num_days_from_sunday())
+                    .copied()
+                    .unwrap_or(default_allowance) as i64;
+                (date, used_day.value <= allowance + earned_day.value)
+            })
+            .collect())
+    }
+
+    // (current streak ending on the last day, longest streak) of days within budget
+    fn streaks(days: &[(NaiveDate, bool)]) -> (i32, i32) {
+        let mut streak = 0;
+        let mut longest = 0;
+        for (_, within_budget) in days {
+            streak = if *within_budget { streak + 1 } else { 0 };
+            longest = longest.max(streak);
+        }
+        (streak, longest)
+    }
+
+    // Only finished days count, so today can't break the streak halfway through
+    pub fn get_adherence_stats(&self) -> SqlResult<AdherenceStats> {
+        let days = match self.history_start()? {
+            Some(start) => self.days_within_budget(Self::history_days(start))?,
+            None => Vec::new(),
+        };
+        let (current_streak_days, longest_streak_days) = Self::streaks(&days);
+
+        Ok(AdherenceStats {
+            current_streak_days,
+            longest_streak_days,
+            over_budget_days_last_30: days.iter().rev().take(30).filter(|(_, within_budget)| !within_budget).count() as i32,
+        })
+    }
+
+    // When tracking started for the active profile: its first session or
+    // learning activity
+    fn history_start(&self) -> SqlResult<Option<DateTime<Utc>>> {
+        let first_session: Option<String> = self.conn.query_row(
+            "SELECT MIN(start_time) FROM sessions WHERE COALESCE(is_imported, 0) = 0 AND profile_id = ?1",
+            [&self.profile_id],
+            |row| row.get(0),
+        )?;
+        let first_activity: Option<String> = self.conn.query_row(
+            "SELECT MIN(timestamp) FROM learning_activities WHERE profile_id = ?1",
+            [&self.profile_id],
+            |row| row.get(0),
+        )?;
+
+        Ok(first_session.into_iter().chain(first_activity)
+            .filter_map(|time| DateTime::parse_from_rfc3339(&time).ok())
+            .map(|time| time.with_timezone(&Utc))
+            .min())
+    }
+
+    // Recomputes personal bests and returns a message for each record broken
+    // since the last check, granting any configured achievement bonus. The
+    // first check only records a baseline.
+    pub fn take_personal_best_alerts(&self) -> SqlResult<Vec<String>> {
+        let bests = self.personal_bests(false)?;
+        let previous: Option<PersonalBests> = self.conn.query_row(
+            "SELECT value FROM settings WHERE key = 'personal_bests'",
+            [],
+            |row| row.get::<_, String>(0),
+        ).optional()?.and_then(|json| serde_json::from_str(&json).ok());
+
+        if previous.as_ref() == Some(&bests) {
+            return Ok(Vec::new());
+        }
+
+        let json = serde_json::to_string(&bests).unwrap_or_default();
+        self.update_setting("personal_bests", &json)?;
+
+        let records = previous.map(|previous| bests.records_broken(&previous)).unwrap_or_default();
+        let mut messages = Vec::new();
+        for (milestone, value, message) in records {
+            match self.grant_achievement_bonus(milestone, value)? {
+                Some(minutes) => messages.push(format!("{} You earned {} bonus minutes.", message, minutes)),
+                None => messages.push(message),
+            }
+        }
+
+        Ok(messages)
+    }
+
+    // Built-in earn ratios with the configured ones laid over them
+    pub fn get_earn_ratios(&self) -> SqlResult<HashMap<String, i32>> {
+        let configured: Option<HashMap<String, i32>> = self.conn.query_row(
+            "SELECT value FROM settings WHERE key = 'earn_ratios'",
+            [],
+            |row| row.get::<_, String>(0),
+        ).optional()?.and_then(|json| serde_json::from_str(&json).ok());
+
+        let mut ratios = LearningActivity::default_earn_ratios();
+        ratios.extend(configured.unwrap_or_default());
+        Ok(ratios)
+    }
+
+    // Replaces the configured ratios; every divisor must be at least 1
+    pub fn set_earn_ratios(&self, ratios: &HashMap<String, i32>) -> Result<(), String> {
+        if let Some((activity_type, ratio)) = ratios.iter().find(|(_, ratio)| **ratio < 1) {
+            return Err(format!("Invalid earn ratio for {}: {}", activity_type, ratio));
+        }
+        if ratios.keys().any(|activity_type| activity_type.trim().is_empty()) {
+            return Err("Activity type cannot be empty".to_string());
+        }
+
+        let json = serde_json::to_string(ratios).map_err(|e| e.to_string())?;
+        self.update_setting("earn_ratios", &json).map_err(|e| e.to_string())?;
+        info!("Earn ratios updated: {:?}", ratios);
+        Ok(())
+    }
+
+    pub fn get_curfew_windows(&self) -> SqlResult<Vec<CurfewWindow>> {
+        let configured: Option<Vec<CurfewWindow>> = self.conn.query_row(
+            "SELECT value FROM settings WHERE key = 'curfew_windows'",
+            [],
+            |row| row.get::<_, String>(0),
+        ).optional()?.and_then(|json| serde_json::from_str(&json).ok());
+
+        Ok(configured.unwrap_or_default())
+    }
+
+    // Replaces every curfew window; an empty list lifts the curfew
+    pub fn set_curfew_windows(&self, windows: &[CurfewWindow]) -> Result<(), String> {
+        for window in windows {
+            if window.weekday > 6 {
+                return Err(format!("Invalid weekday: {} (expected 0 (Sunday) to 6 (Saturday))", window.weekday));
+            }
+            let start = NaiveTime::parse_from_str(&window.start_hm, "%H:%M")
+                .map_err(|_| format!("Invalid curfew start: {} (expected HH:MM)", window.start_hm))?;
+            let end = NaiveTime::parse_from_str(&window.end_hm, "%H:%M")
+                .map_err(|_| format!("Invalid curfew end: {} (expected HH:MM)", window.end_hm))?;
+            if start == end {
+                return Err(format!("Curfew window {}-{} is empty", window.start_hm, window.end_hm));
+            }
+        }
+
+        let json = serde_json::to_string(windows).map_err(|e| e.to_string())?;
+        self.update_setting("curfew_windows", &json).map_err(|e| e.to_string())?;
+        info!("Curfew windows updated: {} windows", windows.len());
+        Ok(())
+    }
+
+    // Salted SHA-256 of a PIN, as lowercase hex
+    fn hash_pin(salt: &str, pin: &str) -> String {
+        Sha256::digest(format!("{}:{}", salt, pin).as_bytes()).iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    // Stored as "salt$hash"; None until a parent sets a PIN
+    fn get_parent_pin_hash(&self) -> SqlResult<Option<String>> {
+        self.conn.query_row(
+            "SELECT value FROM settings WHERE key = 'parent_pin_hash'",
+            [],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    pub fn has_parent_pin(&self) -> SqlResult<bool> {
+        Ok(self.get_parent_pin_hash()?.is_some())
+    }
+
+    // With no PIN set, every caller is treated as the parent
+    pub fn verify_parent_pin(&self, pin: Option<&str>) -> SqlResult<bool> {
+        let stored = match self.get_parent_pin_hash()? {
+            Some(stored) => stored,
+            None => return Ok(true),
+        };
+
+        let (salt, hash) = match stored.split_once('$') {
+            Some(parts) => parts,
+            None => return Ok(false),
+        };
+        Ok(pin.is_some_and(|pin| Self::hash_pin(salt, pin) == hash))
+    }
+
+    // The old PIN is only needed once one has been set
+    pub fn set_parent_pin(&self, old_pin: Option<&str>, new_pin: &str) -> Result<(), String> {
+        if self.has_parent_pin().map_err(|e| e.to_string())? && !self.verify_parent_pin(old_pin).map_err(|e| e.to_string())? {
+            return Err("unauthorized".to_string());
+        }
+        if new_pin.len() < 4 || !new_pin.chars().all(|c| c.is_ascii_digit()) {
+            return Err("PIN must be at least 4 digits".to_string());
+        }
+
+        let salt = uuid::Uuid::new_v4().simple().to_string();
+        let stored = format!("{}${}", salt, Self::hash_pin(&salt, new_pin));
+        self.update_setting("parent_pin_hash", &stored).map_err(|e| e.to_string())?;
+        info!("Parent PIN updated");
+        Ok(())
+    }
+
+    // The active profile's overrides of its base allowance, keyed 0 (Sunday) to 6 (Saturday)
+    pub fn get_weekday_allowances(&self) -> SqlResult<HashMap<u32, i32>> {
+        let configured: Option<HashMap<u32, i32>> = self.conn.query_row(
+            "SELECT weekday_allowances FROM profiles WHERE id = ?1",
+            [&self.profile_id],
+            |row| row.get::<_, Option<String>>(0),
+        ).optional()?.flatten().and_then(|json| serde_json::from_str(&json).ok());
+
+        Ok(configured.unwrap_or_default())
+    }
+
+    // Replaces the overrides; an empty map goes back to daily_allowance_minutes every day
+    pub fn set_weekday_allowances(&self, allowances: &HashMap<u32, i32>) -> Result<(), String> {
+        if let Some(weekday) = allowances.keys().find(|weekday| **weekday > 6) {
+            return Err(format!("Invalid weekday: {} (expected 0 (Sunday) to 6 (Saturday))", weekday));
+        }
+        if let Some((weekday, minutes)) = allowances.iter().find(|(_, minutes)| !(0..=1440).contains(*minutes)) {
+            return Err(format!("Invalid allowance for weekday {}: {}", weekday, minutes));
+        }
+
+        let json = serde_json::to_string(allowances).map_err(|e| e.to_string())?;
+        self.conn.execute(
+            "UPDATE profiles SET weekday_allowances = ?1 WHERE id = ?2",
+            params![json, self.profile_id],
+        ).map_err(|e| e.to_string())?;
+        info!("Weekday allowances updated: {:?}", allowances);
+        Ok(())
+    }
+
+    // The base allowance for a given local date
+    pub fn get_allowance_for(&self, date: NaiveDate) -> SqlResult<i32> {
+        let weekday = date.weekday().num_days_from_sunday();
+        match self.get_weekday_allowances()?.get(&weekday) {
+            Some(minutes) => Ok(*minutes),
+            None => Ok(self.get_settings()?.daily_allowance_minutes),
+        }
+    }
+
+    // Learning minutes per gaming minute for one activity type, 1:5 when unknown
+    pub fn get_earn_ratio(&self, activity_type: &str) -> SqlResult<i32> {
+        Ok(self.get_earn_ratios()?.get(activity_type).copied().unwrap_or(DEFAULT_EARN_RATIO))
+    }
+
+    // Grants the milestone's bonus for this record once; returns the minutes granted
+    fn grant_achievement_bonus(&self, milestone: &str, value: i64) -> SqlResult<Option<i32>> {
+        let minutes: Option<i32> = self.conn.query_row(
+            "SELECT minutes FROM achievement_bonuses WHERE milestone = ?1",
+            [milestone],
+            |row| row.get(0),
+        ).optional()?;
+        let minutes = match minutes {
+            Some(minutes) if minutes > 0 => minutes,
+            _ => return Ok(None),
+        };
+
+        let expires_at = self.day_start(self.tomorrow());
+        let grant = Grant::new("achievement".to_string(), minutes,
+                               format!("Achievement: {} ({})", milestone, value), expires_at);
+
+        // The claim and the grant go in together, so a failed insert leaves the
+        // achievement to be rewarded on the next check
+        self.conn.execute_batch("BEGIN")?;
+        let result = self.conn.execute(
+            "INSERT OR IGNORE INTO achievement_grants (achievement, grant_id, granted_at, minutes, profile_id)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![format!("{}:{}", milestone, value), grant.id, grant.created_at.to_rfc3339(),
+                    minutes, self.profile_id],
+        ).and_then(|claimed| {
+            if claimed == 0 {
+                return Ok(false);
+            }
+            self.add_grant(&grant).map(|_| true)
+        });
+        match result {
+            Ok(claimed) => {
+                self.conn.execute_batch("COMMIT")?;
+                Ok(if claimed { Some(minutes) } else { None })
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
+    }
+
+    pub fn set_achievement_bonus(&self, milestone: &str, minutes: i32) -> SqlResult<()> {
+        if minutes <= 0 {
+            self.conn.execute("DELETE FROM achievement_bonuses WHERE milestone = ?1", [milestone])?;
+        } else {
+            self.conn.execute(
+                "INSERT OR REPLACE INTO achievement_bonuses (milestone, minutes) VALUES (?1, ?2)",
+                params![milestone, minutes],
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn get_achievement_bonuses(&self) -> SqlResult<Vec<AchievementBonus>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT milestone, minutes FROM achievement_bonuses ORDER BY milestone"
+        )?;
+
+        let bonus_iter = stmt.query_map([], |row| {
+            Ok(AchievementBonus {
+                milestone: row.get(0)?,
+                minutes: row.get(1)?,
+            })
+        })?;
+
+        let mut bonuses = Vec::new();
+        for bonus in bonus_iter {
+            bonuses.push(bonus?);
+        }
+
+        Ok(bonuses)
+    }
+
+    // Bonuses granted to the active profile, newest first
+    pub fn get_achievement_history(&self) -> SqlResult<Vec<AchievementGrant>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT achievement, grant_id, minutes, granted_at FROM achievement_grants
+             WHERE profile_id = ?1
+             ORDER BY granted_at DESC"
+        )?;
+
+        let grant_iter = stmt.query_map([&self.profile_id], |row| {
+            let granted_at_str: String = row.get(3)?;
+            Ok(AchievementGrant {
+                achievement: row.get(0)?,
+                grant_id: row.get(1)?,
+                minutes: row.get(2)?,
+                granted_at: DateTime::parse_from_rfc3339(&granted_at_str)
+                    .map_err(|_| rusqlite::Error::InvalidColumnType(3, "granted_at".to_string(), rusqlite::types::Type::Text))?
+                    .with_timezone(&Utc),
+            })
+        })?;
+
+        let mut grants = Vec::new();
+        for grant in grant_iter {
+            grants.push(grant?);
+        }
+
+        Ok(grants)
+    }
+
+    pub fn is_steam_history_imported(&self) -> SqlResult<bool> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM settings WHERE key = 'steam_history_imported' AND value = 'true'",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    // Restores history in the shape export_history writes as JSON, into the
+    // active profile. "merge" skips rows whose id already exists; "replace"
+    // first clears the active profile's rows in the time span the file
+    // covers. Bad rows are rejected one by one, but the writes happen in one
+    // transaction so a failure part way leaves the database as it was.
+    pub fn import_history(&self, json: &str, replace: bool) -> Result<HistoryImportSummary, String> {
+        let value: serde_json::Value = serde_json::from_str(json).map_err(|e| format!("Not valid JSON: {}", e))?;
+        let records = |key: &str| -> Result<Vec<serde_json::Value>, String> {
+            match value.get(key) {
+                None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+                Some(serde_json::Value::Array(records)) => Ok(records.clone()),
+                Some(_) => Err(format!("\"{}\" must be a list", key)),
+            }
+        };
+        let session_records = records("sessions")?;
+        let learning_records = records("learning")?;
+
+        let mut summary = HistoryImportSummary::default();
+        let reject = |summary: &mut HistoryImportSummary, problem: String| {
+            summary.rejected += 1;
+            summary.problems.push(problem);
+        };
+
+        let mut sessions = Vec::new();
+        for (index, record) in session_records.into_iter().enumerate() {
+            match serde_json::from_value::<GameSession>(record).map_err(|e| e.to_string())
+                .and_then(Self::validate_imported_session) {
+                Ok(session) => sessions.push(session),
+                Err(problem) => reject(&mut summary, format!("sessions[{}]: {}", index, problem)),
+            }
+        }
+
+        let mut activities = Vec::new();
+        for (index, record) in learning_records.into_iter().enumerate() {
+            match serde_json::from_value::<LearningActivity>(record).map_err(|e| e.to_string())
+                .and_then(Self::validate_imported_activity) {
+                Ok(activity) => activities.push(activity),
+                Err(problem) => reject(&mut summary, format!("learning[{}]: {}", index, problem)),
+            }
+        }
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result: SqlResult<()> = (|| {
+            if replace {
+                let session_span = sessions.iter().map(|session| session.start_time).min()
+                    .zip(sessions.iter().map(|session| session.start_time).max());
+                if let Some((first, last)) = session_span {
+                    self.conn.execute(
+                        "DELETE FROM sessions WHERE start_time >= ?1 AND start_time <= ?2 AND profile_id = ?3",
+                        params![first.to_rfc3339(), last.to_rfc3339(), self.profile_id],
+                    )?;
+                }
+                let learning_span = activities.iter().map(|activity| activity.timestamp).min()
+                    .zip(activities.iter().map(|activity| activity.timestamp).max());
+                if let Some((first, last)) = learning_span {
+                    self.conn.execute(
+                        "DELETE FROM learning_activities WHERE timestamp >= ?1 AND timestamp <= ?2 AND profile_id = ?3",
+                        params![first.to_rfc3339(), last.to_rfc3339(), self.profile_id],
+                    )?;
+                }
+            }
+
+            for session in &sessions {
+                let exists: bool = self.conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM sessions WHERE id = ?1)",
+                    [&session.id],
+                    |row| row.get(0),
+                )?;
+                if exists {
+                    summary.skipped += 1;
+                } else {
+                    let mut session = session.clone();
+                    session.profile_id = self.profile_id.clone();
+                    self.save_session(&session)?;
+                    summary.sessions_imported += 1;
+                }
+            }
+
+            for activity in &activities {
+                let exists: bool = self.conn.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM learning_activities WHERE id = ?1)",
+                    [&activity.id],
+                    |row| row.get(0),
+                )?;
+                if exists {
+                    summary.skipped += 1;
+                } else {
+                    self.insert_learning_activity(activity, &self.profile_id)?;
+                    summary.learning_imported += 1;
                 }
             }
+            Ok(())
+        })();
+
+        match result {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Imported history: {} sessions and {} learning activities ({} skipped, {} rejected)",
+                      summary.sessions_imported, summary.learning_imported, summary.skipped, summary.rejected);
+                Ok(summary)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(format!("Import failed, nothing was imported: {}", e))
+            }
+        }
+    }
+
+    fn validate_imported_session(mut session: GameSession) -> Result<GameSession, String> {
+        if session.id.as_deref().is_none_or(str::is_empty) {
+            return Err("the session has no id".to_string());
+        }
+        if session.game_name.trim().is_empty() {
+            return Err("the game name is empty".to_string());
+        }
+        let end = session.end_time.ok_or("the session never ended")?;
+        if end < session.start_time {
+            return Err("the session ends before it starts".to_string());
+        }
+        if session.duration_seconds.is_none_or(|seconds| seconds < 0) {
+            return Err("the duration is missing or negative".to_string());
+        }
+        if session.idle_seconds < 0 || session.locked_seconds < 0 {
+            return Err("the idle or locked time is negative".to_string());
         }
 
-        total_seconds
+        session.pids.clear();
+        session.last_seen = end;
+        Ok(session)
     }
 
-    pub fn get_budget_status(&self) -> SqlResult<BudgetStatus> {
-        let settings = self.get_settings()?;
-        let used_today = self.get_today_usage_minutes()?;
-        let rollover = self.get_rollover_minutes()?;
-        let earned = self.get_earned_minutes_today()?;
-
-        let mut budget = BudgetStatus::new(settings.daily_allowance_minutes);
-        budget.rollover_minutes = rollover;
-        budget.earned_minutes = earned;
-        budget.update_usage(used_today);
-
-        Ok(budget)
+    fn validate_imported_activity(activity: LearningActivity) -> Result<LearningActivity, String> {
+        if activity.id.as_deref().is_none_or(str::is_empty) {
+            return Err("the activity has no id".to_string());
+        }
+        if activity.duration_minutes < 0 || activity.earned_gaming_minutes < 0 {
+            return Err("the duration or earned minutes are negative".to_string());
+        }
+        Ok(activity)
     }
 
-    pub fn add_learning_activity(&self, activity: &LearningActivity) -> SqlResult<()> {
-        self.conn.execute(
-            "INSERT INTO learning_activities (id, activity_type, description, duration_minutes, earned_gaming_minutes, timestamp)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![
-                activity.id,
-                activity.activity_type,
-                activity.description,
-                activity.duration_minutes,
-                activity.earned_gaming_minutes,
-                activity.timestamp.to_rfc3339()
-            ],
-        )?;
+    // Saves the imported summary sessions and records the Steam ID, all or nothing
+    pub fn save_steam_import(&self, sessions: &[GameSession], steam_id: &str) -> SqlResult<()> {
+        self.conn.execute_batch("BEGIN")?;
 
-        info!("Learning activity added: {} minutes of {}", activity.duration_minutes, activity.activity_type);
-        Ok(())
+        let result = (|| {
+            for session in sessions {
+                self.save_session(session)?;
+            }
+            self.update_setting("steam_id", steam_id)?;
+            self.update_setting("steam_history_imported", "true")
+        })();
+
+        match result {
+            Ok(()) => self.conn.execute_batch("COMMIT"),
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e)
+            }
+        }
     }
 
-    fn get_earned_minutes_today(&self) -> SqlResult<i32> {
-        let today_start = Local::now().date_naive().and_hms_opt(0, 0, 0).unwrap()
-            .and_local_timezone(Local).single().unwrap()
-            .with_timezone(&Utc);
+    // Read-only sanity check for settings that contradict each other or have no effect
+    pub fn validate_configuration(&self) -> SqlResult<Vec<ConfigWarning>> {
+        let settings = self.get_settings()?;
+        let goals = self.get_game_goals()?;
+        let mut warnings = Vec::new();
 
-        let mut stmt = self.conn.prepare(
-            "SELECT COALESCE(SUM(earned_gaming_minutes), 0) FROM learning_activities
-             WHERE timestamp >= ?1"
-        )?;
+        if settings.daily_allowance_minutes > 0 && settings.warning_threshold_minutes >= settings.daily_allowance_minutes {
+            warnings.push(ConfigWarning::new("warning_threshold_minutes", "warning", format!(
+                "The warning threshold ({} min) is at least the daily allowance ({} min), so the warning fires as soon as you start playing",
+                settings.warning_threshold_minutes, settings.daily_allowance_minutes
+            )));
+        }
 
-        stmt.query_row([today_start.to_rfc3339()], |row| row.get(0))
-    }
+        if settings.daily_allowance_minutes == 0 && settings.rollover_days > 0 {
+            warnings.push(ConfigWarning::new("rollover_days", "info",
+                "Rollover has no effect with a daily allowance of 0 minutes, since there is never unused time to carry over".to_string()
+            ));
+        }
 
-    fn get_rollover_minutes(&self) -> SqlResult<i32> {
-        let now = Utc::now();
+        if settings.max_debt_minutes > settings.daily_allowance_minutes {
+            warnings.push(ConfigWarning::new("max_debt_minutes", "warning", format!(
+                "The debt limit ({} min) is larger than the daily allowance ({} min), so borrowing can wipe out an entire day",
+                settings.max_debt_minutes, settings.daily_allowance_minutes
+            )));
+        }
 
-        // Clean up expired rollover entries
-        self.conn.execute(
-            "DELETE FROM budget_rollover WHERE expires_at < ?1",
-            [now.to_rfc3339()],
-        )?;
+        if settings.cpu_idle_threshold > 0.0 && settings.cpu_idle_seconds == 0 {
+            warnings.push(ConfigWarning::new("cpu_idle_seconds", "warning",
+                "CPU idle detection with a 0 second delay treats every momentary dip in CPU usage as idle time".to_string()
+            ));
+        }
 
-        // Sum remaining rollover minutes
-        let mut stmt = self.conn.prepare(
-            "SELECT COALESCE(SUM(unused_minutes), 0) FROM budget_rollover
-             WHERE expires_at >= ?1"
-        )?;
+        let weekly_allowance = settings.daily_allowance_minutes * 7;
+        let mut seen_goals = std::collections::HashSet::new();
+        for goal in &goals {
+            if goal.goal_type == "max" && goal.target_minutes >= weekly_allowance {
+                warnings.push(ConfigWarning::new("game_goals", "info", format!(
+                    "The weekly limit for {} ({} min) is at least the whole weekly allowance ({} min), so it can never be exceeded",
+                    goal.game_name, goal.target_minutes, weekly_allowance
+                )));
+            }
+            if goal.goal_type == "min" && goal.target_minutes > weekly_allowance {
+                warnings.push(ConfigWarning::new("game_goals", "warning", format!(
+                    "The weekly goal for {} ({} min) is more than the weekly allowance ({} min) without earning extra time",
+                    goal.game_name, goal.target_minutes, weekly_allowance
+                )));
+            }
 
-        stmt.query_row([now.to_rfc3339()], |row| row.get(0))
-    }
+            let duplicates = goals.iter()
+                .filter(|other| other.game_name == goal.game_name && other.goal_type == goal.goal_type)
+                .count();
+            if duplicates > 1 && seen_goals.insert((&goal.game_name, &goal.goal_type)) {
+                warnings.push(ConfigWarning::new("game_goals", "info", format!(
+                    "{} has {} \"{}\" goals; only the strictest one matters",
+                    goal.game_name, duplicates, goal.goal_type
+                )));
+            }
+        }
 
-    pub fn add_rollover(&self, date: &str, unused_minutes: i32, expires_at: DateTime<Utc>) -> SqlResult<()> {
-        self.conn.execute(
-            "INSERT OR REPLACE INTO budget_rollover (date, unused_minutes, expires_at)
-             VALUES (?1, ?2, ?3)",
-            params![date, unused_minutes, expires_at.to_rfc3339()],
-        )?;
-        Ok(())
+        Ok(warnings)
     }
 
-    fn get_settings(&self) -> SqlResult<AppSettings> {
+    pub fn get_settings(&self) -> SqlResult<AppSettings> {
         let mut stmt = self.conn.prepare(
             "SELECT key, value FROM settings"
         )?;
@@ -337,6 +3767,35 @@ impl Database {
             rollover_days: 3,
             notifications_enabled: true,
             warning_threshold_minutes: 15,
+            web_game_detection_enabled: false,
+            cpu_idle_threshold: 0.0,
+            cpu_idle_seconds: 300,
+            nag_interval_minutes: 10,
+            quiet_hours: String::new(),
+            max_debt_minutes: 60,
+            log_format: "text".to_string(),
+            detection_mode: "auto".to_string(),
+            confirm_steam_names: false,
+            personal_best_notifications: false,
+            countdown_minutes: 0,
+            trust_startup_detections: false,
+            full_scan_interval_seconds: 1,
+            max_distinct_games_per_day: 0,
+            monitoring_paused: false,
+            enforcement_enabled: true,
+            kill_grace_seconds: 120,
+            idle_threshold_minutes: 10,
+            max_earned_minutes_per_day: 60,
+            notification_cooldown_seconds: 300,
+            warning_thresholds: vec![30, 15, 5, 0],
+            social_session_discount_percent: 0,
+            allow_time_requests: false,
+            encryption_enabled: false,
+            session_merge_seconds: 60,
+            monitor_interval_secs: 1,
+            count_foreground_only: false,
+            day_reset_hour: 0,
+            notification_style: "overlay".to_string(),
         };
 
         let settings_iter = stmt.query_map([], |row| {
@@ -358,37 +3817,345 @@ impl Database {
                 "warning_threshold_minutes" => {
                     settings.warning_threshold_minutes = value.parse().unwrap_or(15);
                 },
+                "web_game_detection_enabled" => {
+                    settings.web_game_detection_enabled = value == "true";
+                },
+                "cpu_idle_threshold" => {
+                    settings.cpu_idle_threshold = value.parse().unwrap_or(0.0);
+                },
+                "cpu_idle_seconds" => {
+                    settings.cpu_idle_seconds = value.parse().unwrap_or(300);
+                },
+                "nag_interval_minutes" => {
+                    settings.nag_interval_minutes = value.parse().unwrap_or(10);
+                },
+                "quiet_hours" => {
+                    settings.quiet_hours = value;
+                },
+                "max_debt_minutes" => {
+                    settings.max_debt_minutes = value.parse().unwrap_or(60);
+                },
+                "detection_mode" => {
+                    settings.detection_mode = value;
+                },
+                "log_format" => {
+                    settings.log_format = value;
+                },
+                "confirm_steam_names" => {
+                    settings.confirm_steam_names = value == "true";
+                },
+                "personal_best_notifications" => {
+                    settings.personal_best_notifications = value == "true";
+                },
+                "countdown_minutes" => {
+                    settings.countdown_minutes = value.parse().unwrap_or(0);
+                },
+                "trust_startup_detections" => {
+                    settings.trust_startup_detections = value == "true";
+                },
+                "full_scan_interval_seconds" => {
+                    settings.full_scan_interval_seconds = value.parse().unwrap_or(1);
+                },
+                "max_distinct_games_per_day" => {
+                    settings.max_distinct_games_per_day = value.parse().unwrap_or(0);
+                },
+                "monitoring_paused" => {
+                    settings.monitoring_paused = value == "true";
+                },
+                "enforcement_enabled" => {
+                    settings.enforcement_enabled = value == "true";
+                },
+                "kill_grace_seconds" => {
+                    settings.kill_grace_seconds = value.parse().unwrap_or(120);
+                },
+                "idle_threshold_minutes" => {
+                    settings.idle_threshold_minutes = value.parse().unwrap_or(10);
+                },
+                "max_earned_minutes_per_day" => {
+                    settings.max_earned_minutes_per_day = value.parse().unwrap_or(60);
+                },
+                "notification_cooldown_seconds" => {
+                    settings.notification_cooldown_seconds = value.parse().unwrap_or(300);
+                },
+                "social_session_discount_percent" => {
+                    settings.social_session_discount_percent = value.parse().unwrap_or(0);
+                },
+                "allow_time_requests" => {
+                    settings.allow_time_requests = value == "true";
+                },
+                "encryption_enabled" => {
+                    settings.encryption_enabled = value == "true";
+                },
+                "session_merge_seconds" => {
+                    settings.session_merge_seconds = value.parse().unwrap_or(60);
+                },
+                "monitor_interval_secs" => {
+                    settings.monitor_interval_secs = value.parse().unwrap_or(1);
+                },
+                "count_foreground_only" => {
+                    settings.count_foreground_only = value == "true";
+                },
+                "day_reset_hour" => {
+                    settings.day_reset_hour = Self::parse_day_reset_hour(&value);
+                },
+                "notification_style" => {
+                    settings.notification_style = value;
+                },
+                "warning_thresholds" => {
+                    if let Some(thresholds) = Self::parse_warning_thresholds(&value) {
+                        settings.warning_thresholds = thresholds;
+                    }
+                },
                 _ => {}
             }
         }
 
+        // The allowance belongs to the active profile rather than to settings
+        if let Some(allowance) = self.conn.query_row(
+            "SELECT daily_allowance_minutes FROM profiles WHERE id = ?1",
+            [&self.profile_id],
+            |row| row.get(0),
+        ).optional()? {
+            settings.daily_allowance_minutes = allowance;
+        }
+
         Ok(settings)
     }
 
     pub fn update_setting(&self, key: &str, value: &str) -> SqlResult<()> {
+        if key == "daily_allowance_minutes" {
+            self.conn.execute(
+                "UPDATE profiles SET daily_allowance_minutes = ?1 WHERE id = ?2",
+                params![value, self.profile_id],
+            )?;
+            return Ok(());
+        }
+
         self.conn.execute(
             "INSERT OR REPLACE INTO settings (key, value, updated_at)
              VALUES (?1, ?2, CURRENT_TIMESTAMP)",
             params![key, value],
         )?;
+
+        if key == "rollover_days" {
+            if let Ok(days) = value.parse::<i64>() {
+                self.reconcile_rollover_expiry(days)?;
+            }
+        }
+
         Ok(())
     }
 
-    // Debug/Development helpers
-    pub fn reset_today_sessions(&self) -> SqlResult<()> {
-        let today_start = Local::now().date_naive().and_hms_opt(0, 0, 0).unwrap()
-            .and_local_timezone(Local).single().unwrap()
-            .with_timezone(&Utc);
+    // Comma-separated minutes such as "30,15,5,0", returned highest first.
+    // An empty value turns the warnings off.
+    pub fn parse_warning_thresholds(value: &str) -> Option<Vec<i32>> {
+        let mut thresholds = Vec::new();
+        for part in value.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+            let minutes = part.parse::<i32>().ok().filter(|minutes| (0..=1440).contains(minutes))?;
+            thresholds.push(minutes);
+        }
 
-        self.conn.execute(
-            "DELETE FROM sessions WHERE start_time >= ?1",
-            [today_start.to_rfc3339()],
+        thresholds.sort_unstable_by(|a, b| b.cmp(a));
+        thresholds.dedup();
+        Some(thresholds)
+    }
+
+    // "HH:MM-HH:MM" in local time; the window wraps past midnight when it ends
+    // earlier than it starts. An empty value means no quiet hours.
+    pub fn parse_quiet_hours(value: &str) -> Option<Option<(NaiveTime, NaiveTime)>> {
+        if value.trim().is_empty() {
+            return Some(None);
+        }
+        let (start, end) = value.split_once('-')?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").ok()?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").ok()?;
+        Some(Some((start, end)))
+    }
+
+    pub fn in_quiet_hours(value: &str, time: NaiveTime) -> bool {
+        match Self::parse_quiet_hours(value).flatten() {
+            Some((start, end)) if start < end => time >= start && time < end,
+            Some((start, end)) => time >= start || time < end,
+            None => false,
+        }
+    }
+
+    // Checks a user-editable setting's key and value before anything is written
+    pub fn validate_setting(key: &str, value: &str) -> Result<(), String> {
+        let valid = match key {
+            "daily_allowance_minutes" => value.parse::<i32>().map(|v| (0..=1440).contains(&v)).unwrap_or(false),
+            "rollover_days" => value.parse::<i32>().map(|v| (0..=14).contains(&v)).unwrap_or(false),
+            "social_session_discount_percent" => value.parse::<i32>().map(|v| (0..=100).contains(&v)).unwrap_or(false),
+            "warning_threshold_minutes" | "nag_interval_minutes" | "max_debt_minutes" | "countdown_minutes"
+            | "max_distinct_games_per_day" | "idle_threshold_minutes" | "max_earned_minutes_per_day" => {
+                value.parse::<i32>().map(|v| v >= 0).unwrap_or(false)
+            },
+            "cpu_idle_seconds" | "kill_grace_seconds" | "notification_cooldown_seconds" => value.parse::<i64>().map(|v| v >= 0).unwrap_or(false),
+            "full_scan_interval_seconds" => value.parse::<i64>().map(|v| v >= 1).unwrap_or(false),
+            "session_merge_seconds" => value.parse::<i64>().map(|v| (0..=600).contains(&v)).unwrap_or(false),
+            "monitor_interval_secs" => value.parse::<i64>().map(|v| (1..=30).contains(&v)).unwrap_or(false),
+            "day_reset_hour" => value.parse::<u32>().map(|v| v <= 12).unwrap_or(false),
+            "cpu_idle_threshold" => value.parse::<f32>().map(|v| (0.0..=100.0).contains(&v)).unwrap_or(false),
+            "notifications_enabled" | "web_game_detection_enabled" | "confirm_steam_names"
+            | "personal_best_notifications" | "trust_startup_detections" | "enforcement_enabled"
+            | "allow_time_requests" | "count_foreground_only" => {
+                matches!(value, "true" | "false")
+            },
+            "log_format" => crate::logging::is_valid_format(value),
+            "detection_mode" => matches!(value, "auto" | "whitelist"),
+            "notification_style" => matches!(value, "overlay" | "tray" | "notification_only" | "silent"),
+            "warning_thresholds" => Self::parse_warning_thresholds(value).is_some(),
+            "quiet_hours" => Self::parse_quiet_hours(value).is_some(),
+            "encryption_enabled" if value == "true" && !encryption::AVAILABLE => {
+                return Err("This build has no encryption support".to_string());
+            },
+            "encryption_enabled" => matches!(value, "true" | "false"),
+            _ => return Err(format!("Unknown setting: {}", key)),
+        };
+
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("Invalid value for {}: {}", key, value))
+        }
+    }
+
+    // Applies several settings together: all are validated first, then written
+    // in one transaction so a failure never leaves them half-applied
+    pub fn update_settings_atomic(&self, pairs: &[(String, String)]) -> Result<(), String> {
+        for (key, value) in pairs {
+            Self::validate_setting(key, value)?;
+        }
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result = pairs.iter()
+            .try_for_each(|(key, value)| self.update_setting(key, value));
+
+        match result {
+            Ok(()) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Updated {} settings", pairs.len());
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Pull existing rollover expiries in so a shortened window applies retroactively
+    fn reconcile_rollover_expiry(&self, rollover_days: i64) -> SqlResult<()> {
+        let cap = Utc::now() + chrono::Duration::days(rollover_days.max(0));
+
+        let shortened = self.conn.execute(
+            "UPDATE budget_rollover SET expires_at = ?1 WHERE expires_at > ?1",
+            [cap.to_rfc3339()],
         )?;
 
-        info!("Reset all sessions for today");
+        if shortened > 0 {
+            info!("Shortened {} rollover entries to the {}-day window", shortened, rollover_days);
+        }
         Ok(())
     }
 
+    // Debug/Development helpers
+
+    // Moves today's sessions to the trash, where undo_reset_today can bring
+    // them back. Returns how many sessions were moved.
+    pub fn reset_today_sessions(&self) -> Result<usize, String> {
+        let today_start = self.current_day_start().to_rfc3339();
+        let reset_id = uuid::Uuid::new_v4().to_string();
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result = self.conn.execute(
+            &format!(
+                "INSERT INTO sessions_trash ({0}, reset_id, deleted_at)
+                 SELECT {0}, ?3, ?4 FROM sessions WHERE start_time >= ?1 AND profile_id = ?2",
+                SESSION_COLUMNS
+            ),
+            params![today_start, self.profile_id, reset_id, Utc::now().to_rfc3339()],
+        ).and_then(|_| self.conn.execute(
+            "DELETE FROM sessions WHERE start_time >= ?1 AND profile_id = ?2",
+            params![today_start, self.profile_id],
+        ));
+
+        match result {
+            Ok(moved) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Reset today's sessions; {} moved to the trash", moved);
+                Ok(moved)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    // Restores the sessions removed by the active profile's latest reset, or
+    // the session it deleted last. Returns how many sessions came back.
+    pub fn undo_reset_today(&self) -> Result<usize, String> {
+        let reset_id: String = self.conn.query_row(
+            "SELECT reset_id FROM sessions_trash WHERE profile_id = ?1
+             ORDER BY deleted_at DESC LIMIT 1",
+            [&self.profile_id],
+            |row| row.get(0),
+        ).optional().map_err(|e| e.to_string())?
+            .ok_or_else(|| "There is no reset to undo".to_string())?;
+
+        self.conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let result = self.conn.execute(
+            &format!(
+                "INSERT OR IGNORE INTO sessions ({0})
+                 SELECT {0} FROM sessions_trash WHERE reset_id = ?1",
+                SESSION_COLUMNS
+            ),
+            [&reset_id],
+        ).and_then(|restored| {
+            self.conn.execute("DELETE FROM sessions_trash WHERE reset_id = ?1", [&reset_id])
+                .map(|_| restored)
+        });
+
+        match result {
+            Ok(restored) => {
+                self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+                info!("Undid reset {}: {} sessions restored", reset_id, restored);
+                Ok(restored)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch("ROLLBACK");
+                Err(e.to_string())
+            }
+        }
+    }
+
+    pub fn purge_session_trash(&self) -> SqlResult<usize> {
+        let cutoff = Utc::now() - chrono::Duration::days(SESSION_TRASH_DAYS);
+        let purged = self.conn.execute(
+            "DELETE FROM sessions_trash WHERE deleted_at < ?1",
+            [cutoff.to_rfc3339()],
+        )?;
+
+        if purged > 0 {
+            info!("Purged {} sessions from the trash", purged);
+        }
+        Ok(purged)
+    }
+
+    // Voids today's learning so it stops earning minutes but stays in history.
+    // Returns how many activities were voided.
+    pub fn reset_today_earned(&self) -> SqlResult<usize> {
+        let voided = self.conn.execute(
+            "UPDATE learning_activities SET is_voided = TRUE
+             WHERE timestamp >= ?1 AND COALESCE(is_voided, 0) = 0 AND profile_id = ?2",
+            params![self.current_day_start().to_rfc3339(), self.profile_id],
+        )?;
+
+        info!("Voided {} learning activities for today", voided);
+        Ok(voided)
+    }
+
     pub fn add_debug_earned_minutes(&self, minutes: i32) -> SqlResult<()> {
         // Add a fake learning activity to give bonus minutes (or remove if negative)
         let activity = LearningActivity {
@@ -402,9 +4169,11 @@ impl Database {
             duration_minutes: minutes.abs() * 4, // Fake duration
             earned_gaming_minutes: minutes,
             timestamp: Utc::now(),
+            is_voided: false,
         };
 
-        self.add_learning_activity(&activity)?;
+        // Debug minutes skip the earning cap
+        self.insert_learning_activity(&activity, &self.profile_id)?;
         info!("Added {} debug minutes to budget", minutes);
         Ok(())
     }
@@ -423,10 +4192,401 @@ impl Database {
             is_social_session: false,
             is_concurrent: false,
             concurrent_session_ids: Vec::new(),
+            is_web_game: false,
+            idle_seconds: 0,
+            locked_seconds: 0,
+            is_imported: false,
+            end_reason: None,
+            detection_source: "debug".to_string(),
+            pids: Vec::new(),
+            profile_id: self.profile_id.clone(),
+            last_seen: now,
+            is_manual: false,
         };
 
         self.save_session(&session)?;
         info!("Added {} minutes of fake gaming session", minutes);
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Database {
+        let mut db = Database {
+            conn: Connection::open_in_memory().unwrap(),
+            data_dir: std::env::temp_dir(),
+            profile_id: PRIMARY_PROFILE_ID.to_string(),
+            encrypted: false,
+        };
+        db.run_migrations().unwrap();
+        db.insert_default_settings().unwrap();
+        db.load_active_profile().unwrap();
+        db
+    }
+
+    fn finished_session(game_name: &str, start_time: DateTime<Utc>, minutes: i64) -> GameSession {
+        let mut session = GameSession::new(game_name.to_string(), format!("{}.exe", game_name.to_lowercase()));
+        session.start_time = start_time;
+        session.end_session(start_time + chrono::Duration::minutes(minutes));
+        session
+    }
+
+    fn custom_game(process_name: &str, kind: GameMatchKind) -> GameConfig {
+        GameConfig {
+            process_name: process_name.to_string(),
+            display_name: process_name.to_string(),
+            is_monitored: true,
+            notify_on_limit: true,
+            path_pattern: None,
+            is_launcher: false,
+            title_pattern: None,
+            kind,
+            is_exempt: false,
+        }
+    }
+
+    fn set_last_rollover_date(db: &Database, date: Option<NaiveDate>) {
+        db.conn.execute(
+            "UPDATE profiles SET last_rollover_date = ?1 WHERE id = ?2",
+            params![date.map(|date| date.to_string()), db.profile_id],
+        ).unwrap();
+    }
+
+    #[test]
+    fn first_rollover_only_sets_the_baseline() {
+        let db = test_db();
+        assert_eq!(db.finalize_day_rollover().unwrap(), Some(0));
+        assert_eq!(db.get_rollover_minutes().unwrap(), 0);
+        assert_eq!(db.finalize_day_rollover().unwrap(), None);
+    }
+
+    #[test]
+    fn unused_allowance_rolls_over_after_a_tracked_day() {
+        let db = test_db();
+        let yesterday = db.current_day() - chrono::Duration::days(1);
+        set_last_rollover_date(&db, Some(yesterday - chrono::Duration::days(1)));
+
+        let allowance = db.get_allowance_for(yesterday).unwrap();
+        assert_eq!(db.finalize_day_rollover().unwrap(), Some(allowance));
+        assert_eq!(db.get_rollover_minutes().unwrap(), allowance);
+    }
+
+    #[test]
+    fn days_with_the_app_closed_roll_nothing_over() {
+        let db = test_db();
+        let yesterday = db.current_day() - chrono::Duration::days(1);
+        set_last_rollover_date(&db, Some(yesterday - chrono::Duration::days(4)));
+
+        assert_eq!(db.finalize_day_rollover().unwrap(), Some(0));
+        assert_eq!(db.get_rollover_minutes().unwrap(), 0);
+    }
+
+    #[test]
+    fn overtime_is_only_granted_once_time_is_used_up() {
+        let db = test_db();
+        db.update_setting("daily_allowance_minutes", "30").unwrap();
+        assert!(db.grant_overtime(10).is_err());
+
+        db.update_setting("daily_allowance_minutes", "0").unwrap();
+        db.grant_overtime(10).unwrap();
+        let budget = db.get_budget_status().unwrap();
+        assert_eq!(budget.debt_minutes, 10);
+        assert_eq!(budget.debt_capacity_minutes, 50);
+    }
+
+    #[test]
+    fn overtime_and_borrowing_share_the_debt_limit() {
+        let db = test_db();
+        db.update_setting("daily_allowance_minutes", "0").unwrap();
+        db.update_setting("max_debt_minutes", "60").unwrap();
+
+        let overtime = db.grant_overtime(40).unwrap();
+        assert!(db.borrow_from_tomorrow(30).is_err());
+        db.borrow_from_tomorrow(20).unwrap();
+
+        db.revoke_grant(overtime.id.as_deref().unwrap()).unwrap();
+        assert_eq!(db.get_budget_status().unwrap().debt_minutes, 20);
+    }
+
+    #[test]
+    fn quiet_hours_can_wrap_past_midnight() {
+        let at = |hm: &str| NaiveTime::parse_from_str(hm, "%H:%M").unwrap();
+        assert!(Database::in_quiet_hours("21:00-07:00", at("23:30")));
+        assert!(Database::in_quiet_hours("21:00-07:00", at("06:59")));
+        assert!(!Database::in_quiet_hours("21:00-07:00", at("07:00")));
+        assert!(Database::in_quiet_hours("13:00-14:00", at("13:00")));
+        assert!(!Database::in_quiet_hours("13:00-14:00", at("14:00")));
+        assert!(!Database::in_quiet_hours("", at("23:30")));
+    }
+
+    #[test]
+    fn quiet_hours_must_be_a_time_range() {
+        assert!(Database::validate_setting("quiet_hours", "22:00-06:30").is_ok());
+        assert!(Database::validate_setting("quiet_hours", "").is_ok());
+        assert!(Database::validate_setting("quiet_hours", "22:00").is_err());
+        assert!(Database::validate_setting("quiet_hours", "25:00-06:00").is_err());
+    }
+
+    #[test]
+    fn achievement_bonus_is_granted_once_and_kept_in_the_history() {
+        let db = test_db();
+        db.set_achievement_bonus("longest_streak", 15).unwrap();
+
+        assert_eq!(db.grant_achievement_bonus("longest_streak", 7).unwrap(), Some(15));
+        assert_eq!(db.grant_achievement_bonus("longest_streak", 7).unwrap(), None);
+        assert_eq!(db.grant_achievement_bonus("most_learning_day", 90).unwrap(), None);
+
+        let grants = db.get_active_grants().unwrap();
+        assert_eq!(grants.len(), 1);
+        assert_eq!(grants[0].grant_type, "achievement");
+
+        let history = db.get_achievement_history().unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].achievement, "longest_streak:7");
+        assert_eq!(history[0].minutes, 15);
+        assert_eq!(Some(history[0].grant_id.clone()), grants[0].id);
+    }
+
+    #[test]
+    fn weekly_report_totals_each_game_once() {
+        let db = test_db();
+        let start = Utc::now() - chrono::Duration::hours(5);
+        db.save_sessions(&[
+            finished_session("Chess", start, 30),
+            finished_session("Chess", start + chrono::Duration::hours(1), 20),
+            finished_session("Tetris", start + chrono::Duration::hours(2), 45),
+        ]).unwrap();
+
+        let games = db.get_weekly_report().unwrap().top_games;
+        assert_eq!(games.len(), 2);
+        assert_eq!((games[0].game_name.as_str(), games[0].minutes, games[0].session_count), ("Chess", 50, 2));
+        assert_eq!((games[1].game_name.as_str(), games[1].minutes, games[1].session_count), ("Tetris", 45, 1));
+    }
+
+    #[test]
+    fn saving_a_game_never_changes_its_match_kind() {
+        let db = test_db();
+        assert!(db.save_custom_game(&custom_game("gta5", GameMatchKind::Exact)).unwrap());
+        assert!(db.save_custom_game(&custom_game("gta5", GameMatchKind::Exact)).unwrap());
+        assert!(!db.save_custom_game(&custom_game("gta5", GameMatchKind::Regex)).unwrap());
+
+        let saved: Vec<GameConfig> = db.get_custom_games().unwrap().into_iter()
+            .filter(|saved| saved.process_name == "gta5")
+            .collect();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].kind, GameMatchKind::Exact);
+    }
+
+    fn session_count(db: &Database) -> i64 {
+        db.conn.query_row("SELECT COUNT(*) FROM sessions", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn save_sessions_saves_a_whole_batch() {
+        let db = test_db();
+        let start = Utc::now() - chrono::Duration::hours(3);
+        let sessions: Vec<GameSession> = (0..50)
+            .map(|i| finished_session(&format!("Game{}", i), start + chrono::Duration::minutes(i), 1))
+            .collect();
+
+        db.save_sessions(&sessions).unwrap();
+        assert_eq!(session_count(&db), 50);
+    }
+
+    #[test]
+    fn save_sessions_rolls_back_when_one_fails() {
+        let db = test_db();
+        let start = Utc::now() - chrono::Duration::hours(3);
+        let mut sessions: Vec<GameSession> = (0..5)
+            .map(|i| finished_session(&format!("Game{}", i), start + chrono::Duration::minutes(i), 1))
+            .collect();
+        // A duplicate id violates the primary key part way through the batch
+        sessions[3].id = sessions[1].id.clone();
+
+        let error = db.save_sessions(&sessions).unwrap_err();
+        assert!(error.contains("Game3"), "{}", error);
+        assert_eq!(session_count(&db), 0);
+
+        // The connection is usable again afterwards
+        db.save_sessions(&sessions[..2]).unwrap();
+        assert_eq!(session_count(&db), 2);
+    }
+
+    fn local(date: &str, time: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn budget_day_starts_at_the_reset_hour() {
+        let day = |time, reset_hour| Database::budget_day_of(local("2024-03-10", time), reset_hour).to_string();
+
+        assert_eq!(day("02:59", 3), "2024-03-09");
+        assert_eq!(day("03:00", 3), "2024-03-10");
+        assert_eq!(day("04:59", 5), "2024-03-09");
+        assert_eq!(day("05:00", 5), "2024-03-10");
+        assert_eq!(day("23:59", 5), "2024-03-10");
+        assert_eq!(day("00:00", 0), "2024-03-10");
+    }
+
+    #[test]
+    fn late_night_play_counts_toward_the_previous_day() {
+        assert_eq!(Database::budget_day_of(local("2024-01-01", "01:30"), 3).to_string(), "2023-12-31");
+        assert_eq!(Database::budget_day_of(local("2024-03-01", "04:00"), 5).to_string(), "2024-02-29");
+    }
+
+    #[test]
+    fn day_reset_hour_comes_from_its_own_setting() {
+        let db = test_db();
+        assert_eq!(db.day_reset_hour(), 0);
+
+        db.update_setting("day_reset_hour", "5").unwrap();
+        assert_eq!(db.day_reset_hour(), 5);
+
+        db.update_setting("day_reset_hour", "30").unwrap();
+        assert_eq!(db.day_reset_hour(), 12);
+    }
+
+    #[test]
+    fn only_the_normal_game_of_a_concurrent_pair_is_billed() {
+        let db = test_db();
+        db.save_custom_game(&custom_game("typingtutor", GameMatchKind::Exact)).unwrap();
+        assert!(db.set_game_exempt("typingtutor", true).unwrap());
+
+        let start = Utc::now() - chrono::Duration::minutes(45);
+        let mut exempt = finished_session("TypingTutor", start, 40);
+        let mut normal = finished_session("Tetris", start + chrono::Duration::minutes(10), 20);
+        exempt.is_concurrent = true;
+        normal.is_concurrent = true;
+        db.save_sessions(&[exempt, normal]).unwrap();
+
+        assert_eq!(db.get_usage_minutes_since(start, None).unwrap(), 20);
+        assert_eq!(db.get_usage_minutes_since(start, Some("TypingTutor")).unwrap(), 0);
+    }
+
+    #[test]
+    fn a_grant_that_already_expired_contributes_nothing() {
+        let db = test_db();
+        let expired = Grant::new("temporary".to_string(), 60, "Last night".to_string(), Utc::now() - chrono::Duration::hours(1));
+        db.add_grant(&expired).unwrap();
+
+        assert_eq!(db.get_grant_minutes(false).unwrap(), 0);
+        assert_eq!(db.get_budget_status().unwrap().bonus_minutes, 0);
+        assert!(db.get_active_grants().unwrap().is_empty());
+
+        let tonight = Grant::new("temporary".to_string(), 30, "Tonight".to_string(), Utc::now() + chrono::Duration::hours(1));
+        db.add_grant(&tonight).unwrap();
+        assert_eq!(db.get_budget_status().unwrap().bonus_minutes, 30);
+    }
+
+    #[test]
+    fn a_session_across_midnight_is_split_between_the_days() {
+        let db = test_db();
+        let start = Database::local_to_utc(local("2024-03-09", "23:30"));
+        db.save_sessions(&[finished_session("Chess", start, 90)]).unwrap();
+
+        let midnight = Database::local_midnight(NaiveDate::from_ymd_opt(2024, 3, 10).unwrap());
+        assert_eq!(db.get_usage_minutes_since(midnight, None).unwrap(), 60);
+
+        let days = db.get_timeseries("used_minutes", "day",
+                                     Database::local_midnight(NaiveDate::from_ymd_opt(2024, 3, 9).unwrap()),
+                                     Database::local_midnight(NaiveDate::from_ymd_opt(2024, 3, 11).unwrap())).unwrap();
+        let minutes: Vec<i64> = days.iter().map(|point| point.value).collect();
+        assert_eq!(minutes, vec![30, 60]);
+    }
+
+    fn latest_rollover_expiry(db: &Database) -> DateTime<Utc> {
+        let expires_at: String = db.conn.query_row("SELECT MAX(expires_at) FROM budget_rollover", [], |row| row.get(0)).unwrap();
+        DateTime::parse_from_rfc3339(&expires_at).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn lowering_rollover_days_shortens_banked_rollover() {
+        let db = test_db();
+        db.add_rollover("2024-03-08", 30, Utc::now() + chrono::Duration::days(1)).unwrap();
+        db.add_rollover("2024-03-09", 20, Utc::now() + chrono::Duration::days(5)).unwrap();
+        assert_eq!(db.get_rollover_minutes().unwrap(), 50);
+
+        db.update_setting("rollover_days", "2").unwrap();
+        assert!(latest_rollover_expiry(&db) <= Utc::now() + chrono::Duration::days(2));
+        assert_eq!(db.get_rollover_minutes().unwrap(), 50);
+
+        db.update_setting("rollover_days", "0").unwrap();
+        assert_eq!(db.get_rollover_minutes().unwrap(), 0);
+    }
+
+    #[test]
+    fn bucket_count_is_known_before_the_buckets_are_built() {
+        let from = Database::local_midnight(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()) + chrono::Duration::minutes(30);
+        let to = from + chrono::Duration::days(3);
+        for granularity in ["hour", "day", "week"] {
+            let count = Database::timeseries_bucket_count(granularity, from, to).unwrap();
+            let built = Database::timeseries_buckets(granularity, from, to).unwrap().len() as i64;
+            assert!(built <= count && count <= built + 1, "{}: {} built, {} counted", granularity, built, count);
+        }
+
+        let far = from + chrono::Duration::days(365 * 1000);
+        assert_eq!(Database::timeseries_bucket_count("hour", from, far), Some(365 * 1000 * 24 + 1));
+        assert_eq!(Database::timeseries_bucket_count("minute", from, to), None);
+    }
+
+    #[test]
+    fn learning_plan_flags_a_goal_past_the_earning_cap() {
+        let db = test_db();
+        db.update_setting("max_earned_minutes_per_day", "20").unwrap();
+
+        let plan = db.learning_plan_for(30, "reading").unwrap();
+        assert_eq!(plan.learning_minutes, 180);
+        assert_eq!(plan.earnable_today_minutes, 20);
+        assert!(plan.exceeds_cap);
+
+        let plan = db.learning_plan_for(15, "reading").unwrap();
+        assert_eq!((plan.learning_minutes, plan.earnable_today_minutes, plan.exceeds_cap), (90, 15, false));
+
+        db.update_setting("max_earned_minutes_per_day", "0").unwrap();
+        assert!(!db.learning_plan_for(600, "reading").unwrap().exceeds_cap);
+    }
+
+    #[test]
+    fn approved_requests_are_credited_to_the_profile_that_asked() {
+        let mut db = test_db();
+        let kid = db.create_profile("Kid", 60).unwrap();
+        let request = db.add_time_request(30, "").unwrap();
+
+        db.set_active_profile(&kid.id).unwrap();
+        db.add_time_request(15, "").unwrap();
+        db.resolve_time_request(&request.id, true).unwrap();
+        assert_eq!(db.get_budget_status().unwrap().earned_minutes, 0);
+
+        db.set_active_profile(PRIMARY_PROFILE_ID).unwrap();
+        assert_eq!(db.get_budget_status().unwrap().earned_minutes, 30);
+    }
+
+    #[test]
+    fn recurring_grants_and_weekday_allowances_belong_to_one_profile() {
+        let mut db = test_db();
+        let kid = db.create_profile("Kid", 60).unwrap();
+        let today = db.current_day();
+        db.add_recurring_grant(&RecurringGrant {
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            weekday: today.weekday().num_days_from_sunday(),
+            minutes: 20,
+            expires_at_time: None,
+            reason: "Weekend".to_string(),
+        }).unwrap();
+        db.set_weekday_allowances(&HashMap::from([(today.weekday().num_days_from_sunday(), 200)])).unwrap();
+
+        db.set_active_profile(&kid.id).unwrap();
+        db.apply_recurring_grants().unwrap();
+        assert!(db.get_recurring_grants().unwrap().is_empty());
+        assert_eq!(db.get_budget_status().unwrap().recurring_bonus_minutes, 0);
+        assert_eq!(db.get_allowance_for(today).unwrap(), 60);
+
+        db.set_active_profile(PRIMARY_PROFILE_ID).unwrap();
+        db.apply_recurring_grants().unwrap();
+        assert_eq!(db.get_budget_status().unwrap().recurring_bonus_minutes, 20);
+        assert_eq!(db.get_allowance_for(today).unwrap(), 200);
+    }
+}
@@ -0,0 +1,96 @@
+// Keys for encrypting the database at rest with SQLCipher. The key is a random
+// secret kept in the OS keyring rather than derived from the parent PIN: the
+// app opens the database at login, long before anyone types a PIN, and
+// changing the PIN would otherwise mean re-encrypting everything.
+//
+// Only builds with the "encryption" feature can encrypt; elsewhere there is
+// never a key and the database stays plaintext.
+//
+// Other secrets, like the Steam Web API key, are kept in the same keyring under
+// their own name instead of in settings. Builds without the keyring don't keep
+// them at all.
+
+#[cfg(feature = "encryption")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "encryption")]
+const KEYRING_SERVICE: &str = "gaming-time-tracker";
+#[cfg(feature = "encryption")]
+const KEYRING_USER: &str = "database-key";
+
+pub const AVAILABLE: bool = cfg!(feature = "encryption");
+
+#[cfg(feature = "encryption")]
+fn entry() -> Result<keyring::Entry, String> {
+    secret_entry(KEYRING_USER)
+}
+
+#[cfg(feature = "encryption")]
+fn secret_entry(name: &str) -> Result<keyring::Entry, String> {
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .map_err(|e| format!("Can't reach the OS keyring: {}", e))
+}
+
+// The stored key as 64 hex digits, or None when the database isn't encrypted
+#[cfg(feature = "encryption")]
+pub fn load_key() -> Result<Option<String>, String> {
+    match entry()?.get_password() {
+        Ok(key) if key.len() == 64 && key.chars().all(|c| c.is_ascii_hexdigit()) => Ok(Some(key)),
+        Ok(_) => Err("The database key in the OS keyring is malformed".to_string()),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(format!("Can't read the database key from the OS keyring: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn load_key() -> Result<Option<String>, String> {
+    Ok(None)
+}
+
+// Generates a 256-bit key and stores it in the keyring, replacing any old one
+#[cfg(feature = "encryption")]
+pub fn create_key() -> Result<String, String> {
+    let mut hasher = Sha256::new();
+    for _ in 0..4 {
+        hasher.update(uuid::Uuid::new_v4().as_bytes());
+    }
+    let key: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    entry()?.set_password(&key)
+        .map_err(|e| format!("Can't store the database key in the OS keyring: {}", e))?;
+    Ok(key)
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn create_key() -> Result<String, String> {
+    Err("This build has no encryption support".to_string())
+}
+
+#[cfg(feature = "encryption")]
+pub fn delete_key() -> Result<(), String> {
+    match entry()?.delete_password() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(format!("Can't remove the database key from the OS keyring: {}", e)),
+    }
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn delete_key() -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(feature = "encryption")]
+pub fn store_secret(name: &str, value: &str) -> Result<(), String> {
+    secret_entry(name)?.set_password(value)
+        .map_err(|e| format!("Can't store {} in the OS keyring: {}", name, e))
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn store_secret(name: &str, _value: &str) -> Result<(), String> {
+    Err(format!("This build can't keep {} in the OS keyring", name))
+}
+
+// Raw hex keys skip SQLCipher's key derivation, so opening stays fast
+pub fn key_literal(key: &str) -> String {
+    format!("x'{}'", key)
+}
@@ -1,16 +1,161 @@
-use sysinfo::{System, SystemExt, ProcessExt};
-use std::collections::HashMap;
-use log::info;
+use sysinfo::{System, SystemExt, ProcessExt, PidExt, Pid, ProcessRefreshKind, Signal};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
+use log::{debug, error, info};
+use regex::{Regex, RegexBuilder};
 
-use crate::models::GameSession;
+use crate::database::Database;
+use crate::models::{GameMatchKind, GameSession, GameConfig, SessionEndReason, PermissionReport, CurfewWindow, CloseResult};
+use crate::platform;
+
+// Prefix for the pseudo process names given to games detected in a browser tab
+pub const WEB_GAME_PREFIX: &str = "web:";
+
+// A gap this much longer than the update interval means the PC was asleep
+const SLEEP_GAP_SECONDS: i64 = 30;
+
+// Longest gap between snapshots of the open sessions while nothing changes
+const ACTIVE_SNAPSHOT_SECONDS: i64 = 10;
+
+// Kill attempts per game before it's reported as resisting, and the wait
+// between them for the processes to exit
+const CLOSE_ATTEMPTS: u32 = 3;
+pub const CLOSE_RETRY_DELAY_MS: u64 = 500;
+
+// Caps on user-supplied window title and process name regexes. The regex crate
+// always matches in linear time, so this only bounds how large a pattern can
+// compile to.
+const PATTERN_MAX_LEN: usize = 200;
+const PATTERN_SIZE_LIMIT: usize = 1 << 16;
+
+// Key used whenever process names are compared. sysinfo doesn't report names
+// with consistent case across platforms, and only Windows adds ".exe", so
+// "VALORANT.EXE", "Valorant.exe" and "valorant" are all the same game.
+pub fn normalize_process_name(name: &str) -> String {
+    let lower = name.trim().to_lowercase();
+    match lower.strip_suffix(".exe") {
+        Some(stem) if !stem.is_empty() => stem.to_string(),
+        _ => lower,
+    }
+}
+
+// How far up the process tree a launcher is looked for. Games sit directly
+// under their launcher or under a bootstrapper it started.
+const LAUNCHER_TREE_DEPTH: usize = 3;
+
+// Walks up from `parent` and returns the pid of the first ancestor whose
+// normalized name `is_launcher` accepts. `lookup` gives a pid's normalized
+// name and parent, so the walk doesn't depend on sysinfo's process table.
+fn find_launcher_ancestor(
+    parent: Option<u32>,
+    lookup: impl Fn(u32) -> Option<(String, Option<u32>)>,
+    is_launcher: impl Fn(&str) -> bool,
+) -> Option<u32> {
+    let mut current = parent;
+    for _ in 0..LAUNCHER_TREE_DEPTH {
+        let pid = current?;
+        let (name, next) = lookup(pid)?;
+        if is_launcher(&name) {
+            return Some(pid);
+        }
+        current = next;
+    }
+    None
+}
+
+// Programs launchers start alongside games (chat, overlays, embedded web
+// views) that must never be billed or closed as a game, normalized
+const LAUNCHER_CHILD_IGNORED: &[&str] = &[
+    "discord",
+    "gameoverlayui",
+    "overwolf",
+    "msedgewebview2",
+    "epicwebhelper",
+    "qtwebengineprocess",
+];
+
+// Prefix held on guessed Steam names until the user confirms them
+pub const UNCONFIRMED_NAME_PREFIX: &str = "Unconfirmed: ";
+
+// How a running process is treated by detection and closing
+#[derive(Debug, Clone, PartialEq)]
+enum ProcessClass {
+    Blacklisted,
+    KnownGame(String), // display_name
+    Launcher(String), // display_name of a known launcher; detected but not billed
+    SteamGame(String), // display_name guessed from the exe name
+    LauncherChild(String), // Started by a known launcher; display_name guessed from the exe name
+    Other,
+}
+
+// Games being closed, carried between kill attempts so the caller can release
+// the monitor lock while the processes get time to exit
+pub struct PendingClose {
+    targets: HashMap<String, (String, Vec<u32>)>, // process_name -> (display_name, pids still running)
+    refused: HashSet<String>, // Games the OS refused to kill on the last attempt
+    attempt: u32,
+    attempts: u32,
+}
+
+impl PendingClose {
+    // Every target has exited, or there are no attempts left
+    pub fn is_done(&self) -> bool {
+        self.attempt >= self.attempts || self.targets.values().all(|(_, pids)| pids.is_empty())
+    }
+}
 
 pub struct GameMonitor {
     system: System,
     active_sessions: Vec<GameSession>, // Changed from single session to multiple
     completed_sessions: Vec<GameSession>, // Queue of completed sessions
-    known_games: HashMap<String, String>, // process_name -> display_name
+    ended_intervals: Vec<(String, DateTime<Utc>, DateTime<Utc>)>, // (id, start, end) of ended sessions an active one may still overlap
+    known_games: HashMap<String, String>, // normalized process_name -> display_name
+    path_games: Vec<(String, String, String)>, // (process_name, lowercase path substring, display_name); checked before known_games
+    title_games: Vec<(String, Regex, String)>, // (process_name, window title regex, display_name); checked before path_games
+    pattern_games: Vec<(Regex, String)>, // (process name regex, display_name); only tried when no exact entry matches
+    window_titles: HashMap<u32, Vec<String>>, // Visible window titles by pid, read each update while title_games is non-empty
+    silenced_games: HashSet<String>, // process_names that don't trigger limit warnings
+    exempt_games: HashSet<String>, // process_names that are tracked but never billed or closed
+    launchers: HashSet<String>, // process_names of known games that are really launchers
+    open_launchers: Vec<String>, // display_names of launchers seen by the last update
     blacklisted_processes: Vec<String>, // Processes to ignore
+    browser_processes: Vec<String>, // Browsers whose window titles are checked for web games
+    web_game_sites: Vec<(String, String)>, // title keyword -> display_name
+    web_game_detection_enabled: bool,
+    whitelist_only: bool, // "whitelist" detection mode: only known and custom games count
+    confirm_steam_names: bool, // Mark guessed Steam names as unconfirmed instead of auto-accepting them
+    cpu_idle_threshold: f32, // Percent CPU below which a game counts as idle (0 disables)
+    cpu_idle_seconds: i64, // How long CPU must stay low before the game is idle
+    input_idle_seconds: i64, // No user input for this long freezes every session (0 disables)
+    count_foreground_only: bool, // Games in the background count as idle
+    curfew_windows: Vec<CurfewWindow>, // Blackout hours when detected games are closed regardless of budget
+    low_cpu_since: HashMap<String, DateTime<Utc>>, // process_name -> when CPU first dropped
+    idle_seconds: i64, // Time every active session was idle, excluded from active time
+    locked_seconds: i64, // Time the PC was asleep or locked with games open, excluded from active time
+    last_update: Option<DateTime<Utc>>,
+    last_nag: Option<DateTime<Utc>>, // When the over-budget reminder was last shown
+    nag_count: u32, // Reminders shown since the budget ran out
     is_paused: bool,
+    warmed_up: bool, // The process list has been refreshed at least once before detecting
+    full_scan_interval_seconds: i64, // Refresh every process this often; in between only candidates are refreshed
+    update_interval_seconds: i64, // How often the monitoring loop calls update()
+    last_full_scan: Option<DateTime<Utc>>,
+    candidate_pids: HashSet<Pid>, // Games and browsers found by the last full scan
+    targeted_scan: bool, // This cycle only refreshed candidate_pids, so other entries may be stale
+    trust_startup_detections: bool, // Skip the warmup and detect games on the very first cycle
+    enforcement_deadline: Option<DateTime<Utc>>, // When open games get closed, while the grace period runs
+    last_persisted: Option<(DateTime<Utc>, Vec<Option<String>>)>, // When active sessions were last snapshotted, and which
+    steam_names: HashMap<String, Option<String>>, // exe path -> name from the Steam manifest (None if none matched)
+    profile_id: String, // Profile new sessions are attributed to
+    session_merge_seconds: i64, // An exited game's session stays open this long in case the game restarts
+    closing: HashSet<String>, // process_names being closed; their sessions are ended by finish_close, not update
+}
+
+impl Default for GameMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameMonitor {
@@ -19,80 +164,346 @@ impl GameMonitor {
             system: System::new_all(),
             active_sessions: Vec::new(),
             completed_sessions: Vec::new(),
+            ended_intervals: Vec::new(),
             known_games: HashMap::new(),
+            path_games: Vec::new(),
+            title_games: Vec::new(),
+            pattern_games: Vec::new(),
+            window_titles: HashMap::new(),
+            silenced_games: HashSet::new(),
+            exempt_games: HashSet::new(),
+            launchers: HashSet::new(),
+            open_launchers: Vec::new(),
             blacklisted_processes: Vec::new(),
+            browser_processes: Vec::new(),
+            web_game_sites: Vec::new(),
+            web_game_detection_enabled: false,
+            whitelist_only: false,
+            confirm_steam_names: false,
+            cpu_idle_threshold: 0.0,
+            cpu_idle_seconds: 300,
+            input_idle_seconds: 600,
+            count_foreground_only: false,
+            curfew_windows: Vec::new(),
+            low_cpu_since: HashMap::new(),
+            idle_seconds: 0,
+            locked_seconds: 0,
+            last_update: None,
+            last_nag: None,
+            nag_count: 0,
             is_paused: false,
+            warmed_up: false,
+            full_scan_interval_seconds: 1,
+            update_interval_seconds: 1,
+            last_full_scan: None,
+            candidate_pids: HashSet::new(),
+            targeted_scan: false,
+            trust_startup_detections: false,
+            enforcement_deadline: None,
+            last_persisted: None,
+            steam_names: HashMap::new(),
+            profile_id: crate::database::PRIMARY_PROFILE_ID.to_string(),
+            session_merge_seconds: 60,
+            closing: HashSet::new(),
         };
 
-        // Initialize with common gaming processes
-        monitor.add_known_games();
+        // Known games are loaded from the database (seeded from default_games on first run)
         monitor.add_blacklisted_processes();
+        monitor.add_web_game_sites();
         monitor
     }
 
-    fn add_known_games(&mut self) {
-        // Steam games
-        self.known_games.insert("steam.exe".to_string(), "Steam".to_string());
-
-        // Popular games and launchers
+    // Built-in list of common gaming processes, seeded into the database once
+    pub fn default_games() -> Vec<GameConfig> {
+        // (process_name, display_name, is_launcher)
         let games = vec![
-            ("League of Legends.exe", "League of Legends"),
-            ("RiotClientServices.exe", "Riot Games"),
-            ("Valorant.exe", "Valorant"),
-            ("csgo.exe", "Counter-Strike: Global Offensive"),
-            ("dota2.exe", "Dota 2"),
-            ("RocketLeague.exe", "Rocket League"),
-            ("destiny2.exe", "Destiny 2"),
-            ("overwatch.exe", "Overwatch"),
-            ("wow.exe", "World of Warcraft"),
-            ("minecraft.exe", "Minecraft"),
-            ("epicgameslauncher.exe", "Epic Games Launcher"),
-            ("battle.net.exe", "Battle.net"),
-            ("origin.exe", "EA Origin"),
-            ("uplay.exe", "Ubisoft Connect"),
+            // Launchers
+            ("steam.exe", "Steam", true),
+            ("RiotClientServices.exe", "Riot Games", true),
+            ("epicgameslauncher.exe", "Epic Games Launcher", true),
+            ("battle.net.exe", "Battle.net", true),
+            ("origin.exe", "EA Origin", true),
+            ("uplay.exe", "Ubisoft Connect", true),
+
+            // Popular games
+            ("League of Legends.exe", "League of Legends", false),
+            ("Valorant.exe", "Valorant", false),
+            ("csgo.exe", "Counter-Strike: Global Offensive", false),
+            ("dota2.exe", "Dota 2", false),
+            ("RocketLeague.exe", "Rocket League", false),
+            ("destiny2.exe", "Destiny 2", false),
+            ("overwatch.exe", "Overwatch", false),
+            ("wow.exe", "World of Warcraft", false),
+            ("minecraft.exe", "Minecraft", false),
         ];
 
-        for (process, display) in games {
-            self.known_games.insert(process.to_string(), display.to_string());
+        games.into_iter()
+            .map(|(process, display, is_launcher)| GameConfig {
+                process_name: process.to_string(),
+                display_name: display.to_string(),
+                is_monitored: true,
+                notify_on_limit: true,
+                path_pattern: None,
+                is_launcher,
+                title_pattern: None,
+                kind: GameMatchKind::Exact,
+                is_exempt: false,
+            })
+            .collect()
+    }
+
+    pub fn load_custom_games(&mut self, games: Vec<GameConfig>) {
+        self.known_games.clear();
+        self.path_games.clear();
+        self.title_games.clear();
+        self.pattern_games.clear();
+        self.silenced_games.clear();
+        self.exempt_games.clear();
+        self.launchers.clear();
+
+        for game in games {
+            if game.is_monitored {
+                if game.kind == GameMatchKind::Regex {
+                    match Self::compile_name_pattern(&game.process_name) {
+                        Ok(regex) => self.pattern_games.push((regex, game.display_name)),
+                        Err(e) => error!("Skipping {}: {}", game.display_name, e),
+                    }
+                    continue;
+                }
+
+                let key = normalize_process_name(&game.process_name);
+                if game.is_launcher {
+                    self.launchers.insert(key.clone());
+                }
+                if !game.notify_on_limit {
+                    self.silenced_games.insert(key.clone());
+                }
+                if game.is_exempt {
+                    self.exempt_games.insert(key.clone());
+                }
+                if let Some(pattern) = &game.title_pattern {
+                    match Self::compile_title_pattern(pattern) {
+                        Ok(regex) => self.title_games.push((key, regex, game.display_name)),
+                        Err(e) => error!("Skipping {}: {}", game.display_name, e),
+                    }
+                    continue;
+                }
+                match game.path_pattern {
+                    Some(pattern) => self.path_games.push((key, pattern.to_lowercase(), game.display_name)),
+                    None => {
+                        self.known_games.insert(key, game.display_name);
+                    }
+                }
+            }
         }
+
+        info!("Loaded {} known games", self.known_game_count());
     }
 
-    fn add_blacklisted_processes(&mut self) {
-        // Steam software/tools that aren't games
-        let blacklist = vec![
+    // Checked when a game is saved as well as when it's loaded, so a bad pattern
+    // is rejected up front
+    pub fn compile_title_pattern(pattern: &str) -> Result<Regex, String> {
+        Self::compile_pattern(pattern, "title pattern")
+    }
+
+    pub fn compile_name_pattern(pattern: &str) -> Result<Regex, String> {
+        Self::compile_pattern(pattern, "process name pattern")
+    }
+
+    fn compile_pattern(pattern: &str, what: &str) -> Result<Regex, String> {
+        if pattern.len() > PATTERN_MAX_LEN {
+            return Err(format!("The {} is longer than {} characters", what, PATTERN_MAX_LEN));
+        }
+
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .size_limit(PATTERN_SIZE_LIMIT)
+            .build()
+            .map_err(|e| format!("Invalid {}: {}", what, e))
+    }
+
+    // Steam software/tools that aren't games, normalized. The Steam client
+    // itself isn't here: it's a known launcher, shown as open but never billed,
+    // and the blacklist would hide it.
+    pub fn default_blacklist() -> Vec<String> {
+        vec![
             "wallpaper32.exe",
             "wallpaper64.exe",
             "steamwebhelper.exe",
             "steamerrorreporter.exe",
             "crashhandler.exe",
-            "steam.exe", // Steam client itself
+        ].into_iter().map(normalize_process_name).collect()
+    }
+
+    fn add_blacklisted_processes(&mut self) {
+        self.blacklisted_processes.extend(Self::default_blacklist());
+    }
+
+    // Entries added by the user, on top of the defaults
+    pub fn set_user_blacklist(&mut self, entries: Vec<String>) {
+        self.blacklisted_processes.clear();
+        self.add_blacklisted_processes();
+        for entry in entries {
+            let key = normalize_process_name(&entry);
+            if !self.blacklisted_processes.contains(&key) {
+                self.blacklisted_processes.push(key);
+            }
+        }
+    }
+
+    // The tracker's own executable, normalized, so it can't be blacklisted
+    pub fn own_process_name() -> Option<String> {
+        std::env::current_exe().ok()
+            .and_then(|path| path.file_name().map(|name| normalize_process_name(&name.to_string_lossy())))
+    }
+
+    fn add_web_game_sites(&mut self) {
+        let browsers = vec![
+            "chrome.exe",
+            "msedge.exe",
+            "firefox.exe",
+            "opera.exe",
+            "brave.exe",
+        ];
+
+        for browser in browsers {
+            self.browser_processes.push(browser.to_string());
+        }
+
+        // Web game portals and browser games, matched against the active tab's window title
+        let sites = vec![
+            ("Poki", "Poki"),
+            ("CrazyGames", "CrazyGames"),
+            ("Miniclip", "Miniclip"),
+            ("Kongregate", "Kongregate"),
+            ("Armor Games", "Armor Games"),
+            ("Coolmath Games", "Coolmath Games"),
+            ("Newgrounds", "Newgrounds"),
+            ("krunker.io", "Krunker"),
+            ("slither.io", "Slither.io"),
+            ("agar.io", "Agar.io"),
         ];
 
-        for process in blacklist {
-            self.blacklisted_processes.push(process.to_string());
+        for (keyword, display) in sites {
+            self.web_game_sites.push((keyword.to_string(), display.to_string()));
         }
     }
 
     pub fn update(&mut self) {
         if self.is_paused {
+            self.last_update = None;
             return;
         }
 
+        let now = Utc::now();
+        let previous_update = self.last_update;
+        let elapsed = previous_update.map(|last| (now - last).num_seconds()).unwrap_or(0);
+        self.last_update = Some(now);
+
+        // Process CPU usage is computed against the global CPU times, so refresh those too
+        if self.cpu_idle_threshold > 0.0 {
+            self.system.refresh_cpu();
+        }
+
         // Refresh system info to get current processes
-        self.system.refresh_processes();
+        self.refresh_process_list(now);
+        self.resolve_steam_names();
+
+        // Right after launch the process list and CPU figures may not have settled,
+        // so the first cycle only refreshes them instead of starting sessions
+        if !self.warmed_up {
+            self.warmed_up = true;
+            if !self.trust_startup_detections {
+                info!("Warming up process list; ignoring detections for the first cycle");
+                return;
+            }
+        }
+
+        // Only read when some game needs it; enumerating windows isn't free
+        self.window_titles = if self.title_games.is_empty() {
+            HashMap::new()
+        } else {
+            platform::window_titles_by_pid()
+        };
 
         let detected_games = self.find_all_gaming_processes();
-        info!("Update cycle - Found {} games", detected_games.len());
+        debug!("Update cycle - Found {} games", detected_games.len());
+        self.open_launchers = self.find_open_launchers();
 
         // Get currently running process names
         let running_processes: Vec<String> = detected_games.iter()
-            .map(|(process_name, _)| process_name.clone())
+            .map(|(process_name, _, _)| process_name.clone())
             .collect();
 
-        // End sessions for games that are no longer running
+        // Track every running instance; detection is by name, so a session only
+        // ends once all of them have exited
+        for index in 0..self.active_sessions.len() {
+            let pids = self.pids_for(&self.active_sessions[index].process_name);
+            self.active_sessions[index].pids = pids;
+            let session = &mut self.active_sessions[index];
+            let missed_last_update = previous_update.is_some_and(|previous| session.last_seen < previous);
+            if running_processes.contains(&session.process_name) {
+                if missed_last_update {
+                    info!("{} restarted within {}s; resuming its session", session.game_name, self.session_merge_seconds);
+                }
+                session.last_seen = now;
+            } else if self.session_merge_seconds > 0 && !missed_last_update {
+                info!("{} exited; holding its session {}s in case it restarts", session.game_name, self.session_merge_seconds);
+            }
+        }
+
+        self.end_exited_sessions(&running_processes, now);
+
+        // Only games still running are charged for the time since the last tick.
+        // Sleep shows up as a long gap between ticks; either way nobody is playing,
+        // so the time is counted as locked rather than idle
+        let slept = elapsed > self.sleep_gap_seconds();
+        if slept || platform::is_session_locked() {
+            if slept {
+                info!("Resumed after {}s asleep", elapsed);
+            }
+            self.track_locked_time(elapsed);
+        } else {
+            self.track_idle_time(now, elapsed);
+        }
+
+        if self.active_sessions.is_empty() {
+            self.idle_seconds = 0;
+            self.locked_seconds = 0;
+        }
+
+        // Start new sessions for newly detected games
+        for (process_name, display_name, source) in detected_games {
+            let already_tracking = self.active_sessions.iter()
+                .any(|session| session.process_name == process_name);
+
+            if !already_tracking {
+                info!("New game detected and started: {} [via {}]{}", display_name, source,
+                      if !self.active_sessions.is_empty() { " [CONCURRENT]" } else { "" });
+
+                let is_web_game = process_name.starts_with(WEB_GAME_PREFIX);
+                let pids = self.pids_for(&process_name);
+                let mut new_session = GameSession::new(display_name, process_name);
+                new_session.is_web_game = is_web_game;
+                new_session.detection_source = source.to_string();
+                new_session.pids = pids;
+                new_session.profile_id = self.profile_id.clone();
+
+                self.active_sessions.push(new_session);
+            }
+        }
+    }
+
+    // Ends sessions for games that are no longer running, once they've been
+    // gone for the whole merge window. Games being closed are left to
+    // finish_close, so a kill never produces a second, natural end.
+    fn end_exited_sessions(&mut self, running_processes: &[String], now: DateTime<Utc>) {
         let mut sessions_to_end = Vec::new();
         for (index, session) in self.active_sessions.iter().enumerate() {
-            if !running_processes.contains(&session.process_name) {
+            if !running_processes.contains(&session.process_name)
+                && !self.closing.contains(&session.process_name)
+                && (now - session.last_seen).num_seconds() >= self.session_merge_seconds
+            {
                 sessions_to_end.push(index);
             }
         }
@@ -100,13 +511,11 @@ impl GameMonitor {
         // End sessions in reverse order to maintain indices
         for &index in sessions_to_end.iter().rev() {
             let mut session = self.active_sessions.remove(index);
-            session.end_session();
-
-            // Mark as concurrent if there were other active sessions
-            if self.active_sessions.len() > 0 || sessions_to_end.len() > 1 {
-                session.is_concurrent = true;
-                session.concurrent_session_ids = self.get_concurrent_session_ids(&session);
-            }
+            let last_seen = session.last_seen;
+            session.end_session(last_seen);
+            session.end_reason = Some(SessionEndReason::Natural);
+            self.low_cpu_since.remove(&session.process_name);
+            self.settle_concurrency(&mut session);
 
             info!("Game session ended: {} ({}m {}s){}",
                   session.game_name,
@@ -116,91 +525,420 @@ impl GameMonitor {
 
             self.completed_sessions.push(session);
         }
+        self.prune_ended_intervals();
+    }
 
-        // Start new sessions for newly detected games
-        for (process_name, display_name) in detected_games {
-            let already_tracking = self.active_sessions.iter()
-                .any(|session| session.process_name == process_name);
+    // On busy systems refreshing every process each second is wasteful, so between
+    // full scans only the games and browsers seen by the last full scan are
+    // refreshed. Newly launched games are picked up at the next full scan.
+    fn refresh_process_list(&mut self, now: DateTime<Utc>) {
+        let refresh_kind = self.process_refresh_kind();
+        let full_scan_due = self.full_scan_interval_seconds <= 1 || self.last_full_scan
+            .is_none_or(|last| (now - last).num_seconds() >= self.full_scan_interval_seconds);
 
-            if !already_tracking {
-                info!("New game detected and started: {}{}", display_name,
-                      if self.active_sessions.len() > 0 { " [CONCURRENT]" } else { "" });
+        if full_scan_due {
+            self.system.refresh_processes_specifics(refresh_kind);
+            self.last_full_scan = Some(now);
+            self.targeted_scan = false;
 
-                let mut new_session = GameSession::new(display_name, process_name);
+            if self.full_scan_interval_seconds > 1 {
+                self.candidate_pids = self.system.processes().iter()
+                    .filter(|(_, process)| self.is_scan_candidate(process))
+                    .map(|(pid, _)| *pid)
+                    .collect();
+            }
+            return;
+        }
 
-                // Mark as concurrent if other sessions are active
-                if !self.active_sessions.is_empty() {
-                    new_session.is_concurrent = true;
-                    new_session.concurrent_session_ids = self.get_active_session_ids();
+        // refresh_process reports whether the process still exists
+        let candidates = std::mem::take(&mut self.candidate_pids);
+        self.candidate_pids = candidates.into_iter()
+            .filter(|pid| self.system.refresh_process_specifics(*pid, refresh_kind))
+            .collect();
+        self.targeted_scan = true;
+    }
 
-                    // Update existing sessions to mark them as concurrent too
-                    for session in &mut self.active_sessions {
-                        session.is_concurrent = true;
-                        session.concurrent_session_ids.push(new_session.id.as_ref().unwrap().clone());
-                    }
-                }
+    // Name and exe are read whenever a process first appears, so detection needs
+    // none of the optional parts. A plain refresh_processes() also reads every
+    // process's disk I/O counters and owning user each tick (an extra /proc read
+    // per process on Linux, a process handle and token lookup per process on
+    // Windows), which is most of its cost. CPU times are only read when CPU idle
+    // detection is on.
+    fn process_refresh_kind(&self) -> ProcessRefreshKind {
+        if self.cpu_idle_threshold > 0.0 {
+            ProcessRefreshKind::new().with_cpu()
+        } else {
+            ProcessRefreshKind::new()
+        }
+    }
 
-                self.active_sessions.push(new_session);
-            }
+    fn is_scan_candidate(&self, process: &sysinfo::Process) -> bool {
+        match self.classify_process(process) {
+            ProcessClass::KnownGame(_) | ProcessClass::SteamGame(_) | ProcessClass::LauncherChild(_)
+            | ProcessClass::Launcher(_) => true,
+            ProcessClass::Blacklisted => false,
+            ProcessClass::Other => self.web_game_detection_enabled && !self.whitelist_only && self.browser_processes.iter()
+                .any(|browser| browser.eq_ignore_ascii_case(process.name())),
         }
     }
 
-    fn find_all_gaming_processes(&self) -> Vec<(String, String)> {
+    // Processes refreshed this cycle; stale entries are skipped after a targeted scan
+    fn scanned_processes(&self) -> impl Iterator<Item = (&Pid, &sysinfo::Process)> + '_ {
+        self.system.processes().iter()
+            .filter(move |(pid, _)| !self.targeted_scan || self.candidate_pids.contains(pid))
+    }
+
+    // Crashes, self-updaters and launchers handing off to the game exe all
+    // restart a game within seconds; within the window that stays one session
+    pub fn set_session_merge_window(&mut self, seconds: i64) {
+        self.session_merge_seconds = seconds.max(0);
+    }
+
+    // Longer intervals save battery on laptops; the loop still ticks every
+    // second while a countdown or grace period is showing
+    pub fn set_update_interval(&mut self, seconds: i64) {
+        self.update_interval_seconds = seconds.clamp(1, 30);
+    }
+
+    pub fn update_interval_seconds(&self) -> i64 {
+        self.update_interval_seconds
+    }
+
+    // Gaps between updates longer than this were spent asleep
+    pub fn sleep_gap_seconds(&self) -> i64 {
+        SLEEP_GAP_SECONDS + self.update_interval_seconds
+    }
+
+    pub fn set_full_scan_interval(&mut self, seconds: i64) {
+        self.full_scan_interval_seconds = seconds.max(1);
+        self.last_full_scan = None;
+        self.targeted_scan = false;
+    }
+
+    // Launchers are only reported for display; they never start a session, so
+    // leaving one open all day doesn't use up the budget
+    fn find_open_launchers(&self) -> Vec<String> {
+        let mut launchers: Vec<String> = self.scanned_processes()
+            .filter_map(|(_, process)| match self.classify_process(process) {
+                ProcessClass::Launcher(display_name) => Some(display_name),
+                _ => None,
+            })
+            .collect();
+        launchers.sort();
+        launchers.dedup();
+        launchers
+    }
+
+    pub fn get_open_launchers(&self) -> Vec<String> {
+        self.open_launchers.clone()
+    }
+
+    // (process_name, display_name, detection_source) for every game running now
+    fn find_all_gaming_processes(&self) -> Vec<(String, String, &'static str)> {
         let mut gaming_processes = Vec::new();
 
-        for (_pid, process) in self.system.processes() {
+        for (_pid, process) in self.scanned_processes() {
             let process_name = process.name();
 
-            // Skip blacklisted processes
-            if self.blacklisted_processes.contains(&process_name.to_string()) {
-                continue;
+            match self.classify_process(process) {
+                ProcessClass::KnownGame(display_name) => {
+                    gaming_processes.push((process_name.to_string(), display_name, "known_game"));
+                }
+                ProcessClass::SteamGame(mut display_name) => {
+                    if self.confirm_steam_names {
+                        display_name = format!("{}{}", UNCONFIRMED_NAME_PREFIX, display_name);
+                    }
+                    gaming_processes.push((process_name.to_string(), display_name, "steam_heuristic"));
+                }
+                ProcessClass::LauncherChild(display_name) => {
+                    gaming_processes.push((process_name.to_string(), display_name, "launcher_child"));
+                }
+                ProcessClass::Launcher(_) | ProcessClass::Blacklisted | ProcessClass::Other => {}
             }
+        }
 
-            // Check if it's a known gaming process
-            if let Some(display_name) = self.known_games.get(process_name) {
-                gaming_processes.push((process_name.to_string(), display_name.clone()));
-            }
-            // Check for Steam games (they often have random exe names)
-            else if self.is_likely_steam_game(process) {
-                let display_name = self.get_steam_game_name(process_name);
-                gaming_processes.push((process_name.to_string(), display_name));
+        // Web games are found by window title, which whitelist mode doesn't trust
+        if self.web_game_detection_enabled && !self.whitelist_only {
+            for (process_name, display_name) in self.find_web_games() {
+                if !gaming_processes.iter().any(|(name, _, _)| *name == process_name) {
+                    gaming_processes.push((process_name, display_name, "window_title"));
+                }
             }
         }
 
-        info!("Found {} gaming processes: {:?}", gaming_processes.len(),
-              gaming_processes.iter().map(|(_, name)| name).collect::<Vec<_>>());
+        debug!("Found {} gaming processes: {:?}", gaming_processes.len(),
+               gaming_processes.iter().map(|(_, name, _)| name).collect::<Vec<_>>());
 
         gaming_processes
     }
 
-    fn get_active_session_ids(&self) -> Vec<String> {
-        self.active_sessions.iter()
-            .filter_map(|session| session.id.clone())
-            .collect()
-    }
+    // Web games run inside the browser process, so they can only be recognised by
+    // matching known gaming sites against browser window (active tab) titles.
+    fn find_web_games(&self) -> Vec<(String, String)> {
+        let browser_pids: Vec<u32> = self.scanned_processes()
+            .filter(|(_, process)| self.browser_processes.iter()
+                .any(|browser| browser.eq_ignore_ascii_case(process.name())))
+            .map(|(pid, _)| pid.as_u32())
+            .collect();
 
-    fn get_concurrent_session_ids(&self, current_session: &GameSession) -> Vec<String> {
-        self.active_sessions.iter()
-            .filter_map(|session| {
-                if session.id != current_session.id {
-                    session.id.clone()
-                } else {
-                    None
+        if browser_pids.is_empty() {
+            return Vec::new();
+        }
+
+        let titles = platform::window_titles_by_pid();
+        let mut web_games = Vec::new();
+
+        for pid in browser_pids {
+            for title in titles.get(&pid).into_iter().flatten() {
+                let title_lower = title.to_lowercase();
+                for (keyword, display_name) in &self.web_game_sites {
+                    if title_lower.contains(&keyword.to_lowercase()) {
+                        let process_name = format!("{}{}", WEB_GAME_PREFIX, keyword.to_lowercase());
+                        if !web_games.iter().any(|(name, _)| *name == process_name) {
+                            web_games.push((process_name, display_name.clone()));
+                        }
+                    }
                 }
-            })
-            .collect()
+            }
+        }
+
+        web_games
+    }
+
+    // Adds the elapsed tick time to every idle session, and to the monitor-wide
+    // idle total when no active session was being played. In focus mode a game
+    // in the background is idle too, so only its time in front is billed. A
+    // tick is excluded at most once however many of these apply, so focus mode
+    // and idle detection never both discount the same time.
+    fn track_idle_time(&mut self, now: DateTime<Utc>, elapsed: i64) {
+        let mut all_idle = !self.active_sessions.is_empty();
+        let away = self.is_user_away();
+        let foreground_pid = if self.count_foreground_only { platform::foreground_pid() } else { None };
+
+        for index in 0..self.active_sessions.len() {
+            let process_name = self.active_sessions[index].process_name.clone();
+            let in_background = foreground_pid
+                .is_some_and(|pid| !self.owns_foreground(&self.active_sessions[index], pid));
+            // CPU is checked even while away so its low-usage timer keeps running
+            if self.is_session_idle(&process_name, now) || away || in_background {
+                self.active_sessions[index].idle_seconds += elapsed;
+            } else {
+                all_idle = false;
+            }
+        }
+
+        if all_idle {
+            self.idle_seconds += elapsed;
+        }
+    }
+
+    // Excludes time spent asleep or on the lock screen from every open session
+    fn track_locked_time(&mut self, elapsed: i64) {
+        if self.active_sessions.is_empty() {
+            return;
+        }
+
+        for session in &mut self.active_sessions {
+            session.locked_seconds += elapsed;
+        }
+        self.locked_seconds += elapsed;
+
+        // CPU readings from before the lock say nothing about idleness afterwards
+        self.low_cpu_since.clear();
+    }
+
+    // The foreground window belongs to one of the game's processes or to a
+    // child of one (some games draw from a helper process). Web games are in
+    // front whenever a browser is.
+    fn owns_foreground(&self, session: &GameSession, foreground_pid: u32) -> bool {
+        let foreground = self.system.process(Pid::from_u32(foreground_pid));
+        if session.is_web_game {
+            return foreground.is_some_and(|process| self.browser_processes.iter()
+                .any(|browser| browser.eq_ignore_ascii_case(process.name())));
+        }
+
+        session.pids.contains(&foreground_pid)
+            || foreground.and_then(|process| process.parent())
+                .is_some_and(|parent| session.pids.contains(&parent.as_u32()))
+    }
+
+    fn is_session_idle(&mut self, process_name: &str, now: DateTime<Utc>) -> bool {
+        self.is_cpu_idle(process_name, now)
+    }
+
+    // Nobody has touched the keyboard or mouse for a while, so whatever is open
+    // isn't being played. Only Windows reports input times; elsewhere this stays false.
+    fn is_user_away(&self) -> bool {
+        if self.input_idle_seconds <= 0 {
+            return false;
+        }
+
+        platform::seconds_since_last_input()
+            .is_some_and(|seconds| seconds >= self.input_idle_seconds)
+    }
+
+    // Heuristic idle signal: a game sitting in a menu, paused or left alone tends to
+    // use next to no CPU, which works on every platform sysinfo supports.
+    fn is_cpu_idle(&mut self, process_name: &str, now: DateTime<Utc>) -> bool {
+        if self.cpu_idle_threshold <= 0.0 || process_name.starts_with(WEB_GAME_PREFIX) {
+            return false;
+        }
+
+        let key = normalize_process_name(process_name);
+        let cpu_usage: f32 = self.scanned_processes()
+            .filter(|(_, process)| normalize_process_name(process.name()) == key)
+            .map(|(_, process)| process.cpu_usage())
+            .sum();
+
+        if cpu_usage >= self.cpu_idle_threshold {
+            self.low_cpu_since.remove(process_name);
+            return false;
+        }
+
+        let since = *self.low_cpu_since.entry(process_name.to_string()).or_insert(now);
+        (now - since).num_seconds() >= self.cpu_idle_seconds
+    }
+
+    // PIDs of every running process with this name (none for web games)
+    fn pids_for(&self, process_name: &str) -> Vec<u32> {
+        if process_name.starts_with(WEB_GAME_PREFIX) {
+            return Vec::new();
+        }
+
+        let key = normalize_process_name(process_name);
+        let mut pids: Vec<u32> = self.scanned_processes()
+            .filter(|(_, process)| normalize_process_name(process.name()) == key)
+            .map(|(pid, _)| pid.as_u32())
+            .collect();
+        pids.sort_unstable();
+        pids
+    }
+
+    // Marks a session that has just ended as concurrent with every session whose
+    // time actually overlapped its own: those still running, and those that ended
+    // earlier but after it started. Each session settles this for itself when it
+    // ends, so both sides of an overlap agree. Must be called after end_session().
+    fn settle_concurrency(&mut self, session: &mut GameSession) {
+        let end = match session.end_time {
+            Some(end) => end,
+            None => return,
+        };
+
+        let running = self.active_sessions.iter()
+            .filter(|other| other.id != session.id && session.overlaps(other.start_time, end))
+            .filter_map(|other| other.id.clone());
+        let ended = self.ended_intervals.iter()
+            .filter(|(_, start, other_end)| session.overlaps(*start, *other_end))
+            .map(|(id, _, _)| id.clone());
+        session.concurrent_session_ids = running.chain(ended).collect();
+        session.is_concurrent = !session.concurrent_session_ids.is_empty();
+
+        if let Some(id) = &session.id {
+            self.ended_intervals.push((id.clone(), session.start_time, end));
+        }
+    }
+
+    // An ended session can only overlap an active one that started before it ended
+    fn prune_ended_intervals(&mut self) {
+        match self.active_sessions.iter().map(|session| session.start_time).min() {
+            Some(earliest_start) => self.ended_intervals.retain(|(_, _, end)| *end > earliest_start),
+            None => self.ended_intervals.clear(),
+        }
     }
 
     pub fn get_active_sessions(&self) -> Vec<GameSession> {
         self.active_sessions.clone()
     }
 
+    // Returns false when no open session has this id
+    pub fn set_session_social(&mut self, id: &str, is_social: bool) -> bool {
+        match self.active_sessions.iter_mut().find(|session| session.id.as_deref() == Some(id)) {
+            Some(session) => {
+                session.is_social_session = is_social;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Ends every open session, for when the app is shutting down or another
+    // profile is made active. Returns them
+    // along with any finished sessions still waiting to be saved.
+    pub fn end_all_sessions(&mut self, reason: SessionEndReason) -> Vec<GameSession> {
+        while let Some(mut session) = self.active_sessions.pop() {
+            // A game already gone but still inside the merge window ended when last seen
+            let gone = self.last_update.is_some_and(|last| session.last_seen < last);
+            session.end_session(if gone { session.last_seen } else { Utc::now() });
+            session.end_reason = Some(reason);
+            self.low_cpu_since.remove(&session.process_name);
+            self.settle_concurrency(&mut session);
+
+            info!("Game session ended ({}): {}", reason.as_str(), session.game_name);
+            self.completed_sessions.push(session);
+        }
+        self.prune_ended_intervals();
+        self.idle_seconds = 0;
+        self.locked_seconds = 0;
+
+        self.get_completed_sessions()
+    }
+
+    // Called after the PC wakes up. Open sessions end at the last tick before
+    // the gap and are queued for saving; games still running are picked up
+    // as new sessions by the next update, so the sleep is never billed.
+    pub fn handle_resume_gap(&mut self, gap_seconds: i64) {
+        info!("Resumed after {}s asleep; splitting {} open sessions", gap_seconds, self.active_sessions.len());
+
+        while let Some(mut session) = self.active_sessions.pop() {
+            let last_seen = session.last_seen;
+            session.end_session(last_seen);
+            session.end_reason = Some(SessionEndReason::Sleep);
+            self.settle_concurrency(&mut session);
+            self.completed_sessions.push(session);
+        }
+        self.prune_ended_intervals();
+        self.idle_seconds = 0;
+        self.locked_seconds = 0;
+        // CPU readings and the tick before the gap say nothing about the time after it
+        self.low_cpu_since.clear();
+        self.last_update = None;
+    }
+
     pub fn get_completed_sessions(&mut self) -> Vec<GameSession> {
         let completed = self.completed_sessions.clone();
         self.completed_sessions.clear();
         completed
     }
 
+    // Puts back sessions that couldn't be saved so the next tick retries them
+    pub fn requeue_completed_sessions(&mut self, sessions: Vec<GameSession>) {
+        let mut sessions = sessions;
+        sessions.append(&mut self.completed_sessions);
+        self.completed_sessions = sessions;
+    }
+
+    // Ends the session for a process the app killed. Doing this here, under the
+    // same lock as update(), means the next detection cycle can't also end it.
+    fn end_session_force_closed(&mut self, process_name: &str) {
+        if let Some(index) = self.active_sessions.iter().position(|session| session.process_name == process_name) {
+            let mut session = self.active_sessions.remove(index);
+            session.end_session(Utc::now());
+            session.end_reason = Some(SessionEndReason::ForceClose);
+            self.low_cpu_since.remove(&session.process_name);
+            self.settle_concurrency(&mut session);
+
+            info!("Game session force-closed: {}{}", session.game_name,
+                  if session.is_concurrent { " [CONCURRENT]" } else { "" });
+            self.completed_sessions.push(session);
+        }
+        self.prune_ended_intervals();
+
+        if self.active_sessions.is_empty() {
+            self.idle_seconds = 0;
+            self.locked_seconds = 0;
+        }
+    }
+
     pub fn get_total_active_time(&self) -> i64 {
         if self.active_sessions.is_empty() {
             return 0;
@@ -212,12 +950,122 @@ impl GameMonitor {
             .min()
             .unwrap_or(chrono::Utc::now());
 
-        (chrono::Utc::now() - earliest_start).num_seconds()
+        ((chrono::Utc::now() - earliest_start).num_seconds() - self.idle_seconds - self.locked_seconds).max(0)
+    }
+
+    // Single place deciding what a process is, shared by detection, closing and
+    // the permission check. The blacklist always wins, then known games, then
+    // the Steam heuristic (Steam games often have random exe names), then
+    // anything a known launcher started.
+    fn classify_process(&self, process: &sysinfo::Process) -> ProcessClass {
+        self.classify(process.pid().as_u32(), process.name(), process.exe(), || self.is_launcher_child(process))
+    }
+
+    // classify_process without the live process, which sysinfo can't build
+    // outside a real process list. Walking up to a launcher needs the other
+    // processes, so that check is passed in.
+    fn classify(&self, pid: u32, name: &str, exe: &Path, is_launcher_child: impl FnOnce() -> bool) -> ProcessClass {
+        let key = normalize_process_name(name);
+
+        if self.blacklisted_processes.contains(&key) {
+            ProcessClass::Blacklisted
+        } else if let Some(display_name) = self.known_game_name(pid, name, exe, &key) {
+            if self.launchers.contains(&key) {
+                ProcessClass::Launcher(display_name)
+            } else {
+                ProcessClass::KnownGame(display_name)
+            }
+        } else if !self.whitelist_only && self.is_likely_steam_game(exe) {
+            ProcessClass::SteamGame(self.get_steam_game_name(name, exe))
+        } else if !self.whitelist_only && is_launcher_child() {
+            ProcessClass::LauncherChild(self.get_steam_game_name(name, exe))
+        } else {
+            ProcessClass::Other
+        }
+    }
+
+    // A process started by a known launcher from outside the launcher's own
+    // folder is the game it launched. Web helpers, updaters and crash
+    // reporters ship next to the launcher, so they aren't counted, and
+    // neither are processes the game itself starts (crash handlers, browser
+    // subprocesses), which belong to the game's session. Browsers, chat
+    // clients, overlays and the tracker itself are never counted either,
+    // wherever they were installed.
+    fn is_launcher_child(&self, process: &sysinfo::Process) -> bool {
+        if self.launchers.is_empty() || process.exe().as_os_str().is_empty()
+            || self.never_launcher_child(&normalize_process_name(process.name())) {
+            return false;
+        }
+
+        let parent = process.parent().map(|pid| pid.as_u32());
+        let launcher_pid = match find_launcher_ancestor(
+            parent,
+            |pid| self.system.process(Pid::from_u32(pid))
+                .map(|ancestor| (normalize_process_name(ancestor.name()), ancestor.parent().map(|pid| pid.as_u32()))),
+            |name| self.launchers.contains(name),
+        ) {
+            Some(pid) => pid,
+            None => return false,
+        };
+        let launcher_dir = match self.system.process(Pid::from_u32(launcher_pid)).and_then(|launcher| launcher.exe().parent()) {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => return false,
+        };
+
+        let parent_is_game = parent
+            .filter(|pid| *pid != launcher_pid)
+            .and_then(|pid| self.system.process(Pid::from_u32(pid)))
+            .is_some_and(|parent| !parent.exe().starts_with(launcher_dir));
+        !process.exe().starts_with(launcher_dir) && !parent_is_game
+    }
+
+    // `key` is the process's normalized name
+    fn never_launcher_child(&self, key: &str) -> bool {
+        LAUNCHER_CHILD_IGNORED.contains(&key)
+            || self.blacklisted_processes.iter().any(|name| name == key)
+            || self.browser_processes.iter().any(|browser| normalize_process_name(browser) == key)
+            || Self::own_process_name().as_deref() == Some(key)
+    }
+
+    // A game configured with a window title pattern wins when one of the
+    // process's windows matches it, so games sharing a generic executable can be
+    // told apart. Otherwise, a game configured with a path only matches
+    // executables under that path, so another program shipping the same file
+    // name isn't tracked as it. Name-only matching is used just for processes
+    // with no path configured. Process name patterns come last, so an exact
+    // entry always beats a pattern that happens to match too.
+    // `key` is the process's normalized name
+    fn known_game_name(&self, pid: u32, name: &str, exe: &Path, key: &str) -> Option<String> {
+        let titles = self.window_titles.get(&pid);
+        let title_match = self.title_games.iter()
+            .filter(|(name, _, _)| name == key)
+            .find(|(_, pattern, _)| titles.into_iter().flatten().any(|title| pattern.is_match(title)));
+        if let Some((_, _, display_name)) = title_match {
+            return Some(display_name.clone());
+        }
+
+        let mut path_entries = self.path_games.iter()
+            .filter(|(name, _, _)| name == key)
+            .peekable();
+
+        let exact_match = if path_entries.peek().is_none() {
+            self.known_games.get(key).cloned()
+        } else {
+            let exe_path = exe.to_string_lossy().to_lowercase();
+            path_entries
+                .find(|(_, pattern, _)| exe_path.contains(pattern.as_str()))
+                .map(|(_, _, display_name)| display_name.clone())
+        };
+
+        exact_match.or_else(|| {
+            self.pattern_games.iter()
+                .find(|(pattern, _)| pattern.is_match(name))
+                .map(|(_, display_name)| display_name.clone())
+        })
     }
 
-    fn is_likely_steam_game(&self, process: &sysinfo::Process) -> bool {
+    fn is_likely_steam_game(&self, exe_path: &Path) -> bool {
         // Check if process is running from Steam directory
-        let exe_path = process.exe();
         if let Some(path_str) = exe_path.to_str() {
             return path_str.contains("steamapps") ||
                    path_str.contains("Steam\\steamapps") ||
@@ -226,8 +1074,37 @@ impl GameMonitor {
         false
     }
 
-    fn get_steam_game_name(&self, process_name: &str) -> String {
-        // Try to extract a readable name from the process
+    // Reads the real names of Steam games seen for the first time from the
+    // library's appmanifest files. Misses are cached too, so each executable
+    // only costs one look at the disk.
+    fn resolve_steam_names(&mut self) {
+        let mut new_paths: Vec<String> = self.scanned_processes()
+            .filter(|(_, process)| self.is_likely_steam_game(process.exe()))
+            .filter_map(|(_, process)| process.exe().to_str().map(|path| path.to_string()))
+            .filter(|path| !self.steam_names.contains_key(path))
+            .collect();
+        new_paths.sort();
+        new_paths.dedup();
+
+        for path in new_paths {
+            let name = steam_manifest_name(Path::new(&path));
+            match &name {
+                Some(name) => info!("Resolved Steam game name: {} -> {}", path, name),
+                None => info!("No Steam manifest matches {}", path),
+            }
+            self.steam_names.insert(path, name);
+        }
+    }
+
+    fn get_steam_game_name(&self, process_name: &str, exe: &Path) -> String {
+        let manifest_name = exe.to_str()
+            .and_then(|path| self.steam_names.get(path))
+            .and_then(|name| name.clone());
+        if let Some(name) = manifest_name {
+            return name;
+        }
+
+        // Otherwise try to extract a readable name from the process
         let name = process_name
             .trim_end_matches(".exe")
             .replace("_", " ")
@@ -247,7 +1124,25 @@ impl GameMonitor {
     }
 
     pub fn get_detected_games(&self) -> Vec<String> {
-        self.known_games.values().cloned().collect()
+        self.known_games.values()
+            .chain(self.path_games.iter().map(|(_, _, display_name)| display_name))
+            .chain(self.title_games.iter().map(|(_, _, display_name)| display_name))
+            .chain(self.pattern_games.iter().map(|(_, display_name)| display_name))
+            .cloned()
+            .collect()
+    }
+
+    pub fn known_game_count(&self) -> usize {
+        self.known_games.len() + self.path_games.len() + self.title_games.len() + self.pattern_games.len()
+    }
+
+    // Refreshes the full process list and returns how many processes are
+    // visible. Only this app's own process means enumeration is blocked.
+    pub fn visible_process_count(&mut self) -> usize {
+        let refresh_kind = self.process_refresh_kind();
+        self.system.refresh_processes_specifics(refresh_kind);
+        self.targeted_scan = false;
+        self.system.processes().len()
     }
 
     pub fn pause(&mut self) {
@@ -255,45 +1150,765 @@ impl GameMonitor {
         info!("Game monitoring paused");
     }
 
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
     pub fn resume(&mut self) {
         self.is_paused = false;
         info!("Game monitoring resumed");
     }
 
+    pub fn set_web_game_detection(&mut self, enabled: bool) {
+        self.web_game_detection_enabled = enabled;
+        info!("Web game detection {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    // "auto" also counts likely Steam games and web games; "whitelist" only
+    // counts games in the known and custom games lists
+    pub fn set_detection_mode(&mut self, mode: &str) {
+        self.whitelist_only = mode == "whitelist";
+        info!("Detection mode: {}", if self.whitelist_only { "whitelist" } else { "auto" });
+    }
+
+    pub fn set_trust_startup_detections(&mut self, trusted: bool) {
+        self.trust_startup_detections = trusted;
+    }
+
+    pub fn set_steam_name_confirmation(&mut self, enabled: bool) {
+        self.confirm_steam_names = enabled;
+        info!("Steam name confirmation {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    // Only Windows can tell which window is in front; elsewhere every game
+    // keeps counting as if it were
+    pub fn set_count_foreground_only(&mut self, enabled: bool) {
+        self.count_foreground_only = enabled;
+        info!("Foreground-only counting {}", if enabled { "enabled" } else { "disabled" });
+    }
+
+    pub fn set_input_idle_threshold(&mut self, minutes: i32) {
+        self.input_idle_seconds = minutes.max(0) as i64 * 60;
+    }
+
+    pub fn set_curfew_windows(&mut self, windows: Vec<CurfewWindow>) {
+        self.curfew_windows = windows;
+    }
+
+    // Only sessions starting after this count against the new profile; open
+    // ones are ended with end_all_sessions first
+    pub fn set_active_profile(&mut self, profile_id: &str) {
+        self.profile_id = profile_id.to_string();
+    }
+
+    // Whether gaming is blocked outright at this local time
+    pub fn in_curfew(&self, now: DateTime<Local>) -> bool {
+        let weekday = now.weekday().num_days_from_sunday();
+        let time = now.time();
+        self.curfew_windows.iter().any(|window| window.contains(weekday, time))
+    }
+
+    pub fn set_cpu_idle_detection(&mut self, threshold_percent: f32, idle_seconds: i64) {
+        self.cpu_idle_threshold = threshold_percent.max(0.0);
+        self.cpu_idle_seconds = idle_seconds.max(0);
+        self.low_cpu_since.clear();
+    }
+
+    // Returns the reminder number when an over-budget nag is due, spacing
+    // reminders at least `interval_minutes` apart.
+    pub fn next_nag(&mut self, now: DateTime<Utc>, interval_minutes: i32) -> Option<u32> {
+        if interval_minutes <= 0 {
+            return None;
+        }
+
+        let due = match self.last_nag {
+            None => true,
+            Some(last) => (now - last).num_minutes() >= interval_minutes as i64,
+        };
+
+        if !due {
+            return None;
+        }
+
+        self.last_nag = Some(now);
+        self.nag_count += 1;
+        Some(self.nag_count)
+    }
+
+    pub fn reset_nag(&mut self) {
+        self.last_nag = None;
+        self.nag_count = 0;
+    }
+
+    // Snapshots the open sessions so a crash loses at most a few seconds of play.
+    // Written whenever sessions start or end, and otherwise every few seconds.
+    pub fn persist_active(&mut self, db: &Database) -> Result<(), String> {
+        let now = Utc::now();
+        let ids: Vec<Option<String>> = self.active_sessions.iter().map(|session| session.id.clone()).collect();
+        let due = match &self.last_persisted {
+            None => true,
+            Some((at, persisted_ids)) => *persisted_ids != ids || (now - *at).num_seconds() >= ACTIVE_SNAPSHOT_SECONDS,
+        };
+
+        if !due {
+            return Ok(());
+        }
+
+        db.save_active_sessions(&self.active_sessions)?;
+        self.last_persisted = Some((now, ids));
+        Ok(())
+    }
+
+    // Starts the grace period before games are force-closed. Calling it again
+    // while a countdown is running keeps the original deadline.
+    pub fn begin_enforcement_countdown(&mut self, now: DateTime<Utc>, grace_seconds: i64) -> DateTime<Utc> {
+        *self.enforcement_deadline.get_or_insert(now + Duration::seconds(grace_seconds.max(0)))
+    }
+
+    pub fn enforcement_due(&self, now: DateTime<Utc>) -> bool {
+        self.enforcement_deadline.is_some_and(|deadline| now >= deadline)
+    }
+
+    // Seconds until games are closed, or None when no countdown is running
+    pub fn enforcement_seconds_left(&self, now: DateTime<Utc>) -> Option<i64> {
+        self.enforcement_deadline.map(|deadline| (deadline - now).num_seconds().max(0))
+    }
+
+    pub fn cancel_enforcement(&mut self) {
+        self.enforcement_deadline = None;
+    }
+
+    pub fn set_game_notification_pref(&mut self, process_name: &str, notify_on_limit: bool) {
+        let key = normalize_process_name(process_name);
+        if notify_on_limit {
+            self.silenced_games.remove(&key);
+        } else {
+            self.silenced_games.insert(key);
+        }
+    }
+
+    pub fn set_game_exempt(&mut self, process_name: &str, exempt: bool) {
+        let key = normalize_process_name(process_name);
+        if exempt {
+            self.exempt_games.insert(key);
+        } else {
+            self.exempt_games.remove(&key);
+        }
+    }
+
+    fn is_exempt(&self, process_name: &str) -> bool {
+        self.exempt_games.contains(&normalize_process_name(process_name))
+    }
+
+    // Open sessions that count against the budget, i.e. all but exempt games
+    pub fn get_billable_sessions(&self) -> Vec<GameSession> {
+        self.active_sessions.iter()
+            .filter(|session| !self.is_exempt(&session.process_name))
+            .cloned()
+            .collect()
+    }
+
+    // Limit warnings fire only while at least one open game still wants them
+    pub fn should_notify_limit(&self) -> bool {
+        self.active_sessions.iter()
+            .any(|session| !self.is_exempt(&session.process_name)
+                && !self.silenced_games.contains(&normalize_process_name(&session.process_name)))
+    }
+
     pub fn add_game(&mut self, process_name: String, display_name: String) {
-        self.known_games.insert(process_name, display_name);
+        self.known_games.insert(normalize_process_name(&process_name), display_name);
     }
 
-    pub fn close_detected_games(&self) -> Vec<String> {
-        let mut closed_games = Vec::new();
+    // Applies a confirmed name to sessions still running under a provisional one
+    pub fn rename_active_sessions(&mut self, process_name: &str, display_name: &str) {
+        let key = normalize_process_name(process_name);
+        for session in self.active_sessions.iter_mut().filter(|s| normalize_process_name(&s.process_name) == key) {
+            session.game_name = display_name.to_string();
+        }
+    }
 
-        for (_pid, process) in self.system.processes() {
-            let process_name = process.name();
+    // Checks what the app is allowed to do without touching any other process
+    pub fn check_permissions(&mut self) -> PermissionReport {
+        self.system.refresh_processes();
 
-            // Skip blacklisted processes
-            if self.blacklisted_processes.contains(&process_name.to_string()) {
+        let mut notes = Vec::new();
+        let is_elevated = platform::is_elevated();
+        let process_enumeration = self.system.processes().len() > 1;
+        if !process_enumeration {
+            notes.push("Only this app's own process is visible, so games can't be detected.".to_string());
+        }
+
+        // Without elevation we can only close processes running as our own user
+        let own_user = sysinfo::get_current_pid().ok()
+            .and_then(|pid| self.system.process(pid))
+            .and_then(|process| process.user_id().cloned());
+        let mut foreign_games: Vec<String> = self.system.processes().values()
+            .filter(|process| matches!(self.classify_process(process),
+                                       ProcessClass::KnownGame(_) | ProcessClass::SteamGame(_) | ProcessClass::LauncherChild(_)))
+            .filter(|process| own_user.is_none() || process.user_id() != own_user.as_ref())
+            .map(|process| process.name().to_string())
+            .collect();
+        foreign_games.sort();
+        foreign_games.dedup();
+
+        let process_termination = is_elevated == Some(true) || foreign_games.is_empty();
+        if !process_termination {
+            notes.push(format!("These games run as another user and can't be closed without elevation: {}",
+                               foreign_games.join(", ")));
+        }
+
+        let window_inspection = cfg!(target_os = "windows");
+        if !window_inspection {
+            notes.push("Reading window titles isn't supported on this platform, so web games aren't detected.".to_string());
+        }
+
+        if cfg!(target_os = "windows") && is_elevated == Some(false) {
+            notes.push("Not running as administrator; games started elevated can't be closed.".to_string());
+        }
+
+        if let Some(scope) = platform::ptrace_scope() {
+            if scope > 0 {
+                notes.push(format!("ptrace is restricted (ptrace_scope = {}); closing games only uses signals, \
+                                    which need the same user or root.", scope));
+            }
+        }
+
+        PermissionReport {
+            process_enumeration,
+            process_termination,
+            window_inspection,
+            is_elevated,
+            notes,
+        }
+    }
+
+    // Starts closing detected games (or only `only`). Closing is done in steps
+    // (close_attempt, refresh_close, then finish_close) so the caller can
+    // release the monitor lock while the processes exit.
+    pub fn begin_close(&mut self, only: Option<&str>) -> PendingClose {
+        // Always close from a full, fresh process list, even between full scans
+        let refresh_kind = self.process_refresh_kind();
+        self.system.refresh_processes_specifics(refresh_kind);
+        self.targeted_scan = false;
+
+        let only_key = only.map(normalize_process_name);
+
+        // Group instances by name so a game with several processes is closed as one
+        let mut targets: HashMap<String, (String, Vec<u32>)> = HashMap::new();
+        for (pid, process) in self.system.processes() {
+            if only_key.as_ref().is_some_and(|key| *key != normalize_process_name(process.name())) {
+                continue;
+            }
+            if self.is_exempt(process.name()) {
                 continue;
             }
 
-            // Check if it's a gaming process we should close
-            let should_close = self.known_games.contains_key(process_name) ||
-                               self.is_likely_steam_game(process);
+            // Launchers are left open; the games they started are closed instead
+            let display_name = match self.classify_process(process) {
+                ProcessClass::KnownGame(display_name) | ProcessClass::SteamGame(display_name)
+                | ProcessClass::LauncherChild(display_name) => display_name,
+                ProcessClass::Launcher(_) | ProcessClass::Blacklisted | ProcessClass::Other => continue,
+            };
+
+            targets.entry(process.name().to_string())
+                .or_insert_with(|| (display_name, Vec::new()))
+                .1.push(pid.as_u32());
+        }
+
+        // Include every instance a tracked session knows about
+        for session in &self.active_sessions {
+            if let Some((_, pids)) = targets.get_mut(&session.process_name) {
+                for pid in &session.pids {
+                    if !pids.contains(pid) {
+                        pids.push(*pid);
+                    }
+                }
+            }
+        }
+
+        self.pending_close(targets, CLOSE_ATTEMPTS)
+    }
+
+    fn pending_close(&mut self, targets: HashMap<String, (String, Vec<u32>)>, attempts: u32) -> PendingClose {
+        self.closing.extend(targets.keys().cloned());
+        PendingClose { targets, refused: HashSet::new(), attempt: 0, attempts: attempts.max(1) }
+    }
 
-            if should_close {
-                let display_name = self.known_games.get(process_name)
-                    .cloned()
-                    .unwrap_or_else(|| self.get_steam_game_name(process_name));
+    // The first attempt asks politely where the platform allows it; later ones
+    // kill outright, and the last one on Windows also terminates the process
+    // tree, since many games run as a wrapper around child processes
+    pub fn close_attempt(&mut self, pending: &mut PendingClose) {
+        pending.attempt += 1;
+        pending.refused.clear();
+        for (process_name, (_, pids)) in &pending.targets {
+            for pid in pids {
+                let process = match self.system.process(Pid::from_u32(*pid)) {
+                    Some(process) => process,
+                    None => continue, // Already exited
+                };
 
-                // Attempt to close the process
-                if process.kill() {
-                    info!("Closed game: {}", display_name);
-                    closed_games.push(display_name);
+                let accepted = if pending.attempt == 1 {
+                    process.kill_with(Signal::Term).unwrap_or_else(|| process.kill())
+                } else if pending.attempt == pending.attempts && platform::terminate_process_tree(*pid) {
+                    true
                 } else {
-                    info!("Failed to close game: {}", display_name);
+                    process.kill()
+                };
+                if !accepted {
+                    pending.refused.insert(process_name.clone());
                 }
             }
         }
+    }
+
+    // Drops the instances that have exited since the last attempt
+    pub fn refresh_close(&mut self, pending: &mut PendingClose) {
+        let refresh_kind = self.process_refresh_kind();
+        // refresh_process reports whether the process still exists
+        for (_, pids) in pending.targets.values_mut() {
+            pids.retain(|pid| self.system.refresh_process_specifics(Pid::from_u32(*pid), refresh_kind));
+        }
+    }
+
+    // A game only counts as closed once every instance is gone. Its session
+    // is ended here, as force-closed, and only here.
+    pub fn finish_close(&mut self, pending: PendingClose) -> CloseResult {
+        let mut result = CloseResult::default();
+        for (process_name, (display_name, pids)) in pending.targets {
+            self.closing.remove(&process_name);
+            if pids.is_empty() {
+                info!("Closed game: {}", display_name);
+                self.end_session_force_closed(&process_name);
+                result.closed.push(display_name);
+            } else if pending.refused.contains(&process_name) {
+                info!("Failed to close game: {} ({} instance{} could not be killed)",
+                      display_name, pids.len(), if pids.len() == 1 { "" } else { "s" });
+                result.failed.push(display_name);
+            } else {
+                info!("Game resisted closing: {} ({} instance{} still running after {} attempts)",
+                      display_name, pids.len(), if pids.len() == 1 { "" } else { "s" }, pending.attempt);
+                result.resisted.push(display_name);
+            }
+        }
+
+        result
+    }
+}
+
+// Name of the Steam game installed at `steamapps/common/<installdir>/...`, taken
+// from the appmanifest_*.acf whose installdir matches.
+fn steam_manifest_name(exe_path: &Path) -> Option<String> {
+    let (steamapps, install_dir) = exe_path.ancestors().find_map(|dir| {
+        let common = dir.parent()?;
+        let steamapps = common.parent()?;
+        let is_library = common.file_name()?.eq_ignore_ascii_case("common")
+            && steamapps.file_name()?.eq_ignore_ascii_case("steamapps");
+        if is_library { Some((steamapps, dir.file_name()?.to_str()?)) } else { None }
+    })?;
+
+    for entry in std::fs::read_dir(steamapps).ok()?.flatten() {
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if !file_name.starts_with("appmanifest_") || !file_name.ends_with(".acf") {
+            continue;
+        }
+
+        let text = match std::fs::read_to_string(entry.path()) {
+            Ok(text) => text,
+            Err(_) => continue,
+        };
+        let values = parse_acf_values(&text);
+        if values.get("installdir").is_some_and(|dir| dir.eq_ignore_ascii_case(install_dir)) {
+            if let Some(name) = values.get("name") {
+                return Some(name.clone());
+            }
+        }
+    }
+
+    None
+}
+
+enum AcfToken {
+    Open,
+    Close,
+    Text(String),
+}
+
+// Splits ACF/VDF text into quoted or bare strings and braces, skipping comments
+fn acf_tokens(text: &str) -> Vec<AcfToken> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => tokens.push(AcfToken::Open),
+            '}' => tokens.push(AcfToken::Close),
+            '"' => {
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('n') => value.push('\n'),
+                            Some('t') => value.push('\t'),
+                            Some(escaped) => value.push(escaped),
+                            None => break,
+                        },
+                        _ => value.push(c),
+                    }
+                }
+                tokens.push(AcfToken::Text(value));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                while chars.next().is_some_and(|c| c != '\n') {}
+            }
+            c if c.is_whitespace() => {}
+            _ => {
+                let mut value = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || matches!(next, '{' | '}' | '"') {
+                        break;
+                    }
+                    value.push(next);
+                    chars.next();
+                }
+                tokens.push(AcfToken::Text(value));
+            }
+        }
+    }
+
+    tokens
+}
+
+// String values directly inside the root block of an ACF file (e.g. "AppState"),
+// keyed in lowercase since Steam isn't consistent about key case
+fn parse_acf_values(text: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut depth = 0;
+    let mut key: Option<String> = None;
+
+    for token in acf_tokens(text) {
+        match token {
+            AcfToken::Open => {
+                depth += 1;
+                key = None;
+            }
+            AcfToken::Close => {
+                depth -= 1;
+                key = None;
+            }
+            AcfToken::Text(text) => match key.take() {
+                None => key = Some(text),
+                Some(name) => {
+                    if depth == 1 {
+                        values.insert(name.to_lowercase(), text);
+                    }
+                }
+            },
+        }
+    }
+
+    values
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // pid -> (normalized name, parent pid)
+    fn process_map() -> HashMap<u32, (String, Option<u32>)> {
+        [
+            (1, ("explorer", None)),
+            (10, ("steam", Some(1))),
+            (20, ("eldenring", Some(10))),
+            (30, ("start_protected_game", Some(10))),
+            (31, ("bootstrapper", Some(30))),
+            (32, ("somegame", Some(31))),
+            (33, ("crashpad_handler", Some(32))),
+            (40, ("notepad", Some(1))),
+        ].into_iter()
+            .map(|(pid, (name, parent))| (pid, (name.to_string(), parent)))
+            .collect()
+    }
+
+    fn launcher_of(pid: u32) -> Option<u32> {
+        let map = process_map();
+        let parent = map.get(&pid).and_then(|(_, parent)| *parent);
+        find_launcher_ancestor(parent, |pid| map.get(&pid).cloned(), |name| name == "steam")
+    }
+
+    #[test]
+    fn finds_launcher_that_started_the_game() {
+        assert_eq!(launcher_of(20), Some(10));
+    }
+
+    #[test]
+    fn finds_launcher_through_bootstrappers() {
+        assert_eq!(launcher_of(31), Some(10));
+        assert_eq!(launcher_of(32), Some(10));
+    }
+
+    #[test]
+    fn stops_looking_past_the_tree_depth() {
+        assert_eq!(launcher_of(33), None);
+    }
+
+    #[test]
+    fn ignores_processes_not_under_a_launcher() {
+        assert_eq!(launcher_of(40), None);
+        assert_eq!(launcher_of(10), None);
+    }
+
+    #[test]
+    fn stops_at_a_missing_parent() {
+        let map = process_map();
+        assert_eq!(find_launcher_ancestor(Some(99), |pid| map.get(&pid).cloned(), |name| name == "steam"), None);
+    }
 
-        closed_games
+    #[test]
+    fn browsers_chat_and_blacklisted_processes_are_never_launcher_children() {
+        let monitor = GameMonitor::new();
+        for name in ["chrome", "firefox", "discord", "gameoverlayui", "steamwebhelper"] {
+            assert!(monitor.never_launcher_child(name), "{} should be ignored", name);
+        }
+        assert!(!monitor.never_launcher_child("eldenring"));
+    }
+
+    #[test]
+    fn a_game_being_closed_is_ended_once_as_force_closed() {
+        let mut monitor = GameMonitor::new();
+        let mut session = GameSession::new("Some Game".to_string(), "somegame.exe".to_string());
+        session.last_seen = Utc::now() - chrono::Duration::minutes(5);
+        monitor.active_sessions.push(session);
+
+        // The game exits mid-close; the detection tick must leave its session alone
+        let targets = HashMap::from([("somegame.exe".to_string(), ("Some Game".to_string(), Vec::new()))]);
+        let pending = monitor.pending_close(targets, 1);
+        monitor.end_exited_sessions(&[], Utc::now());
+        assert_eq!(monitor.get_active_sessions().len(), 1);
+        assert!(monitor.get_completed_sessions().is_empty());
+
+        let result = monitor.finish_close(pending);
+        assert_eq!(result.closed, vec!["Some Game".to_string()]);
+        let completed = monitor.get_completed_sessions();
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].end_reason, Some(SessionEndReason::ForceClose));
+        assert!(monitor.get_active_sessions().is_empty());
+
+        // Once closing is over, nothing is left for the next tick to end again
+        monitor.end_exited_sessions(&[], Utc::now());
+        assert!(monitor.get_completed_sessions().is_empty());
+    }
+
+    fn monitor_with_session_last_seen(ago: chrono::Duration) -> (GameMonitor, GameSession) {
+        let mut monitor = GameMonitor::new();
+        let mut session = GameSession::new("Some Game".to_string(), "somegame.exe".to_string());
+        session.start_time = Utc::now() - chrono::Duration::hours(1);
+        session.last_seen = Utc::now() - ago;
+        monitor.active_sessions.push(session.clone());
+        (monitor, session)
+    }
+
+    #[test]
+    fn a_ten_second_restart_keeps_the_same_session() {
+        let (mut monitor, session) = monitor_with_session_last_seen(chrono::Duration::seconds(10));
+
+        monitor.end_exited_sessions(&[], Utc::now());
+        monitor.end_exited_sessions(&["somegame.exe".to_string()], Utc::now());
+
+        let active = monitor.get_active_sessions();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].id, session.id);
+        assert!(monitor.get_completed_sessions().is_empty());
+    }
+
+    #[test]
+    fn a_five_minute_gap_ends_the_session_when_last_seen() {
+        let (mut monitor, session) = monitor_with_session_last_seen(chrono::Duration::minutes(5));
+
+        monitor.end_exited_sessions(&[], Utc::now());
+
+        assert!(monitor.get_active_sessions().is_empty());
+        let completed = monitor.get_completed_sessions();
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].end_time, Some(session.last_seen));
+        assert_eq!(completed[0].end_reason, Some(SessionEndReason::Natural));
     }
-}
\ No newline at end of file
+
+    fn game_config(process_name: &str, display_name: &str) -> GameConfig {
+        GameConfig {
+            process_name: process_name.to_string(),
+            display_name: display_name.to_string(),
+            is_monitored: true,
+            notify_on_limit: true,
+            path_pattern: None,
+            is_launcher: false,
+            title_pattern: None,
+            kind: GameMatchKind::Exact,
+            is_exempt: false,
+        }
+    }
+
+    fn classify(monitor: &GameMonitor, name: &str, exe: &str) -> ProcessClass {
+        monitor.classify(1000, name, Path::new(exe), || false)
+    }
+
+    #[test]
+    fn a_path_qualified_game_only_matches_under_its_path() {
+        let mut monitor = GameMonitor::new();
+        monitor.load_custom_games(vec![GameConfig {
+            path_pattern: Some("Games/Alpha".to_string()),
+            ..game_config("game.exe", "Alpha")
+        }]);
+
+        assert_eq!(classify(&monitor, "game.exe", "/home/me/Games/Alpha/game.exe"), ProcessClass::KnownGame("Alpha".to_string()));
+        assert_eq!(classify(&monitor, "game.exe", "/home/me/Games/Beta/game.exe"), ProcessClass::Other);
+    }
+
+    #[test]
+    fn a_launcher_on_its_own_is_shown_but_never_billed() {
+        let mut monitor = GameMonitor::new();
+        monitor.load_custom_games(GameMonitor::default_games());
+
+        assert_eq!(classify(&monitor, "steam.exe", "C:\\Program Files (x86)\\Steam\\steam.exe"),
+                   ProcessClass::Launcher("Steam".to_string()));
+        assert!(monitor.get_active_sessions().is_empty());
+        assert_eq!(monitor.get_total_active_time(), 0);
+    }
+
+    #[test]
+    fn process_names_match_whatever_their_case_or_extension() {
+        assert_eq!(normalize_process_name("VALORANT.EXE"), "valorant");
+        assert_eq!(normalize_process_name(" Valorant.exe "), "valorant");
+        assert_eq!(normalize_process_name("valorant"), "valorant");
+        assert_eq!(normalize_process_name(".exe"), ".exe");
+
+        let mut monitor = GameMonitor::new();
+        monitor.load_custom_games(vec![game_config("Valorant.exe", "Valorant")]);
+        assert_eq!(classify(&monitor, "VALORANT.EXE", "C:\\Riot Games\\VALORANT\\VALORANT.EXE"),
+                   ProcessClass::KnownGame("Valorant".to_string()));
+        assert_eq!(classify(&monitor, "valorant", "/opt/valorant/valorant"),
+                   ProcessClass::KnownGame("Valorant".to_string()));
+    }
+
+    #[test]
+    fn a_game_with_two_instances_is_only_closed_once_both_are_gone() {
+        // This test process stands in for an instance that is still running;
+        // the other pid doesn't exist. No kill is attempted.
+        let running = std::process::id();
+        let exited = u32::MAX - 1;
+
+        let mut monitor = GameMonitor::new();
+        let mut session = GameSession::new("Some Game".to_string(), "somegame.exe".to_string());
+        session.pids = vec![running, exited];
+        monitor.active_sessions.push(session);
+
+        let targets = HashMap::from([("somegame.exe".to_string(), ("Some Game".to_string(), vec![running, exited]))]);
+        let mut pending = monitor.pending_close(targets, 1);
+        monitor.refresh_close(&mut pending);
+        assert_eq!(pending.targets["somegame.exe"].1, vec![running]);
+
+        let result = monitor.finish_close(pending);
+        assert_eq!(result.resisted, vec!["Some Game".to_string()]);
+        assert_eq!(monitor.get_active_sessions().len(), 1);
+
+        let targets = HashMap::from([("somegame.exe".to_string(), ("Some Game".to_string(), vec![exited]))]);
+        let mut pending = monitor.pending_close(targets, 1);
+        monitor.refresh_close(&mut pending);
+        assert!(pending.is_done());
+        assert_eq!(monitor.finish_close(pending).closed, vec!["Some Game".to_string()]);
+        assert_eq!(monitor.get_completed_sessions().len(), 1);
+    }
+
+    #[test]
+    fn time_spent_locked_is_not_counted() {
+        let (mut monitor, _) = monitor_with_session_last_seen(chrono::Duration::zero());
+        monitor.track_locked_time(20 * 60);
+
+        let active_minutes = monitor.get_total_active_time() / 60;
+        assert!((39..=40).contains(&active_minutes), "{} minutes", active_minutes);
+
+        let mut session = monitor.get_active_sessions().remove(0);
+        assert_eq!(session.locked_seconds, 20 * 60);
+        session.end_session(Utc::now());
+        assert!((39 * 60..=40 * 60).contains(&session.duration_seconds.unwrap()));
+    }
+
+    #[test]
+    fn each_kind_of_process_gets_its_own_class() {
+        let mut monitor = GameMonitor::new();
+        monitor.load_custom_games(vec![game_config("eldenring.exe", "Elden Ring")]);
+
+        assert_eq!(classify(&monitor, "steamwebhelper.exe", "C:\\Steam\\bin\\steamwebhelper.exe"),
+                   ProcessClass::Blacklisted);
+        assert_eq!(classify(&monitor, "eldenring.exe", "D:\\Games\\ELDEN RING\\eldenring.exe"),
+                   ProcessClass::KnownGame("Elden Ring".to_string()));
+        assert_eq!(classify(&monitor, "hades.exe", "C:\\Steam\\steamapps\\common\\Hades\\hades.exe"),
+                   ProcessClass::SteamGame("Hades".to_string()));
+        assert_eq!(monitor.classify(1000, "some_game.exe", Path::new("D:\\Epic\\SomeGame\\some_game.exe"), || true),
+                   ProcessClass::LauncherChild("Some Game".to_string()));
+        assert_eq!(classify(&monitor, "notepad.exe", "C:\\Windows\\notepad.exe"), ProcessClass::Other);
+    }
+
+    #[test]
+    fn the_blacklist_beats_a_known_game_entry() {
+        let mut monitor = GameMonitor::new();
+        monitor.load_custom_games(vec![game_config("eldenring.exe", "Elden Ring")]);
+        monitor.set_user_blacklist(vec!["EldenRing.exe".to_string()]);
+
+        assert_eq!(classify(&monitor, "eldenring.exe", "D:\\Games\\ELDEN RING\\eldenring.exe"),
+                   ProcessClass::Blacklisted);
+    }
+
+    #[test]
+    fn whitelist_mode_only_trusts_known_games() {
+        let mut monitor = GameMonitor::new();
+        monitor.whitelist_only = true;
+
+        assert_eq!(classify(&monitor, "hades.exe", "C:\\Steam\\steamapps\\common\\Hades\\hades.exe"),
+                   ProcessClass::Other);
+        assert_eq!(monitor.classify(1000, "some_game.exe", Path::new("D:\\Epic\\SomeGame\\some_game.exe"), || true),
+                   ProcessClass::Other);
+    }
+
+    // Average time per call, in microseconds, after one warm-up call
+    fn average_micros(runs: u32, mut f: impl FnMut()) -> u128 {
+        f();
+        let started = std::time::Instant::now();
+        for _ in 0..runs {
+            f();
+        }
+        started.elapsed().as_micros() / runs as u128
+    }
+
+    // Benchmark against the live process list:
+    // cargo test full_and_targeted_scan_cost -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn full_and_targeted_scan_cost() {
+        let mut monitor = GameMonitor::new();
+        monitor.load_custom_games(GameMonitor::default_games());
+
+        monitor.set_full_scan_interval(1);
+        let full = average_micros(50, || monitor.refresh_process_list(Utc::now()));
+
+        monitor.set_full_scan_interval(3600);
+        let targeted = average_micros(50, || monitor.refresh_process_list(Utc::now()));
+
+        println!("{} processes: full scan {} µs, targeted scan of {} candidates {} µs",
+                 monitor.system.processes().len(), full, monitor.candidate_pids.len(), targeted);
+    }
+
+    // cargo test process_refresh_cost -- --ignored --nocapture
+    #[test]
+    #[ignore = "benchmark"]
+    fn process_refresh_cost() {
+        let mut system = System::new();
+        let everything = average_micros(50, || system.refresh_processes());
+        let detection_only = average_micros(50, || system.refresh_processes_specifics(ProcessRefreshKind::new()));
+        let with_cpu = average_micros(50, || system.refresh_processes_specifics(ProcessRefreshKind::new().with_cpu()));
+
+        println!("{} processes: refresh_processes {} µs, name and exe only {} µs, with CPU {} µs",
+                 system.processes().len(), everything, detection_only, with_cpu);
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::{error, info};
+
+// Logging starts before the database is open, so the configured format is
+// applied afterwards by flipping this flag
+static JSON_FORMAT: AtomicBool = AtomicBool::new(false);
+
+// Where the log files live, once init has run
+static LOG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+const LOG_FILE_NAME: &str = "gtt.log";
+const LOG_MAX_BYTES: u64 = 5 * 1024 * 1024;
+const LOG_FILES_KEPT: usize = 5; // gtt.log plus gtt.log.1 ..= gtt.log.4
+
+// Writes to stderr and to <log dir>/gtt.log. Once the file reaches
+// LOG_MAX_BYTES it becomes gtt.log.1, older files shift up one, and the
+// oldest is dropped.
+struct RotatingFile {
+    dir: PathBuf,
+    file: Option<File>,
+    size: u64,
+}
+
+impl RotatingFile {
+    fn open(dir: PathBuf) -> io::Result<Self> {
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG_FILE_NAME))?;
+        let size = file.metadata()?.len();
+        Ok(RotatingFile { dir, file: Some(file), size })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        for index in (1..LOG_FILES_KEPT).rev() {
+            let from = rotated_path(&self.dir, index - 1);
+            if from.exists() {
+                fs::rename(&from, rotated_path(&self.dir, index))?;
+            }
+        }
+        self.file = Some(File::create(self.dir.join(LOG_FILE_NAME))?);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let _ = io::stderr().write_all(buf);
+
+        if self.size > 0 && self.size + buf.len() as u64 > LOG_MAX_BYTES {
+            // Keep logging to stderr even if the files can't be rotated
+            if self.rotate().is_err() {
+                return Ok(buf.len());
+            }
+        }
+        if let Some(file) = &mut self.file {
+            file.write_all(buf)?;
+            self.size += buf.len() as u64;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+// Index 0 is the live file; higher indexes are older
+fn rotated_path(dir: &Path, index: usize) -> PathBuf {
+    if index == 0 {
+        dir.join(LOG_FILE_NAME)
+    } else {
+        dir.join(format!("{}.{}", LOG_FILE_NAME, index))
+    }
+}
+
+// RUST_LOG still picks the level; without it, info and above are kept so a
+// bug report has something to show
+pub fn init(data_dir: &Path) {
+    let log_dir = data_dir.join("logs");
+    let mut builder = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+
+    let file_error = match RotatingFile::open(log_dir.clone()) {
+        Ok(file) => {
+            builder.target(env_logger::Target::Pipe(Box::new(file)));
+            let _ = LOG_DIR.set(log_dir);
+            None
+        }
+        Err(e) => Some(e),
+    };
+
+    builder
+        .format(|buf, record| {
+            if JSON_FORMAT.load(Ordering::Relaxed) {
+                let line = serde_json::json!({
+                    "timestamp": chrono::Utc::now().to_rfc3339(),
+                    "level": record.level().to_string(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+                writeln!(buf, "{}", line)
+            } else {
+                writeln!(
+                    buf,
+                    "[{} {:<5} {}] {}",
+                    chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+                    record.level(),
+                    record.target(),
+                    record.args()
+                )
+            }
+        })
+        .init();
+
+    if let Some(e) = file_error {
+        error!("Failed to open the log file; logging to stderr only: {}", e);
+    }
+}
+
+pub fn log_dir() -> Option<&'static Path> {
+    LOG_DIR.get().map(PathBuf::as_path)
+}
+
+pub fn log_path() -> Option<PathBuf> {
+    log_dir().map(|dir| dir.join(LOG_FILE_NAME))
+}
+
+// Joins every kept log file, oldest first, into one file to attach to a bug report
+pub fn export_logs(dest: &Path) -> Result<(), String> {
+    let dir = log_dir().ok_or("Logs aren't being written to a file")?;
+    let mut out = File::create(dest).map_err(|e| format!("Failed to create {:?}: {}", dest, e))?;
+
+    for index in (0..LOG_FILES_KEPT).rev() {
+        let path = rotated_path(dir, index);
+        if !path.exists() {
+            continue;
+        }
+        let mut file = File::open(&path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+        io::copy(&mut file, &mut out).map_err(|e| format!("Failed to export {:?}: {}", path, e))?;
+    }
+
+    info!("Logs exported to {:?}", dest);
+    Ok(())
+}
+
+pub fn is_valid_format(format: &str) -> bool {
+    matches!(format, "text" | "json")
+}
+
+pub fn set_format(format: &str) {
+    JSON_FORMAT.store(format == "json", Ordering::Relaxed);
+    info!("Log format set to {}", format);
+}
@@ -2,32 +2,211 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod database;
+mod encryption;
 mod game_monitor;
+mod logging;
 mod models;
-
-use std::sync::{Arc, Mutex};
+mod platform;
+mod report;
+mod steam;
+mod tray;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard, TryLockError};
 use tauri::{State, Manager, Window};
-use log::{info, error};
+use log::{debug, info, warn, error};
 use notify_rust::Notification;
 
-use crate::database::Database;
-use crate::game_monitor::GameMonitor;
-use crate::models::{GameSession, BudgetStatus, LearningActivity};
+use crate::database::{Database, SCHEMA_VERSION};
+use crate::game_monitor::{GameMonitor, CLOSE_RETRY_DELAY_MS, UNCONFIRMED_NAME_PREFIX, normalize_process_name};
+use crate::models::{GameSession, GameConfig, GameMatchKind, BudgetStatus, DashboardState, EarnUseStats, HistogramBin, GameTotal, TimeseriesPoint, PersonalBests, AdherenceStats, AchievementBonus, AchievementGrant, ACHIEVEMENT_MILESTONES, LearningActivity, LearningCredit, LearningPlan, Grant, GameGoal, GameGoalProgress, SteamImportSummary, HistoryImportSummary, ConfigWarning, RecurringGrant, PendingGameName, PermissionReport, LoopHealth, NotificationCooldown, CloseResult, DistinctGameStatus, AppSettings, UsageStats, GameLimit, CurfewWindow, EnforcementEntry, EnforcementReason, SessionFilter, SessionPage, Diagnostics, TimeRequest, Profile, SessionEndReason, BlacklistEntry, LiveCountdown};
 
 // Shared application state
 pub struct AppState {
     pub db: Arc<Mutex<Database>>,
     pub monitor: Arc<Mutex<GameMonitor>>,
+    pub loop_health: Arc<Mutex<LoopHealth>>,
+    pub notification_cooldown: Arc<Mutex<NotificationCooldown>>,
+}
+
+// A panic while a lock was held poisons it, and without recovery every later
+// command would fail with the same PoisonError until restart. The monitor and
+// cooldowns are plain in-memory state that stays usable, so those are taken
+// over as is; the lock is cleared so the warning is only logged once.
+fn lock_or_recover<'a, T>(mutex: &'a Mutex<T>, name: &str) -> MutexGuard<'a, T> {
+    mutex.lock().unwrap_or_else(|e| {
+        warn!("The {} lock was poisoned by a panic; recovering", name);
+        mutex.clear_poison();
+        e.into_inner()
+    })
+}
+
+// Like lock_or_recover, but gives up instead of waiting on a busy lock
+fn try_lock_or_recover<'a, T>(mutex: &'a Mutex<T>, name: &str) -> Option<MutexGuard<'a, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(e)) => {
+            warn!("The {} lock was poisoned by a panic; recovering", name);
+            mutex.clear_poison();
+            Some(e.into_inner())
+        }
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+// The database is only trusted again once any transaction the panic left
+// open has been rolled back; if that fails it stays poisoned
+fn recover_database<'a>(mutex: &'a Mutex<Database>, guard: MutexGuard<'a, Database>) -> Result<MutexGuard<'a, Database>, String> {
+    match guard.rollback_unfinished() {
+        Ok(rolled_back) => {
+            warn!("The database lock was poisoned by a panic; recovering{}",
+                  if rolled_back { " after rolling back an unfinished transaction" } else { "" });
+            mutex.clear_poison();
+            Ok(guard)
+        }
+        Err(e) => {
+            error!("Can't recover the poisoned database lock: {}", e);
+            Err("The database is in an inconsistent state after an internal error. Please restart the app.".to_string())
+        }
+    }
+}
+
+fn lock_database(mutex: &Mutex<Database>) -> Result<MutexGuard<'_, Database>, String> {
+    match mutex.lock() {
+        Ok(guard) => Ok(guard),
+        Err(e) => recover_database(mutex, e.into_inner()),
+    }
+}
+
+fn try_lock_database(mutex: &Mutex<Database>) -> Option<Result<MutexGuard<'_, Database>, String>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(Ok(guard)),
+        Err(TryLockError::Poisoned(e)) => Some(recover_database(mutex, e.into_inner())),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+fn lock_db(state: &AppState) -> Result<MutexGuard<'_, Database>, String> {
+    lock_database(&state.db)
+}
+
+fn lock_monitor(state: &AppState) -> MutexGuard<'_, GameMonitor> {
+    lock_or_recover(&state.monitor, "monitor")
+}
+
+// Closes detected games (or only `only`), taking the monitor lock for each step
+// but not while waiting for the processes to exit, so the loop and commands
+// keep running meanwhile. Returns at once when nothing matched.
+async fn close_games(monitor: &Mutex<GameMonitor>, only: Option<&str>) -> CloseResult {
+    let mut pending = lock_or_recover(monitor, "monitor").begin_close(only);
+    while !pending.is_done() {
+        lock_or_recover(monitor, "monitor").close_attempt(&mut pending);
+        tokio::time::sleep(std::time::Duration::from_millis(CLOSE_RETRY_DELAY_MS)).await;
+        lock_or_recover(monitor, "monitor").refresh_close(&mut pending);
+    }
+    lock_or_recover(monitor, "monitor").finish_close(pending)
+}
+
+// This long past the update interval without a clean iteration means the loop is stuck
+const LOOP_STALL_SECONDS: i64 = 30;
+
+#[tauri::command]
+async fn get_loop_health(state: State<'_, AppState>) -> Result<LoopHealth, String> {
+    let update_interval = lock_monitor(&state).update_interval_seconds();
+    let mut health = lock_or_recover(&state.loop_health, "loop health");
+    health.check_stalled(chrono::Utc::now(), LOOP_STALL_SECONDS + update_interval);
+    Ok(health.clone())
+}
+
+// Logs an error from the monitoring loop and keeps it for the health report
+fn loop_error(errors: &mut Vec<String>, message: String) {
+    error!("{}", message);
+    errors.push(message);
+}
+
+// Records games enforcement closed and tells the frontend about each one.
+// Called with the monitor unlocked, since commands lock the database first.
+fn record_enforcement(app_handle: &tauri::AppHandle, db: &Mutex<Database>, games: &[String], reason: EnforcementReason, errors: &mut Vec<String>) {
+    if games.is_empty() {
+        return;
+    }
+
+    let db = match lock_database(db) {
+        Ok(db) => db,
+        Err(e) => return loop_error(errors, format!("Failed to log enforcement: {}", e)),
+    };
+    for game in games {
+        match db.log_enforcement(game, reason) {
+            Ok(entry) => {
+                if let Err(e) = app_handle.emit_all("enforcement", &entry) {
+                    loop_error(errors, format!("Failed to emit enforcement: {}", e));
+                }
+            }
+            Err(e) => loop_error(errors, format!("Failed to log enforcement for {}: {}", game, e)),
+        }
+    }
+}
+
+// notification_style, or "silent" when notifications are switched off
+fn notification_style(db: &Database) -> String {
+    match db.get_settings() {
+        Ok(settings) if settings.notifications_enabled => settings.notification_style,
+        Ok(_) => "silent".to_string(),
+        Err(_) => "overlay".to_string(),
+    }
+}
+
+// Shows a warning the way the notification style asks. Only "overlay" opens
+// a window; the others never pull focus out of a fullscreen game.
+async fn deliver_warning(app_handle: &tauri::AppHandle, style: &str, title: String, message: String, severity: String) -> Result<(), String> {
+    match style {
+        "overlay" => show_simple_overlay(app_handle.clone(), title, message, severity).await,
+        "notification_only" => send_system_notification(title, message, severity),
+        "tray" => {
+            tray::show_warning(app_handle, &title, &message);
+            Ok(())
+        }
+        _ => {
+            info!("Not showing warning (notifications are silent): {}", title);
+            Ok(())
+        }
+    }
+}
+
+// Same for messages that are only ever a system notification
+fn deliver_notification(app_handle: &tauri::AppHandle, style: &str, title: String, message: String, urgency: String) -> Result<(), String> {
+    match style {
+        "overlay" | "notification_only" => send_system_notification(title, message, urgency),
+        "tray" => {
+            tray::show_warning(app_handle, &title, &message);
+            Ok(())
+        }
+        _ => {
+            info!("Not showing notification (notifications are silent): {}", title);
+            Ok(())
+        }
+    }
 }
 
 #[tauri::command]
 async fn show_game_overlay(
-    _window: Window,
+    window: Window,
     title: String,
     message: String,
     notification_type: String,
     remaining_minutes: Option<i32>,
 ) -> Result<(), String> {
+    let app_handle = window.app_handle();
+    let style = {
+        let state = app_handle.state::<AppState>();
+        let db = lock_db(&state)?;
+        notification_style(&db)
+    };
+    if style != "overlay" {
+        return deliver_notification(&app_handle, &style, title, message, notification_type);
+    }
+
     info!("Creating game overlay notification: {}", title);
 
     use tauri::api::dialog::{MessageDialogBuilder, MessageDialogKind};
@@ -54,13 +233,16 @@ async fn show_game_overlay(
             info!("Dialog closed: {:?}", result);
         });
 
-    if let Err(e) = show_system_notification(title.clone(), message, notification_type).await {
+    if let Err(e) = send_system_notification(title.clone(), message, notification_type) {
         error!("Failed to show system notification: {}", e);
     }
 
     Ok(())
 }
 
+// Single window reused for every warning so they can't pile up on screen
+const OVERLAY_WINDOW_ID: &str = "simple-overlay";
+
 #[tauri::command]
 async fn show_simple_overlay(
     app_handle: tauri::AppHandle,
@@ -68,26 +250,120 @@ async fn show_simple_overlay(
     message: String,
     notification_type: String,
 ) -> Result<(), String> {
+    let allow_requests = {
+        let state = app_handle.state::<AppState>();
+        let db = lock_db(&state)?;
+        db.get_settings().map(|settings| settings.allow_time_requests).unwrap_or(false)
+    };
+
+    // Only one overlay is ever open; later warnings replace what it shows
+    if let Some(window) = app_handle.get_window(OVERLAY_WINDOW_ID) {
+        info!("Updating overlay: {}", title);
+        let update = format!("updateOverlay({}, {}, {}, {})",
+                             serde_json::to_string(&title).map_err(|e| e.to_string())?,
+                             serde_json::to_string(&message).map_err(|e| e.to_string())?,
+                             serde_json::to_string(&notification_type).map_err(|e| e.to_string())?,
+                             allow_requests);
+        window.eval(&update).map_err(|e| e.to_string())?;
+        let _ = window.set_focus();
+        return Ok(());
+    }
+
     info!("Creating simple overlay: {}", title);
 
-    let window_id = format!("overlay-{}", chrono::Utc::now().timestamp_millis());
+    // The page is one of the app's own assets so its button can reach
+    // close_overlay_window; pages loaded from temp files can't call commands
+    let initial = serde_json::json!({
+        "windowId": OVERLAY_WINDOW_ID,
+        "title": title,
+        "message": message,
+        "type": notification_type,
+        "allowRequests": allow_requests,
+    });
+
+    match tauri::WindowBuilder::new(
+        &app_handle,
+        OVERLAY_WINDOW_ID,
+        tauri::WindowUrl::App("overlay.html".into())
+    )
+    .initialization_script(&format!("window.__OVERLAY__ = {};", initial))
+    .title("🎮 Gaming Time Warning")
+    .inner_size(600.0, 400.0)
+    .center()
+    .resizable(false)
+    .maximizable(false)
+    .minimizable(false)
+    .closable(true)
+    .decorations(true)
+    .always_on_top(true)
+    .skip_taskbar(false)
+    .fullscreen(false)
+    .focused(true)
+    .visible(true)
+    .build() {
+        Ok(_window) => {
+            info!("Overlay window created successfully: {}", OVERLAY_WINDOW_ID);
+            Ok(())
+        }
+        Err(e) => {
+            error!("Failed to create overlay window: {}", e);
+            Err(format!("Failed to create overlay window: {}", e))
+        }
+    }
+}
+
+// Single window reused for the whole countdown so each tick updates it in place
+const COUNTDOWN_WINDOW_ID: &str = "countdown-overlay";
+
+// Seconds left and the open games while inside the final countdown window
+fn countdown_state(db: &Database, monitor: &GameMonitor) -> Option<(i64, Vec<String>)> {
+    let sessions = monitor.get_active_sessions();
+    if sessions.is_empty() || !monitor.should_notify_limit() {
+        return None;
+    }
+
+    let settings = db.get_settings().ok()?;
+    if settings.countdown_minutes <= 0 {
+        return None;
+    }
+
+    let budget = db.get_budget_status().ok()?;
+    let remaining_seconds = budget.total_available_minutes as i64 * 60
+        - db.get_today_usage_seconds_with_active(&sessions).ok()?;
+    if remaining_seconds <= 0 || remaining_seconds > settings.countdown_minutes as i64 * 60 {
+        return None;
+    }
+
+    let games = sessions.into_iter().map(|session| session.game_name).collect();
+    Some((remaining_seconds, games))
+}
+
+// Opens the countdown overlay on the first tick, then pushes the new time and
+// game list into it every second after that. `closing` switches it to the
+// grace period after the budget has run out.
+fn update_countdown_overlay(app_handle: &tauri::AppHandle, remaining_seconds: i64, games: &[String], closing: bool) -> Result<(), String> {
+    let update = format!("updateCountdown({}, {}, {})", remaining_seconds,
+                         serde_json::to_string(games).map_err(|e| e.to_string())?, closing);
+
+    if let Some(window) = app_handle.get_window(COUNTDOWN_WINDOW_ID) {
+        return window.eval(&update).map_err(|e| e.to_string());
+    }
 
     let html_content = format!(r#"
 <!DOCTYPE html>
 <html>
 <head>
     <meta charset="UTF-8">
-    <title>{title}</title>
+    <title>Gaming Time Countdown</title>
     <style>
         body {{
             font-family: Arial, sans-serif;
             background: linear-gradient(135deg, #2d3748 0%, #4a5568 100%);
             color: white;
             margin: 0;
-            padding: 40px;
+            padding: 30px;
             height: 100vh;
             display: flex;
-            flex-direction: column;
             justify-content: center;
             align-items: center;
             text-align: center;
@@ -96,187 +372,82 @@ async fn show_simple_overlay(
 
         .container {{
             background: rgba(0, 0, 0, 0.8);
-            padding: 40px;
+            padding: 30px;
             border-radius: 15px;
-            border: 3px solid {border_color};
+            border: 3px solid #f97316;
             max-width: 500px;
-            box-shadow: 0 20px 40px rgba(0, 0, 0, 0.5);
-        }}
-
-        .icon {{
-            font-size: 60px;
-            margin-bottom: 20px;
-            animation: pulse 2s infinite;
-        }}
-
-        @keyframes pulse {{
-            0%, 100% {{ transform: scale(1); }}
-            50% {{ transform: scale(1.1); }}
         }}
 
-        .title {{
-            font-size: 32px;
+        .time {{
+            font-size: 64px;
             font-weight: bold;
-            margin-bottom: 15px;
-            color: {text_color};
-        }}
-
-        .message {{
-            font-size: 18px;
-            margin-bottom: 30px;
-            line-height: 1.4;
-        }}
-
-        .buttons {{
-            display: flex;
-            gap: 15px;
-            justify-content: center;
-            flex-wrap: wrap;
+            color: #f97316;
+            font-variant-numeric: tabular-nums;
         }}
 
-        .button {{
-            padding: 15px 30px;
+        .games {{
             font-size: 16px;
-            font-weight: bold;
-            border: none;
-            border-radius: 8px;
-            cursor: pointer;
-            transition: all 0.3s ease;
-            color: white;
-        }}
-
-        .button:hover {{
-            transform: translateY(-2px);
-        }}
-
-        .close-btn {{
-            background: #dc2626;
-        }}
-
-        .close-btn:hover {{
-            background: #b91c1c;
-        }}
-
-        .ok-btn {{
-            background: #7c3aed;
+            margin: 15px 0;
         }}
 
-        .ok-btn:hover {{
-            background: #6d28d9;
+        .footer {{
+            font-size: 14px;
+            color: #ccc;
         }}
     </style>
 </head>
 <body>
     <div class="container">
-        <div class="icon">{icon}</div>
-        <div class="title">{title_content}</div>
-        <div class="message">{message}</div>
-        <div class="buttons">
-            <button class="button ok-btn" onclick="acknowledgeAndClose()">👍 Got It</button>
-            <div style="margin-top: 15px; font-size: 14px; color: #ccc;">
-                Games will be closed automatically when time expires
-            </div>
-        </div>
+        <div id="heading">⏳ Gaming time left</div>
+        <div class="time" id="time"></div>
+        <div class="games" id="games"></div>
+        <div class="footer">At zero these games will be closed automatically. Save your progress now!</div>
     </div>
 
     <script>
-        console.log('Overlay loaded successfully!');
-
-        function acknowledgeAndClose() {{
-            console.log('Acknowledge button clicked - just hiding overlay');
-
-            // Simple approach: just hide the overlay content
-            // Don't try to close the window, just make it invisible
-            document.body.innerHTML = '<div style="color: white; text-align: center; padding: 50px; font-family: Arial;">Overlay dismissed. You can close this window manually if needed.</div>';
-            document.title = 'Gaming Time Warning - Dismissed';
-
-            // Try to minimize the window so it's not in the way
-            try {{
-                window.moveTo(-1000, -1000);
-                window.resizeTo(300, 100);
-                window.blur();
-            }} catch (e) {{
-                console.log('Could not minimize window');
-            }}
+        function updateCountdown(seconds, games, closing) {{
+            document.getElementById('heading').textContent = closing ? '⛔ Time is up! Games close in' : '⏳ Gaming time left';
+            const minutes = Math.floor(seconds / 60);
+            const rest = String(seconds % 60).padStart(2, '0');
+            document.getElementById('time').textContent = minutes + ':' + rest;
+            document.getElementById('games').textContent = 'Open: ' + games.join(', ');
         }}
 
-        document.addEventListener('keydown', function(e) {{
-            if (e.key === 'Escape') {{
-                acknowledgeAndClose();
-            }}
-        }});
-
-        window.focus();
-        console.log('Overlay ready - single button approach');
+        {update};
     </script>
 </body>
 </html>
-    "#,
-    title = title,
-    border_color = match notification_type.as_str() {
-        "warning" => "#fbbf24",
-        "critical" => "#f97316",
-        "exceeded" => "#dc2626",
-        _ => "#6b7280",
-    },
-    text_color = match notification_type.as_str() {
-        "warning" => "#fbbf24",
-        "critical" => "#f97316",
-        "exceeded" => "#dc2626",
-        _ => "#6b7280",
-    },
-    icon = match notification_type.as_str() {
-        "warning" => "⚠️",
-        "critical" => "🚨",
-        "exceeded" => "❌",
-        _ => "ℹ️",
-    },
-    title_content = title,
-    message = message
-    );
-
-    let temp_dir = std::env::temp_dir();
-    let file_path = temp_dir.join(format!("{}.html", window_id));
+    "#, update = update);
 
+    let file_path = std::env::temp_dir().join(format!("{}.html", COUNTDOWN_WINDOW_ID));
     std::fs::write(&file_path, html_content).map_err(|e| format!("Failed to write HTML file: {}", e))?;
-
     let file_url = format!("file://{}", file_path.to_string_lossy());
 
-    match tauri::WindowBuilder::new(
-        &app_handle,
-        &window_id,
+    tauri::WindowBuilder::new(
+        app_handle,
+        COUNTDOWN_WINDOW_ID,
         tauri::WindowUrl::External(file_url.parse().map_err(|e| format!("URL parse error: {}", e))?)
     )
-    .title("🎮 Gaming Time Warning")
-    .inner_size(600.0, 400.0)
-    .center()
+    .title("🎮 Gaming Time Countdown")
+    .inner_size(420.0, 300.0)
     .resizable(false)
     .maximizable(false)
     .minimizable(false)
-    .closable(true)
-    .decorations(true)
     .always_on_top(true)
-    .skip_taskbar(false)
-    .fullscreen(false)
-    .focused(true)
-    .visible(true)
-    .build() {
-        Ok(_window) => {
-            info!("Overlay window created successfully: {}", window_id);
+    .focused(false)
+    .build()
+    .map_err(|e| format!("Failed to create countdown window: {}", e))?;
 
-            let file_path_clone = file_path.clone();
-            tauri::async_runtime::spawn(async move {
-                tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
-                let _ = std::fs::remove_file(file_path_clone);
-            });
+    info!("Countdown overlay opened with {}s left", remaining_seconds);
+    Ok(())
+}
 
-            Ok(())
-        }
-        Err(e) => {
-            error!("Failed to create overlay window: {}", e);
-            let _ = std::fs::remove_file(file_path);
-            Err(format!("Failed to create overlay window: {}", e))
+fn close_countdown_overlay(app_handle: &tauri::AppHandle) {
+    if let Some(window) = app_handle.get_window(COUNTDOWN_WINDOW_ID) {
+        if let Err(e) = window.close() {
+            error!("Failed to close countdown overlay: {}", e);
         }
+        let _ = std::fs::remove_file(std::env::temp_dir().join(format!("{}.html", COUNTDOWN_WINDOW_ID)));
     }
 }
 
@@ -289,12 +460,26 @@ async fn close_overlay_window(window: Window, window_id: String) -> Result<(), S
     Ok(())
 }
 
+// Shown at most once per cooldown for each urgency; repeats inside it are dropped
 #[tauri::command]
 async fn show_system_notification(
+    state: State<'_, AppState>,
     title: String,
     message: String,
     urgency: String,
 ) -> Result<(), String> {
+    {
+        let mut cooldown = lock_or_recover(&state.notification_cooldown, "notification cooldown");
+        if !cooldown.try_fire(&urgency, chrono::Utc::now()) {
+            info!("Skipping {} notification during cooldown: {}", urgency, title);
+            return Ok(());
+        }
+    }
+
+    send_system_notification(title, message, urgency)
+}
+
+fn send_system_notification(title: String, message: String, urgency: String) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
         let mut notification = Notification::new();
@@ -353,136 +538,2027 @@ async fn show_system_notification(
     }
 }
 
+// Fails with "unauthorized" unless the parent PIN checks out (or none is set)
+fn require_parent_pin(db: &Database, pin: Option<&str>) -> Result<(), String> {
+    if db.verify_parent_pin(pin).map_err(|e| e.to_string())? {
+        Ok(())
+    } else {
+        Err("unauthorized".to_string())
+    }
+}
+
 #[tauri::command]
-async fn reset_today_sessions(state: State<'_, AppState>) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    db.reset_today_sessions().map_err(|e| e.to_string())
+async fn verify_pin(state: State<'_, AppState>, pin: String) -> Result<bool, String> {
+    let db = lock_db(&state)?;
+    if !db.has_parent_pin().map_err(|e| e.to_string())? {
+        return Ok(false);
+    }
+    db.verify_parent_pin(Some(&pin)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn has_parent_pin(state: State<'_, AppState>) -> Result<bool, String> {
+    let db = lock_db(&state)?;
+    db.has_parent_pin().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_parent_pin(state: State<'_, AppState>, old: Option<String>, new: String) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    db.set_parent_pin(old.as_deref(), &new)
+}
+
+#[tauri::command]
+async fn reset_today_sessions(state: State<'_, AppState>, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.reset_today_sessions().map(|_| ())
+}
+
+#[tauri::command]
+async fn undo_reset_today(state: State<'_, AppState>, pin: Option<String>) -> Result<usize, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.undo_reset_today()
+}
+
+#[tauri::command]
+async fn reset_today_earned(state: State<'_, AppState>, pin: Option<String>) -> Result<BudgetStatus, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    let monitor = lock_monitor(&state);
+
+    db.reset_today_earned().map_err(|e| e.to_string())?;
+    realtime_budget_status(&db, &monitor)
 }
 
 #[tauri::command]
-async fn add_budget_minutes(state: State<'_, AppState>, minutes: i32) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+async fn add_budget_minutes(state: State<'_, AppState>, minutes: i32, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
     db.add_debug_earned_minutes(minutes).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn remove_budget_minutes(state: State<'_, AppState>, minutes: i32) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+async fn remove_budget_minutes(state: State<'_, AppState>, minutes: i32, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
     db.add_debug_earned_minutes(-minutes).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn add_fake_playtime(state: State<'_, AppState>, minutes: i32) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+async fn add_fake_playtime(state: State<'_, AppState>, minutes: i32, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
     db.add_fake_gaming_session(minutes).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn close_all_games(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let monitor = state.monitor.lock().map_err(|e| e.to_string())?;
-    Ok(monitor.close_detected_games())
+async fn close_all_games(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<CloseResult, String> {
+    // Closing waits on the games, so the database isn't held meanwhile
+    let result = close_games(&state.monitor, None).await;
+
+    // The closed games' sessions are saved together right away instead of
+    // waiting for the next tick
+    let db = lock_db(&state)?;
+    let mut monitor = lock_monitor(&state);
+    let sessions = monitor.get_completed_sessions();
+    if let Err(e) = db.save_sessions(&sessions) {
+        error!("{}", e);
+        monitor.requeue_completed_sessions(sessions);
+        return Ok(result);
+    }
+    for session in &sessions {
+        if let Err(e) = app_handle.emit_all("session-ended", session) {
+            error!("Failed to emit session-ended: {}", e);
+        }
+    }
+    Ok(result)
+}
+
+// Works on open sessions as well as saved ones
+#[tauri::command]
+async fn mark_session_social(state: State<'_, AppState>, session_id: String, is_social: bool, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+
+    let mut monitor = lock_monitor(&state);
+    if monitor.set_session_social(&session_id, is_social) {
+        return Ok(());
+    }
+
+    if db.set_session_social(&session_id, is_social).map_err(|e| e.to_string())? {
+        Ok(())
+    } else {
+        Err(format!("No session with id {}", session_id))
+    }
+}
+
+#[tauri::command]
+async fn get_open_launchers(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let monitor = lock_monitor(&state);
+    Ok(monitor.get_open_launchers())
 }
 
 #[tauri::command]
 async fn get_current_sessions(state: State<'_, AppState>) -> Result<Vec<GameSession>, String> {
-    let monitor = state.monitor.lock().map_err(|e| e.to_string())?;
+    let monitor = lock_monitor(&state);
     Ok(monitor.get_active_sessions())
 }
 
 #[tauri::command]
 async fn get_total_active_time(state: State<'_, AppState>) -> Result<i64, String> {
-    let monitor = state.monitor.lock().map_err(|e| e.to_string())?;
+    let monitor = lock_monitor(&state);
     Ok(monitor.get_total_active_time())
 }
 
+fn realtime_budget_status(db: &Database, monitor: &GameMonitor) -> Result<BudgetStatus, String> {
+    let mut budget = db.get_budget_status().map_err(|e| e.to_string())?;
+
+    let used_minutes = db.get_today_usage_with_active(&monitor.get_active_sessions())
+        .map_err(|e| e.to_string())?;
+    budget.update_usage(used_minutes);
+
+    Ok(budget)
+}
+
 #[tauri::command]
 async fn get_realtime_budget_status(state: State<'_, AppState>) -> Result<BudgetStatus, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
-    let monitor = state.monitor.lock().map_err(|e| e.to_string())?;
+    let db = lock_db(&state)?;
+    let monitor = lock_monitor(&state);
 
-    let mut budget = db.get_budget_status().map_err(|e| e.to_string())?;
+    realtime_budget_status(&db, &monitor)
+}
+
+// Seconds left today, for a countdown the UI ticks down between polls. Usage
+// is read to the second; the total available doesn't depend on usage, so this
+// agrees with get_realtime_budget_status taken at the same moment.
+#[tauri::command]
+async fn get_live_countdown(state: State<'_, AppState>) -> Result<LiveCountdown, String> {
+    let db = lock_db(&state)?;
+    let monitor = lock_monitor(&state);
+
+    let active_sessions = monitor.get_active_sessions();
+    let as_of = chrono::Utc::now();
+    let used_seconds = db.get_today_usage_seconds_with_active(&active_sessions)
+        .map_err(|e| e.to_string())?;
+    let budget = db.get_budget_status().map_err(|e| e.to_string())?;
+
+    Ok(LiveCountdown {
+        remaining_seconds: (budget.total_available_minutes as i64 * 60 - used_seconds).max(0),
+        as_of,
+        active_game_count: active_sessions.len(),
+    })
+}
 
-    let active_time_minutes = (monitor.get_total_active_time() / 60) as i32;
-    budget.update_usage(budget.used_today_minutes + active_time_minutes);
+// Projects when the remaining budget runs out if current play continues.
+// Concurrent games share one clock, so the budget drains at one minute per
+// minute however many games are open.
+#[tauri::command]
+async fn get_budget_exhaustion_time(state: State<'_, AppState>) -> Result<Option<chrono::DateTime<chrono::Utc>>, String> {
+    let db = lock_db(&state)?;
+    let monitor = lock_monitor(&state);
 
-    Ok(budget)
+    if monitor.get_active_sessions().is_empty() {
+        return Ok(None);
+    }
+
+    let budget = realtime_budget_status(&db, &monitor)?;
+    let now = chrono::Utc::now();
+    let exhaustion_time = now + chrono::Duration::minutes(budget.remaining_today_minutes as i64);
+
+    let tomorrow_start = db.day_start(db.current_day() + chrono::Duration::days(1));
+
+    if exhaustion_time >= tomorrow_start {
+        return Ok(None);
+    }
+
+    Ok(Some(exhaustion_time))
 }
 
 #[tauri::command]
 async fn get_budget_status(state: State<'_, AppState>) -> Result<BudgetStatus, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let db = lock_db(&state)?;
     db.get_budget_status().map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn get_recent_sessions(state: State<'_, AppState>) -> Result<Vec<GameSession>, String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+    let db = lock_db(&state)?;
     db.get_recent_sessions(20).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+async fn query_sessions(state: State<'_, AppState>, filter: Option<SessionFilter>) -> Result<SessionPage, String> {
+    let db = lock_db(&state)?;
+    db.query_sessions(&filter.unwrap_or_default()).map_err(|e| e.to_string())
+}
+
+// Everything the main dashboard shows, read under one acquisition of both locks
+// so all fields describe the same moment
+#[tauri::command]
+async fn get_dashboard_state(state: State<'_, AppState>) -> Result<DashboardState, String> {
+    let db = lock_db(&state)?;
+    let monitor = lock_monitor(&state);
+
+    let active_sessions = monitor.get_active_sessions().into_iter()
+        .map(|mut session| {
+            session.duration_seconds = Some(session.current_duration());
+            session
+        })
+        .collect();
+
+    let next_reset = db.day_start(db.current_day() + chrono::Duration::days(1));
+
+    Ok(DashboardState {
+        budget: realtime_budget_status(&db, &monitor)?,
+        active_sessions,
+        total_active_seconds: monitor.get_total_active_time(),
+        recent_sessions: db.get_recent_sessions(20).map_err(|e| e.to_string())?,
+        is_paused: monitor.is_paused(),
+        next_reset,
+        generated_at: chrono::Utc::now(),
+    })
+}
+
 #[tauri::command]
 async fn add_learning_activity(
     state: State<'_, AppState>,
     activity_type: String,
     description: String,
     duration_minutes: i32,
-) -> Result<(), String> {
-    let db = state.db.lock().map_err(|e| e.to_string())?;
+) -> Result<LearningCredit, String> {
+    let db = lock_db(&state)?;
 
-    let activity = LearningActivity::new(activity_type, description, duration_minutes);
+    let earn_ratio = db.get_earn_ratio(&activity_type).map_err(|e| e.to_string())?;
+    let mut activity = LearningActivity::new(activity_type, description, duration_minutes, earn_ratio);
 
-    db.add_learning_activity(&activity).map_err(|e| e.to_string())
+    db.add_learning_activity(&mut activity).map_err(|e| e.to_string())
 }
 
+// Same calculation as add_learning_activity, without saving anything
 #[tauri::command]
-async fn get_detected_games(state: State<'_, AppState>) -> Result<Vec<String>, String> {
-    let monitor = state.monitor.lock().map_err(|e| e.to_string())?;
-    Ok(monitor.get_detected_games())
+async fn preview_earned_minutes(
+    state: State<'_, AppState>,
+    activity_type: String,
+    duration_minutes: i32,
+) -> Result<LearningCredit, String> {
+    let db = lock_db(&state)?;
+
+    let earn_ratio = db.get_earn_ratio(&activity_type).map_err(|e| e.to_string())?;
+    let requested_minutes = LearningActivity::earned_minutes_for(duration_minutes, earn_ratio);
+    let credit = db.learning_credit_for(requested_minutes).map_err(|e| e.to_string())?;
+
+    debug!("Previewed {} minutes of {}: {} of {} gaming minutes", duration_minutes, activity_type,
+           credit.granted_minutes, credit.requested_minutes);
+    Ok(credit)
 }
 
 #[tauri::command]
-async fn pause_monitoring(state: State<'_, AppState>) -> Result<(), String> {
-    let mut monitor = state.monitor.lock().map_err(|e| e.to_string())?;
-    monitor.pause();
-    Ok(())
+async fn get_learning_activities(state: State<'_, AppState>, limit: usize) -> Result<Vec<LearningActivity>, String> {
+    let db = lock_db(&state)?;
+    db.get_learning_activities(limit).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
-async fn resume_monitoring(state: State<'_, AppState>) -> Result<(), String> {
-    let mut monitor = state.monitor.lock().map_err(|e| e.to_string())?;
-    monitor.resume();
-    Ok(())
+async fn update_learning_activity(
+    state: State<'_, AppState>,
+    id: String,
+    duration_minutes: i32,
+    description: String,
+    pin: Option<String>,
+) -> Result<LearningActivity, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.update_learning_activity(&id, duration_minutes, &description)
 }
 
-fn main() {
-    env_logger::init();
+#[tauri::command]
+async fn delete_learning_activity(state: State<'_, AppState>, id: String, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.delete_learning_activity(&id)
+}
 
-    let db = Arc::new(Mutex::new(
-        Database::new().expect("Failed to initialize database")
-    ));
+// What the budget would look like after logging the activity now; nothing is saved
+#[tauri::command]
+async fn budget_if_i_learn(
+    state: State<'_, AppState>,
+    activity_type: String,
+    minutes: i32,
+) -> Result<BudgetStatus, String> {
+    if minutes <= 0 {
+        return Err("Minutes must be positive".to_string());
+    }
 
-    let monitor = Arc::new(Mutex::new(GameMonitor::new()));
+    let db = lock_db(&state)?;
+    let monitor = lock_monitor(&state);
+    let mut budget = realtime_budget_status(&db, &monitor)?;
 
-    let app_state = AppState {
-        db: db.clone(),
-        monitor: monitor.clone(),
-    };
+    let earn_ratio = db.get_earn_ratio(&activity_type).map_err(|e| e.to_string())?;
+    let preview = LearningActivity::new(activity_type, String::new(), minutes, earn_ratio);
+    budget.earned_minutes += db.cap_earned_minutes(preview.earned_gaming_minutes).map_err(|e| e.to_string())?;
+    budget.update_usage(budget.used_today_minutes);
+    Ok(budget)
+}
 
-    info!("Starting Gaming Time Tracker");
+#[tauri::command]
+async fn minutes_needed_to_play(state: State<'_, AppState>, game_minutes: i32, activity_type: String) -> Result<LearningPlan, String> {
+    let db = lock_db(&state)?;
+    db.learning_plan_for(game_minutes, &activity_type).map_err(|e| e.to_string())
+}
 
-    tauri::Builder::default()
-        .manage(app_state)
-        .invoke_handler(tauri::generate_handler![
-            get_current_sessions,
-            get_total_active_time,
-            get_budget_status,
-            get_realtime_budget_status,
-            get_recent_sessions,
+#[tauri::command]
+async fn get_earn_ratios(state: State<'_, AppState>) -> Result<HashMap<String, i32>, String> {
+    let db = lock_db(&state)?;
+    db.get_earn_ratios().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_earn_ratios(state: State<'_, AppState>, ratios: HashMap<String, i32>, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.set_earn_ratios(&ratios)
+}
+
+#[tauri::command]
+async fn get_weekday_allowances(state: State<'_, AppState>) -> Result<HashMap<u32, i32>, String> {
+    let db = lock_db(&state)?;
+    db.get_weekday_allowances().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_weekday_allowances(state: State<'_, AppState>, allowances: HashMap<u32, i32>, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.set_weekday_allowances(&allowances)
+}
+
+#[tauri::command]
+async fn get_curfew_windows(state: State<'_, AppState>) -> Result<Vec<CurfewWindow>, String> {
+    let db = lock_db(&state)?;
+    db.get_curfew_windows().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_curfew_windows(state: State<'_, AppState>, windows: Vec<CurfewWindow>, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.set_curfew_windows(&windows)?;
+
+    let mut monitor = lock_monitor(&state);
+    monitor.set_curfew_windows(windows);
+    Ok(())
+}
+
+#[tauri::command]
+async fn check_permissions(state: State<'_, AppState>) -> Result<PermissionReport, String> {
+    let mut monitor = lock_monitor(&state);
+    Ok(monitor.check_permissions())
+}
+
+// Each check is independent, so one failing doesn't hide the others
+#[tauri::command]
+async fn run_diagnostics(state: State<'_, AppState>) -> Result<Diagnostics, String> {
+    let db = lock_db(&state)?;
+    let mut monitor = lock_monitor(&state);
+    let mut problems = Vec::new();
+
+    let database_writable = db.is_writable();
+    if !database_writable {
+        problems.push("The database can't be written to, so sessions aren't being saved.".to_string());
+    }
+    let schema_version = match db.schema_version() {
+        Ok(version) => Some(version),
+        Err(e) => {
+            problems.push(format!("Couldn't read the schema version: {}", e));
+            None
+        }
+    };
+    if schema_version.is_some_and(|version| version != SCHEMA_VERSION) {
+        problems.push(format!("The database is at schema version {} but this app expects {}.",
+                              schema_version.unwrap_or_default(), SCHEMA_VERSION));
+    }
+
+    let encryption_enabled = db.get_settings().map(|settings| settings.encryption_enabled).unwrap_or(false);
+    if encryption_enabled != db.is_encrypted() {
+        problems.push(format!("Encryption is turned {} but the database is {}; this is applied at the next start.",
+                              if encryption_enabled { "on" } else { "off" },
+                              if db.is_encrypted() { "encrypted" } else { "not encrypted" }));
+    }
+
+    let visible_processes = monitor.visible_process_count();
+    let process_enumeration = visible_processes > 1;
+    if !process_enumeration {
+        problems.push("Only this app's own process is visible, so games can't be detected.".to_string());
+    }
+
+    let known_games = monitor.known_game_count();
+    if known_games == 0 {
+        problems.push("No known games are loaded.".to_string());
+    }
+
+    let notifications_available = platform::notifications_available();
+    if notifications_available == Some(false) {
+        problems.push("No notification server responded, so warnings only appear as overlays.".to_string());
+    }
+
+    Ok(Diagnostics {
+        generated_at: chrono::Utc::now(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        data_dir: db.data_dir().display().to_string(),
+        database_writable,
+        database_encrypted: db.is_encrypted(),
+        schema_version,
+        supported_schema_version: SCHEMA_VERSION,
+        process_enumeration,
+        visible_processes,
+        known_games,
+        notifications_available,
+        monitoring_paused: monitor.is_paused(),
+        problems,
+    })
+}
+
+#[tauri::command]
+async fn set_game_notification_pref(
+    state: State<'_, AppState>,
+    process_name: String,
+    notify_on_limit: bool,
+) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    if !db.set_game_notification_pref(&process_name, notify_on_limit).map_err(|e| e.to_string())? {
+        return Err(format!("Unknown game: {}", process_name));
+    }
+
+    let mut monitor = lock_monitor(&state);
+    monitor.set_game_notification_pref(&process_name, notify_on_limit);
+    Ok(())
+}
+
+// Exempt games (e.g. a typing tutor) are still tracked for history, but their
+// time isn't billed and enforcement leaves them running
+#[tauri::command]
+async fn set_game_exempt(
+    state: State<'_, AppState>,
+    process_name: String,
+    exempt: bool,
+    pin: Option<String>,
+) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    if !db.set_game_exempt(&process_name, exempt).map_err(|e| e.to_string())? {
+        return Err(format!("Unknown game: {}", process_name));
+    }
+
+    let mut monitor = lock_monitor(&state);
+    monitor.set_game_exempt(&process_name, exempt);
+    info!("{} is {} the budget", process_name, if exempt { "now exempt from" } else { "counted against" });
+    Ok(())
+}
+
+// Whether the UI should show limit warnings/overlays for the games now open
+#[tauri::command]
+async fn should_notify_limit(state: State<'_, AppState>) -> Result<bool, String> {
+    let monitor = lock_monitor(&state);
+    Ok(monitor.should_notify_limit())
+}
+
+#[tauri::command]
+async fn get_detected_games(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    let monitor = lock_monitor(&state);
+    Ok(monitor.get_detected_games())
+}
+
+#[tauri::command]
+async fn pause_monitoring(app_handle: tauri::AppHandle, state: State<'_, AppState>, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.update_setting("monitoring_paused", "true").map_err(|e| e.to_string())?;
+
+    let mut monitor = lock_monitor(&state);
+    monitor.pause();
+    tray::show_paused(&app_handle, true);
+    Ok(())
+}
+
+#[tauri::command]
+async fn resume_monitoring(app_handle: tauri::AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    db.update_setting("monitoring_paused", "false").map_err(|e| e.to_string())?;
+
+    let mut monitor = lock_monitor(&state);
+    monitor.resume();
+    tray::show_paused(&app_handle, false);
+    Ok(())
+}
+
+// Lets the main window finish a quit started from the tray once the PIN is in
+#[tauri::command]
+async fn quit_app(app_handle: tauri::AppHandle, state: State<'_, AppState>, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    drop(db);
+
+    info!("Quitting at the parent's request");
+    app_handle.exit(0);
+    Ok(())
+}
+
+// True while monitoring is paused
+#[tauri::command]
+async fn get_monitoring_status(state: State<'_, AppState>) -> Result<bool, String> {
+    let monitor = lock_monitor(&state);
+    Ok(monitor.is_paused())
+}
+
+// Sent from the warning overlay, so it needs no PIN; only one can be pending
+#[tauri::command]
+async fn request_extra_time(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    minutes: i32,
+    reason: Option<String>,
+) -> Result<TimeRequest, String> {
+    let request = {
+        let db = lock_db(&state)?;
+        if !db.get_settings().map_err(|e| e.to_string())?.allow_time_requests {
+            return Err("Requests for more time are turned off".to_string());
+        }
+        db.add_time_request(minutes, reason.as_deref().unwrap_or(""))?
+    };
+
+    if let Err(e) = app_handle.emit_all("time-request", &request) {
+        error!("Failed to emit time-request: {}", e);
+    }
+    Ok(request)
+}
+
+#[tauri::command]
+async fn get_pending_requests(state: State<'_, AppState>) -> Result<Vec<TimeRequest>, String> {
+    let db = lock_db(&state)?;
+    db.get_pending_requests().map_err(|e| e.to_string())
+}
+
+fn resolve_time_request(
+    app_handle: &tauri::AppHandle,
+    state: &AppState,
+    request_id: &str,
+    approve: bool,
+    pin: Option<&str>,
+) -> Result<TimeRequest, String> {
+    let request = {
+        let db = lock_db(&state)?;
+        require_parent_pin(&db, pin)?;
+        db.resolve_time_request(request_id, approve)?
+    };
+
+    if let Err(e) = app_handle.emit_all("time-request", &request) {
+        error!("Failed to emit time-request: {}", e);
+    }
+    Ok(request)
+}
+
+#[tauri::command]
+async fn approve_request(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    request_id: String,
+    pin: Option<String>,
+) -> Result<TimeRequest, String> {
+    resolve_time_request(&app_handle, &state, &request_id, true, pin.as_deref())
+}
+
+#[tauri::command]
+async fn deny_request(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    request_id: String,
+    pin: Option<String>,
+) -> Result<TimeRequest, String> {
+    resolve_time_request(&app_handle, &state, &request_id, false, pin.as_deref())
+}
+
+#[tauri::command]
+async fn list_profiles(state: State<'_, AppState>) -> Result<Vec<Profile>, String> {
+    let db = lock_db(&state)?;
+    db.get_profiles().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn create_profile(
+    state: State<'_, AppState>,
+    name: String,
+    daily_allowance_minutes: i32,
+    pin: Option<String>,
+) -> Result<Profile, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.create_profile(&name, daily_allowance_minutes)
+}
+
+// Open sessions are ended and saved under the old profile first, so one
+// session's time is never split between two players
+#[tauri::command]
+async fn set_active_profile(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    profile_id: String,
+    pin: Option<String>,
+) -> Result<Profile, String> {
+    let mut db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    let mut profile = db.get_profiles().map_err(|e| e.to_string())?
+        .into_iter()
+        .find(|profile| profile.id == profile_id)
+        .ok_or_else(|| format!("No profile with id {}", profile_id))?;
+    if profile.is_active {
+        return Ok(profile);
+    }
+
+    {
+        let mut monitor = lock_monitor(&state);
+        let sessions = monitor.end_all_sessions(SessionEndReason::ProfileSwitch);
+        let mut unsaved = Vec::new();
+        for session in sessions {
+            if let Err(e) = db.save_session(&session) {
+                error!("Failed to save {} before switching profiles: {}", session.game_name, e);
+                unsaved.push(session);
+            }
+        }
+        if !unsaved.is_empty() {
+            monitor.requeue_completed_sessions(unsaved);
+        } else if let Err(e) = db.save_active_sessions(&[]) {
+            error!("Failed to clear the active session snapshot: {}", e);
+        }
+
+        db.set_active_profile(&profile_id)?;
+        monitor.set_active_profile(&profile_id);
+    }
+
+    // The profile may not have been active at midnight to finalize yesterday
+    if let Err(e) = db.finalize_day_rollover() {
+        error!("Failed to roll over yesterday's budget: {}", e);
+    }
+
+    profile.is_active = true;
+    if let Err(e) = app_handle.emit_all("profile-changed", &profile) {
+        error!("Failed to emit profile-changed: {}", e);
+    }
+    Ok(profile)
+}
+
+#[tauri::command]
+async fn delete_profile(state: State<'_, AppState>, profile_id: String, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.delete_profile(&profile_id)
+}
+
+#[tauri::command]
+async fn list_active_grants(state: State<'_, AppState>) -> Result<Vec<Grant>, String> {
+    let db = lock_db(&state)?;
+    db.get_active_grants().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn split_session_at(
+    state: State<'_, AppState>,
+    session_id: String,
+    timestamp: chrono::DateTime<chrono::Utc>,
+    pin: Option<String>,
+) -> Result<Vec<GameSession>, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    let (first, second) = db.split_session_at(&session_id, timestamp)?;
+    Ok(vec![first, second])
+}
+
+// For play the app didn't detect; returned so the UI can show it right away
+#[tauri::command]
+async fn add_manual_session(
+    state: State<'_, AppState>,
+    game_name: String,
+    start: chrono::DateTime<chrono::Utc>,
+    end: chrono::DateTime<chrono::Utc>,
+    pin: Option<String>,
+) -> Result<GameSession, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.add_manual_session(&game_name, start, end)
+}
+
+// Deleted sessions go to the trash and can be restored with undo_reset_today
+#[tauri::command]
+async fn delete_session(state: State<'_, AppState>, session_id: String, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    if !db.delete_session(&session_id)? {
+        return Err(format!("No session with id {}", session_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn revoke_grant(state: State<'_, AppState>, grant_id: String, pin: Option<String>) -> Result<BudgetStatus, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+
+    if !db.revoke_grant(&grant_id).map_err(|e| e.to_string())? {
+        return Err(format!("No active grant with id {}", grant_id));
+    }
+
+    db.get_budget_status().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn add_recurring_grant(
+    state: State<'_, AppState>,
+    weekday: u32,
+    minutes: i32,
+    expires_at_time: Option<String>,
+    reason: Option<String>,
+    pin: Option<String>,
+) -> Result<RecurringGrant, String> {
+    if weekday > 6 {
+        return Err("Weekday must be between 0 (Sunday) and 6 (Saturday)".to_string());
+    }
+    if minutes <= 0 {
+        return Err("Recurring grant minutes must be positive".to_string());
+    }
+    if let Some(time) = &expires_at_time {
+        chrono::NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| format!("Invalid expiry time: {} (expected HH:MM)", time))?;
+    }
+
+    let rule = RecurringGrant {
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        weekday,
+        minutes,
+        expires_at_time,
+        reason: reason.unwrap_or_else(|| "Weekly boost".to_string()),
+    };
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.add_recurring_grant(&rule).map_err(|e| e.to_string())?;
+    // A rule for today's weekday takes effect straight away
+    db.apply_recurring_grants().map_err(|e| e.to_string())?;
+    Ok(rule)
+}
+
+#[tauri::command]
+async fn remove_recurring_grant(state: State<'_, AppState>, rule_id: String, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+
+    if !db.remove_recurring_grant(&rule_id).map_err(|e| e.to_string())? {
+        return Err(format!("No recurring grant with id {}", rule_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_recurring_grants(state: State<'_, AppState>) -> Result<Vec<RecurringGrant>, String> {
+    let db = lock_db(&state)?;
+    db.get_recurring_grants().map_err(|e| e.to_string())
+}
+
+// Extra time for one occasion ("one more hour tonight"). It's counted as bonus
+// time until expires_at and then disappears on its own.
+#[tauri::command]
+async fn grant_temporary_minutes(
+    state: State<'_, AppState>,
+    minutes: i32,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    reason: Option<String>,
+    pin: Option<String>,
+) -> Result<BudgetStatus, String> {
+    if !(1..=1440).contains(&minutes) {
+        return Err(format!("Invalid grant: {} minutes (expected 1 to 1440)", minutes));
+    }
+    if expires_at <= chrono::Utc::now() {
+        return Err("The grant must expire in the future".to_string());
+    }
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty())
+        .unwrap_or_else(|| "Temporary bonus".to_string());
+    db.add_grant(&Grant::new("temporary".to_string(), minutes, reason, expires_at)).map_err(|e| e.to_string())?;
+    db.get_budget_status().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn borrow_from_tomorrow(state: State<'_, AppState>, minutes: i32) -> Result<BudgetStatus, String> {
+    if minutes <= 0 {
+        return Err("Borrowed minutes must be positive".to_string());
+    }
+
+    let db = lock_db(&state)?;
+    db.borrow_from_tomorrow(minutes)?;
+    db.get_budget_status().map_err(|e| e.to_string())
+}
+
+// Once today's time is used up, lets play continue and takes the minutes off
+// tomorrow, within the same debt limit as borrowing
+#[tauri::command]
+async fn grant_overtime(state: State<'_, AppState>, minutes: i32) -> Result<BudgetStatus, String> {
+    if minutes <= 0 {
+        return Err("Overtime minutes must be positive".to_string());
+    }
+
+    let db = lock_db(&state)?;
+    db.grant_overtime(minutes)?;
+    db.get_budget_status().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn add_custom_game(
+    state: State<'_, AppState>,
+    process_name: String,
+    display_name: String,
+    is_monitored: Option<bool>,
+    path_pattern: Option<String>,
+    is_launcher: Option<bool>,
+    title_pattern: Option<String>,
+    pin: Option<String>,
+) -> Result<(), String> {
+    let process_name = process_name.trim().to_string();
+    let display_name = display_name.trim().to_string();
+    if process_name.is_empty() || display_name.is_empty() {
+        return Err("Both a process name and a display name are required".to_string());
+    }
+    let path_pattern = path_pattern
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty());
+    let title_pattern = title_pattern
+        .map(|pattern| pattern.trim().to_string())
+        .filter(|pattern| !pattern.is_empty());
+    if let Some(pattern) = &title_pattern {
+        GameMonitor::compile_title_pattern(pattern)?;
+    }
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    let saved = db.save_custom_game(&GameConfig {
+        process_name: process_name.clone(),
+        display_name,
+        is_monitored: is_monitored.unwrap_or(true),
+        notify_on_limit: true,
+        path_pattern,
+        is_launcher: is_launcher.unwrap_or(false),
+        title_pattern,
+        kind: GameMatchKind::Exact,
+        is_exempt: false,
+    }).map_err(|e| e.to_string())?;
+    if !saved {
+        return Err(format!("{} is already added as a name pattern; remove it first", process_name));
+    }
+
+    let games = db.get_custom_games().map_err(|e| e.to_string())?;
+    let mut monitor = lock_monitor(&state);
+    monitor.load_custom_games(games);
+    Ok(())
+}
+
+#[tauri::command]
+async fn remove_custom_game(
+    state: State<'_, AppState>,
+    process_name: String,
+    path_pattern: Option<String>,
+    title_pattern: Option<String>,
+    pin: Option<String>,
+) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    if !db.remove_custom_game(&process_name, path_pattern.as_deref(), title_pattern.as_deref()).map_err(|e| e.to_string())? {
+        return Err(format!("Unknown game: {}", process_name));
+    }
+
+    let games = db.get_custom_games().map_err(|e| e.to_string())?;
+    let mut monitor = lock_monitor(&state);
+    monitor.load_custom_games(games);
+    Ok(())
+}
+
+// For games whose executable name changes between versions or installs. Exact
+// entries still win over a pattern that matches the same process.
+#[tauri::command]
+async fn add_game_pattern(
+    state: State<'_, AppState>,
+    pattern: String,
+    display_name: String,
+    pin: Option<String>,
+) -> Result<(), String> {
+    let pattern = pattern.trim().to_string();
+    let display_name = display_name.trim().to_string();
+    if pattern.is_empty() || display_name.is_empty() {
+        return Err("Both a pattern and a display name are required".to_string());
+    }
+    GameMonitor::compile_name_pattern(&pattern)?;
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    let saved = db.save_custom_game(&GameConfig {
+        process_name: pattern.clone(),
+        display_name,
+        is_monitored: true,
+        notify_on_limit: true,
+        path_pattern: None,
+        is_launcher: false,
+        title_pattern: None,
+        kind: GameMatchKind::Regex,
+        is_exempt: false,
+    }).map_err(|e| e.to_string())?;
+    if !saved {
+        return Err(format!("{} is already added as an exact process name; remove it first", pattern));
+    }
+
+    let games = db.get_custom_games().map_err(|e| e.to_string())?;
+    let mut monitor = lock_monitor(&state);
+    monitor.load_custom_games(games);
+    Ok(())
+}
+
+#[tauri::command]
+async fn list_game_patterns(state: State<'_, AppState>) -> Result<Vec<GameConfig>, String> {
+    let db = lock_db(&state)?;
+    let games = db.get_custom_games().map_err(|e| e.to_string())?;
+    Ok(games.into_iter().filter(|game| game.kind == GameMatchKind::Regex).collect())
+}
+
+#[tauri::command]
+async fn list_custom_games(state: State<'_, AppState>) -> Result<Vec<GameConfig>, String> {
+    let db = lock_db(&state)?;
+    db.get_custom_games().map_err(|e| e.to_string())
+}
+
+// Built-in entries first, then the user's
+#[tauri::command]
+async fn list_blacklist(state: State<'_, AppState>) -> Result<Vec<BlacklistEntry>, String> {
+    let db = lock_db(&state)?;
+    let defaults = GameMonitor::default_blacklist();
+    let user_entries = db.get_blacklist().map_err(|e| e.to_string())?;
+
+    let mut entries: Vec<BlacklistEntry> = defaults.iter()
+        .map(|process_name| BlacklistEntry { process_name: process_name.clone(), is_default: true })
+        .collect();
+    entries.extend(user_entries.into_iter()
+        .filter(|process_name| !defaults.contains(process_name))
+        .map(|process_name| BlacklistEntry { process_name, is_default: false }));
+    Ok(entries)
+}
+
+// A blacklisted process is never tracked or closed, even if it's a known game.
+// A session already open for it ends at the next update.
+#[tauri::command]
+async fn add_blacklist_entry(state: State<'_, AppState>, process_name: String, pin: Option<String>) -> Result<BlacklistEntry, String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    let process_name = db.add_blacklist_entry(&process_name)?;
+
+    let entries = db.get_blacklist().map_err(|e| e.to_string())?;
+    let mut monitor = lock_monitor(&state);
+    monitor.set_user_blacklist(entries);
+    Ok(BlacklistEntry {
+        is_default: GameMonitor::default_blacklist().contains(&process_name),
+        process_name,
+    })
+}
+
+#[tauri::command]
+async fn remove_blacklist_entry(state: State<'_, AppState>, process_name: String, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    if GameMonitor::default_blacklist().contains(&normalize_process_name(&process_name)) {
+        return Err(format!("{} is on the built-in blacklist and can't be removed", process_name));
+    }
+    if !db.remove_blacklist_entry(&process_name).map_err(|e| e.to_string())? {
+        return Err(format!("{} isn't blacklisted", process_name));
+    }
+
+    let entries = db.get_blacklist().map_err(|e| e.to_string())?;
+    let mut monitor = lock_monitor(&state);
+    monitor.set_user_blacklist(entries);
+    Ok(())
+}
+
+#[tauri::command]
+async fn reseed_default_games(state: State<'_, AppState>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    db.reseed_default_games(&GameMonitor::default_games()).map_err(|e| e.to_string())?;
+
+    let games = db.get_custom_games().map_err(|e| e.to_string())?;
+    let mut monitor = lock_monitor(&state);
+    monitor.load_custom_games(games);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_game_goal(
+    state: State<'_, AppState>,
+    game_name: String,
+    goal_type: String,
+    target_minutes: i32,
+) -> Result<GameGoal, String> {
+    if goal_type != "min" && goal_type != "max" {
+        return Err(format!("Invalid goal type: {} (expected \"min\" or \"max\")", goal_type));
+    }
+    if target_minutes <= 0 {
+        return Err("Goal target must be a positive number of minutes".to_string());
+    }
+
+    let goal = GameGoal {
+        id: Some(uuid::Uuid::new_v4().to_string()),
+        game_name,
+        goal_type,
+        target_minutes,
+    };
+
+    let db = lock_db(&state)?;
+    db.add_game_goal(&goal).map_err(|e| e.to_string())?;
+    Ok(goal)
+}
+
+#[tauri::command]
+async fn remove_game_goal(state: State<'_, AppState>, goal_id: String) -> Result<(), String> {
+    let db = lock_db(&state)?;
+
+    if !db.remove_game_goal(&goal_id).map_err(|e| e.to_string())? {
+        return Err(format!("No goal with id {}", goal_id));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_game_goal_progress(state: State<'_, AppState>) -> Result<Vec<GameGoalProgress>, String> {
+    let db = lock_db(&state)?;
+    db.get_game_goal_progress().map_err(|e| e.to_string())
+}
+
+fn goal_alert_notification(progress: &GameGoalProgress) -> (String, String, String) {
+    let goal = &progress.goal;
+    if progress.status == "met" {
+        (
+            "Goal Reached!".to_string(),
+            format!("You played {} for {} minutes this week, reaching your goal of {}. Nice work!",
+                    goal.game_name, progress.minutes_this_week, goal.target_minutes),
+            "info".to_string(),
+        )
+    } else {
+        (
+            "Weekly Goal Exceeded".to_string(),
+            format!("You've played {} for {} minutes this week, over your goal of {}.",
+                    goal.game_name, progress.minutes_this_week, goal.target_minutes),
+            "warning".to_string(),
+        )
+    }
+}
+
+// One-time opt-in import of lifetime Steam playtime as one summary session per
+// game. Imported sessions are flagged and never count toward the daily budget.
+#[tauri::command]
+async fn import_steam_history(
+    state: State<'_, AppState>,
+    api_key: String,
+    steam_id: String,
+) -> Result<SteamImportSummary, String> {
+    {
+        let db = lock_db(&state)?;
+        if db.is_steam_history_imported().map_err(|e| e.to_string())? {
+            return Err("Steam history has already been imported".to_string());
+        }
+    }
+
+    steam::validate_credentials(&api_key, &steam_id)?;
+
+    let fetch_key = api_key.clone();
+    let fetch_id = steam_id.clone();
+    let owned_games = tauri::async_runtime::spawn_blocking(move || steam::fetch_owned_games(&fetch_key, &fetch_id))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let now = chrono::Utc::now();
+    let sessions: Vec<GameSession> = owned_games.iter()
+        .filter(|game| game.playtime_minutes > 0)
+        .map(|game| {
+            let end_time = game.last_played.unwrap_or(now);
+            let mut session = GameSession::new(game.name.clone(), format!("steam:{}", game.appid));
+            session.start_time = end_time - chrono::Duration::minutes(game.playtime_minutes);
+            session.end_time = Some(end_time);
+            session.duration_seconds = Some(game.playtime_minutes * 60);
+            session.is_imported = true;
+            session.detection_source = "steam_import".to_string();
+            session
+        })
+        .collect();
+
+    let summary = SteamImportSummary {
+        games_imported: sessions.len(),
+        minutes_imported: owned_games.iter().map(|game| game.playtime_minutes).sum(),
+    };
+
+    let db = lock_db(&state)?;
+    db.save_steam_import(&sessions, &steam_id).map_err(|e| e.to_string())?;
+    // The key isn't needed again, so an import without a keyring just forgets it
+    if let Err(e) = encryption::store_secret(steam::API_KEY_SECRET, &api_key) {
+        warn!("{}", e);
+    }
+
+    info!("Imported Steam history: {} games, {} minutes", summary.games_imported, summary.minutes_imported);
+    Ok(summary)
+}
+
+#[tauri::command]
+async fn get_earn_vs_use(state: State<'_, AppState>, period: String) -> Result<EarnUseStats, String> {
+    if Database::period_start(&period).is_none() {
+        return Err(format!("Invalid period: {} (expected \"day\", \"week\" or \"month\")", period));
+    }
+
+    let db = lock_db(&state)?;
+    db.get_earn_vs_use(&period).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_game_totals(state: State<'_, AppState>, exclude_debug: Option<bool>) -> Result<Vec<GameTotal>, String> {
+    let db = lock_db(&state)?;
+    db.get_game_totals(exclude_debug.unwrap_or(false)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_usage_stats(state: State<'_, AppState>, period: String) -> Result<UsageStats, String> {
+    if !matches!(period.as_str(), "week" | "month") {
+        return Err(format!("Invalid period: {} (expected \"week\" or \"month\")", period));
+    }
+
+    let db = lock_db(&state)?;
+    db.get_usage_stats(&period).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_session_length_histogram(
+    state: State<'_, AppState>,
+    period: String,
+    buckets: Option<Vec<i32>>,
+) -> Result<Vec<HistogramBin>, String> {
+    if Database::period_start(&period).is_none() {
+        return Err(format!("Invalid period: {} (expected \"day\", \"week\" or \"month\")", period));
+    }
+
+    // Default edges give <15m, 15–30m, 30m–1h, 1–2h and >2h
+    let edges = buckets.unwrap_or_else(|| vec![15, 30, 60, 120]);
+    if edges.iter().any(|&edge| edge <= 0) || edges.windows(2).any(|pair| pair[0] >= pair[1]) {
+        return Err("Bucket edges must be positive and strictly increasing".to_string());
+    }
+
+    let db = lock_db(&state)?;
+    db.get_session_length_histogram(&period, &edges).map_err(|e| e.to_string())
+}
+
+// Largest number of buckets a single chart request may return
+const MAX_TIMESERIES_BUCKETS: usize = 1000;
+
+#[tauri::command]
+async fn get_timeseries(
+    state: State<'_, AppState>,
+    metric: String,
+    granularity: String,
+    from: chrono::DateTime<chrono::Utc>,
+    to: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<TimeseriesPoint>, String> {
+    if !matches!(metric.as_str(), "used_minutes" | "earned_minutes" | "session_count") {
+        return Err(format!("Invalid metric: {} (expected \"used_minutes\", \"earned_minutes\" or \"session_count\")", metric));
+    }
+    if from >= to {
+        return Err("The range must end after it starts".to_string());
+    }
+
+    // Counted before anything is allocated, so a huge range can't exhaust memory
+    let bucket_count = Database::timeseries_bucket_count(&granularity, from, to)
+        .ok_or_else(|| format!("Invalid granularity: {} (expected \"hour\", \"day\" or \"week\")", granularity))?;
+    if bucket_count > MAX_TIMESERIES_BUCKETS as i64 {
+        return Err(format!("Range too large: {} {} buckets (max {})", bucket_count, granularity, MAX_TIMESERIES_BUCKETS));
+    }
+
+    let db = lock_db(&state)?;
+    db.get_timeseries(&metric, &granularity, from, to).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_personal_bests(state: State<'_, AppState>) -> Result<PersonalBests, String> {
+    let db = lock_db(&state)?;
+    db.get_personal_bests().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_adherence_stats(state: State<'_, AppState>) -> Result<AdherenceStats, String> {
+    let db = lock_db(&state)?;
+    db.get_adherence_stats().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn set_achievement_bonus(state: State<'_, AppState>, milestone: String, minutes: i32, pin: Option<String>) -> Result<(), String> {
+    if !ACHIEVEMENT_MILESTONES.contains(&milestone.as_str()) {
+        return Err(format!("Unknown milestone: {} (expected one of {})", milestone, ACHIEVEMENT_MILESTONES.join(", ")));
+    }
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.set_achievement_bonus(&milestone, minutes).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn list_achievement_bonuses(state: State<'_, AppState>) -> Result<Vec<AchievementBonus>, String> {
+    let db = lock_db(&state)?;
+    db.get_achievement_bonuses().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_achievement_history(state: State<'_, AppState>) -> Result<Vec<AchievementGrant>, String> {
+    let db = lock_db(&state)?;
+    db.get_achievement_history().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn export_today_report(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
+    let db = lock_db(&state)?;
+    db.export_today_report().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn save_today_report(state: State<'_, AppState>, dest_path: String) -> Result<(), String> {
+    let report = {
+        let db = lock_db(&state)?;
+        db.export_today_report().map_err(|e| e.to_string())?
+    };
+
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    std::fs::write(&dest_path, json).map_err(|e| format!("Failed to write report: {}", e))?;
+
+    info!("Today's report saved to {}", dest_path);
+    Ok(())
+}
+
+// Writes a printable HTML summary of the last seven days to the temp folder
+// and returns its path, for the frontend to open or print
+#[tauri::command]
+async fn generate_weekly_report(state: State<'_, AppState>) -> Result<String, String> {
+    let report = {
+        let db = lock_db(&state)?;
+        db.get_weekly_report().map_err(|e| e.to_string())?
+    };
+
+    let file_name = format!("gaming-report-{}.html", chrono::Local::now().format("%Y-%m-%d"));
+    let path = std::env::temp_dir().join(file_name);
+    std::fs::write(&path, report::weekly_report_html(&report)).map_err(|e| format!("Failed to write report: {}", e))?;
+
+    info!("Weekly report written to {}", path.display());
+    Ok(path.display().to_string())
+}
+
+// Reads an export range bound: a full RFC 3339 time, or a local date. A date
+// used as the end includes that whole day.
+fn parse_export_bound(value: &str, is_end: bool) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date: {} (expected YYYY-MM-DD or an RFC 3339 time)", value))?;
+    let date = if is_end { date + chrono::Duration::days(1) } else { date };
+    date.and_hms_opt(0, 0, 0)
+        .and_then(|midnight| midnight.and_local_timezone(chrono::Local).earliest())
+        .map(|time| time.with_timezone(&chrono::Utc))
+        .ok_or_else(|| format!("Invalid date: {}", value))
+}
+
+// Quotes a CSV field when it contains a separator, quote or line break
+fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Two CSV sections, sessions then learning, each with its own header row
+fn history_csv(sessions: &[GameSession], learning: &[LearningActivity]) -> String {
+    let mut csv = String::from("# sessions\n");
+    csv.push_str("id,game_name,process_name,start_time,end_time,duration_seconds,is_social_session,is_concurrent,concurrent_session_ids,is_web_game,idle_seconds,locked_seconds,is_imported,end_reason,detection_source\n");
+    for session in sessions {
+        let row = [
+            session.id.clone().unwrap_or_default(),
+            session.game_name.clone(),
+            session.process_name.clone(),
+            session.start_time.to_rfc3339(),
+            session.end_time.map(|t| t.to_rfc3339()).unwrap_or_default(),
+            session.duration_seconds.map(|d| d.to_string()).unwrap_or_default(),
+            session.is_social_session.to_string(),
+            session.is_concurrent.to_string(),
+            session.concurrent_session_ids.join(";"),
+            session.is_web_game.to_string(),
+            session.idle_seconds.to_string(),
+            session.locked_seconds.to_string(),
+            session.is_imported.to_string(),
+            session.end_reason.map(|reason| reason.as_str().to_string()).unwrap_or_default(),
+            session.detection_source.clone(),
+        ];
+        csv.push_str(&row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+        csv.push('\n');
+    }
+
+    csv.push_str("\n# learning\n");
+    csv.push_str("id,activity_type,description,duration_minutes,earned_gaming_minutes,timestamp,is_voided\n");
+    for activity in learning {
+        let row = [
+            activity.id.clone().unwrap_or_default(),
+            activity.activity_type.clone(),
+            activity.description.clone(),
+            activity.duration_minutes.to_string(),
+            activity.earned_gaming_minutes.to_string(),
+            activity.timestamp.to_rfc3339(),
+            activity.is_voided.to_string(),
+        ];
+        csv.push_str(&row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+// Session and learning history as "csv" or "json", optionally limited to a range
+#[tauri::command]
+async fn export_history(
+    state: State<'_, AppState>,
+    format: String,
+    start: Option<String>,
+    end: Option<String>,
+) -> Result<String, String> {
+    if !matches!(format.as_str(), "csv" | "json") {
+        return Err(format!("Invalid format: {} (expected \"csv\" or \"json\")", format));
+    }
+
+    let start = start.as_deref().map(|value| parse_export_bound(value, false)).transpose()?;
+    let end = end.as_deref().map(|value| parse_export_bound(value, true)).transpose()?;
+    if let (Some(start), Some(end)) = (start, end) {
+        if start >= end {
+            return Err("The range must end after it starts".to_string());
+        }
+    }
+
+    let db = lock_db(&state)?;
+    let sessions = db.get_sessions_between(start, end).map_err(|e| e.to_string())?;
+    let learning = db.get_learning_between(start, end).map_err(|e| e.to_string())?;
+
+    if format == "csv" {
+        Ok(history_csv(&sessions, &learning))
+    } else {
+        serde_json::to_string_pretty(&serde_json::json!({
+            "sessions": sessions,
+            "learning": learning,
+        })).map_err(|e| e.to_string())
+    }
+}
+
+// Restores history written by export_history as JSON. `mode` is "merge" or
+// "replace"; see Database::import_history.
+#[tauri::command]
+async fn import_history(
+    state: State<'_, AppState>,
+    json: String,
+    mode: String,
+    pin: Option<String>,
+) -> Result<HistoryImportSummary, String> {
+    let replace = match mode.as_str() {
+        "merge" => false,
+        "replace" => true,
+        _ => return Err(format!("Invalid mode: {} (expected \"merge\" or \"replace\")", mode)),
+    };
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.import_history(&json, replace)
+}
+
+#[tauri::command]
+async fn validate_configuration(state: State<'_, AppState>) -> Result<Vec<ConfigWarning>, String> {
+    let db = lock_db(&state)?;
+    db.validate_configuration().map_err(|e| e.to_string())
+}
+
+// Run at startup and after every settings change. The warnings are logged and
+// sent to the frontend as "config-warnings", even when there are none, so it
+// can clear ones that were fixed.
+fn report_config_warnings(app_handle: &tauri::AppHandle, db: &Database) {
+    let warnings = match db.validate_configuration() {
+        Ok(warnings) => warnings,
+        Err(e) => return error!("Failed to validate the configuration: {}", e),
+    };
+    for warning in &warnings {
+        warn!("Configuration {} for {}: {}", warning.severity, warning.setting, warning.message);
+    }
+    if let Err(e) = app_handle.emit_all("config-warnings", &warnings) {
+        error!("Failed to emit config-warnings: {}", e);
+    }
+}
+
+#[tauri::command]
+async fn set_log_format(state: State<'_, AppState>, format: String) -> Result<(), String> {
+    if !logging::is_valid_format(&format) {
+        return Err(format!("Invalid log format: {} (expected \"text\" or \"json\")", format));
+    }
+
+    let db = lock_db(&state)?;
+    db.update_setting("log_format", &format).map_err(|e| e.to_string())?;
+    logging::set_format(&format);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_web_game_detection(state: State<'_, AppState>, enabled: bool, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.update_setting("web_game_detection_enabled", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())?;
+
+    let mut monitor = lock_monitor(&state);
+    monitor.set_web_game_detection(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+async fn set_detection_mode(state: State<'_, AppState>, mode: String, pin: Option<String>) -> Result<(), String> {
+    if !matches!(mode.as_str(), "auto" | "whitelist") {
+        return Err(format!("Invalid detection mode: {} (expected \"auto\" or \"whitelist\")", mode));
+    }
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.update_setting("detection_mode", &mode).map_err(|e| e.to_string())?;
+
+    let mut monitor = lock_monitor(&state);
+    monitor.set_detection_mode(&mode);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_settings(state: State<'_, AppState>) -> Result<AppSettings, String> {
+    let db = lock_db(&state)?;
+    db.get_settings().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn update_settings(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    settings: Vec<(String, String)>,
+    pin: Option<String>,
+) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.update_settings_atomic(&settings)?;
+    report_config_warnings(&app_handle, &db);
+
+    let current = db.get_settings().map_err(|e| e.to_string())?;
+    reapply_cached_settings(&state, &current)
+}
+
+// Re-apply the settings the running app caches
+fn reapply_cached_settings(state: &AppState, current: &AppSettings) -> Result<(), String> {
+    logging::set_format(&current.log_format);
+
+    let mut monitor = lock_monitor(&state);
+    monitor.set_web_game_detection(current.web_game_detection_enabled);
+    monitor.set_detection_mode(&current.detection_mode);
+    monitor.set_steam_name_confirmation(current.confirm_steam_names);
+    monitor.set_cpu_idle_detection(current.cpu_idle_threshold, current.cpu_idle_seconds);
+    monitor.set_input_idle_threshold(current.idle_threshold_minutes);
+    monitor.set_full_scan_interval(current.full_scan_interval_seconds);
+    monitor.set_session_merge_window(current.session_merge_seconds);
+    monitor.set_update_interval(current.monitor_interval_secs);
+    monitor.set_count_foreground_only(current.count_foreground_only);
+
+    let mut cooldown = lock_or_recover(&state.notification_cooldown, "notification cooldown");
+    cooldown.cooldown_seconds = current.notification_cooldown_seconds;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_data_dir(state: State<'_, AppState>) -> Result<String, String> {
+    let db = lock_db(&state)?;
+    Ok(db.data_dir().display().to_string())
+}
+
+#[tauri::command]
+async fn open_data_folder(state: State<'_, AppState>) -> Result<(), String> {
+    let data_dir = {
+        let db = lock_db(&state)?;
+        db.data_dir().to_path_buf()
+    };
+    platform::open_folder(&data_dir)
+}
+
+#[tauri::command]
+async fn get_log_path() -> Result<String, String> {
+    logging::log_path()
+        .map(|path| path.display().to_string())
+        .ok_or_else(|| "Logs aren't being written to a file".to_string())
+}
+
+#[tauri::command]
+async fn open_log_folder() -> Result<(), String> {
+    let log_dir = logging::log_dir().ok_or("Logs aren't being written to a file")?;
+    platform::open_folder(log_dir)
+}
+
+#[tauri::command]
+async fn export_logs(dest_path: String) -> Result<(), String> {
+    logging::export_logs(Path::new(&dest_path))
+}
+
+#[tauri::command]
+async fn backup_database(state: State<'_, AppState>, dest_path: String) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    db.backup_to(Path::new(&dest_path))
+}
+
+// The PIN is checked against the current database, before the backup's own
+// settings (and PIN) replace it
+#[tauri::command]
+async fn restore_database(state: State<'_, AppState>, src_path: String, pin: Option<String>) -> Result<(), String> {
+    let mut db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.restore_from(Path::new(&src_path))?;
+
+    let current = db.get_settings().map_err(|e| e.to_string())?;
+    let games = db.get_custom_games().map_err(|e| e.to_string())?;
+    let curfew_windows = db.get_curfew_windows().map_err(|e| e.to_string())?;
+    let blacklist = db.get_blacklist().map_err(|e| e.to_string())?;
+    {
+        let mut monitor = lock_monitor(&state);
+        monitor.load_custom_games(games);
+        monitor.set_user_blacklist(blacklist);
+        monitor.set_curfew_windows(curfew_windows);
+        monitor.set_active_profile(db.active_profile_id());
+    }
+    reapply_cached_settings(&state, &current)
+}
+
+#[tauri::command]
+async fn set_steam_name_confirmation(state: State<'_, AppState>, enabled: bool, pin: Option<String>) -> Result<(), String> {
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.update_setting("confirm_steam_names", if enabled { "true" } else { "false" })
+        .map_err(|e| e.to_string())?;
+
+    let mut monitor = lock_monitor(&state);
+    monitor.set_steam_name_confirmation(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_pending_game_names(state: State<'_, AppState>) -> Result<Vec<PendingGameName>, String> {
+    let db = lock_db(&state)?;
+    db.get_pending_game_names().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn confirm_game_name(
+    state: State<'_, AppState>,
+    process_name: String,
+    display_name: String,
+) -> Result<usize, String> {
+    let display_name = display_name.trim().to_string();
+    if display_name.is_empty() || display_name.starts_with(UNCONFIRMED_NAME_PREFIX) {
+        return Err("Please enter the game's real name".to_string());
+    }
+
+    let db = lock_db(&state)?;
+    let renamed = db.confirm_game_name(&process_name, &display_name).map_err(|e| e.to_string())?;
+
+    let mut monitor = lock_monitor(&state);
+    monitor.rename_active_sessions(&process_name, &display_name);
+    monitor.add_game(process_name, display_name);
+    Ok(renamed)
+}
+
+// Today's distinct games, counting games still open that haven't been saved yet
+fn distinct_game_status(db: &Database, monitor: &GameMonitor) -> Result<DistinctGameStatus, String> {
+    let settings = db.get_settings().map_err(|e| e.to_string())?;
+    let mut games = db.get_games_played_today().map_err(|e| e.to_string())?;
+
+    let mut active = monitor.get_active_sessions();
+    active.sort_by_key(|session| session.start_time);
+    for session in active {
+        if !games.contains(&session.game_name) {
+            games.push(session.game_name);
+        }
+    }
+
+    Ok(DistinctGameStatus::new(games, settings.max_distinct_games_per_day))
+}
+
+#[tauri::command]
+async fn get_distinct_game_status(state: State<'_, AppState>) -> Result<DistinctGameStatus, String> {
+    let db = lock_db(&state)?;
+    let monitor = lock_monitor(&state);
+    distinct_game_status(&db, &monitor)
+}
+
+// Open games past their own daily limit, as (process_name, game_name, limit).
+// Games without a limit are never returned, and neither are sessions that were
+// already open when `enforced` (normalized process_name -> when) last closed
+// the game.
+fn games_over_limit(
+    db: &Database,
+    monitor: &GameMonitor,
+    enforced: &HashMap<String, chrono::DateTime<chrono::Utc>>,
+) -> Result<Vec<(String, String, i32)>, String> {
+    let limits = db.get_game_limits().map_err(|e| e.to_string())?;
+    if limits.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let today_start = db.current_day_start();
+    let now = chrono::Utc::now();
+    let mut over_limit = Vec::new();
+    for session in monitor.get_active_sessions() {
+        if enforced.get(&normalize_process_name(&session.process_name)).is_some_and(|at| session.start_time <= *at) {
+            continue;
+        }
+        let limit = match limits.iter().find(|limit| normalize_process_name(&limit.process_name) == normalize_process_name(&session.process_name)) {
+            Some(limit) => limit.daily_limit_minutes,
+            None => continue,
+        };
+
+        // Only the part of the open session played today counts, less idle and
+        // locked time, as in the budget
+        let billable_end = (now - chrono::Duration::seconds(session.excluded_seconds())).max(session.start_time);
+        let live_seconds = (billable_end - session.start_time.max(today_start)).num_seconds().max(0);
+        let used = db.get_today_usage_for_game(&session.process_name).map_err(|e| e.to_string())?
+            + (live_seconds / 60) as i32;
+        if used >= limit {
+            over_limit.push((session.process_name, session.game_name, limit));
+        }
+    }
+
+    Ok(over_limit)
+}
+
+#[tauri::command]
+async fn set_game_limit(state: State<'_, AppState>, process_name: String, daily_limit_minutes: i32, pin: Option<String>) -> Result<(), String> {
+    if daily_limit_minutes < 0 {
+        return Err("Limit can't be negative".to_string());
+    }
+
+    let db = lock_db(&state)?;
+    require_parent_pin(&db, pin.as_deref())?;
+    db.set_game_limit(&process_name, daily_limit_minutes).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_game_limits(state: State<'_, AppState>) -> Result<Vec<GameLimit>, String> {
+    let db = lock_db(&state)?;
+    db.get_game_limits().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn get_enforcement_log(state: State<'_, AppState>, limit: Option<usize>) -> Result<Vec<EnforcementEntry>, String> {
+    let db = lock_db(&state)?;
+    db.get_enforcement_log(limit.unwrap_or(100)).map_err(|e| e.to_string())
+}
+
+// Severity for a warning threshold: 0 means time is up, the lowest positive
+// threshold is the final warning, and anything higher is a heads-up
+fn threshold_severity(threshold: i32, thresholds: &[i32]) -> &'static str {
+    if threshold == 0 {
+        "exceeded"
+    } else if thresholds.iter().filter(|t| **t > 0).min() == Some(&threshold) {
+        "critical"
+    } else {
+        "warning"
+    }
+}
+
+// The most severe threshold newly crossed, if any. Every crossed threshold is
+// marked as fired so a big drop (or a late start) only warns once.
+fn crossed_warning_threshold(remaining_minutes: i32, thresholds: &[i32], fired: &mut Vec<i32>) -> Option<i32> {
+    let crossed: Vec<i32> = thresholds.iter()
+        .copied()
+        .filter(|threshold| remaining_minutes <= *threshold && !fired.contains(threshold))
+        .collect();
+    fired.extend(&crossed);
+    crossed.into_iter().min()
+}
+
+// Moves the auto-close grace period along. It starts once the budget is used
+// up and a game has started since games were last closed, so the same games
+// aren't killed again every tick, and is cancelled as soon as time is back
+// (e.g. more minutes were earned) or play stops. Returns whether the countdown
+// just began, the seconds left and the games that will be closed.
+fn enforcement_countdown(
+    db: &Database,
+    monitor: &mut GameMonitor,
+    last_enforced: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Option<(bool, i64, Vec<String>)> {
+    let sessions = monitor.get_billable_sessions();
+    let settings = db.get_settings().ok();
+    let enabled = settings.as_ref().is_none_or(|settings| settings.enforcement_enabled);
+    let out_of_time = realtime_budget_status(db, monitor)
+        .map(|budget| budget.remaining_today_minutes == 0)
+        .unwrap_or(false);
+
+    if sessions.is_empty() || !enabled || !out_of_time {
+        monitor.cancel_enforcement();
+        return None;
+    }
+
+    let started = monitor.enforcement_seconds_left(now).is_none();
+    if started {
+        if !sessions.iter().any(|session| last_enforced.is_none_or(|last| session.start_time > last)) {
+            return None;
+        }
+        let grace_seconds = settings.map_or(120, |settings| settings.kill_grace_seconds);
+        monitor.begin_enforcement_countdown(now, grace_seconds);
+    }
+
+    let seconds_left = monitor.enforcement_seconds_left(now)?;
+    let games = sessions.into_iter().map(|session| session.game_name).collect();
+    Some((started, seconds_left, games))
+}
+
+// Builds the next over-budget reminder while games stay open past the limit,
+// getting firmer with each repeat.
+fn over_budget_nag(db: &Database, monitor: &mut GameMonitor) -> Option<(String, String)> {
+    if monitor.get_billable_sessions().is_empty() {
+        monitor.reset_nag();
+        return None;
+    }
+
+    let budget = realtime_budget_status(db, monitor).ok()?;
+    if budget.remaining_today_minutes > 0 {
+        monitor.reset_nag();
+        return None;
+    }
+
+    if !monitor.should_notify_limit() {
+        return None;
+    }
+
+    // No reminders during quiet hours; enforcement, if it's on, still closes games
+    let settings = db.get_settings().ok()?;
+    if Database::in_quiet_hours(&settings.quiet_hours, chrono::Local::now().time()) {
+        return None;
+    }
+    let nag_count = monitor.next_nag(chrono::Utc::now(), settings.nag_interval_minutes)?;
+
+    let message = match nag_count {
+        1 => "You've used up today's gaming time. Time to start wrapping up!",
+        2 => "You're still playing past your limit. Please save and quit soon.",
+        _ => "You've been over your gaming budget for a while now. Please stop playing now.",
+    };
+
+    Some(("Gaming Time Exceeded".to_string(), message.to_string()))
+}
+
+// How long shutdown waits on a lock held by a stuck command or tick
+const EXIT_LOCK_TIMEOUT_MS: u64 = 2000;
+
+// Polls for a lock until the deadline instead of blocking shutdown. A poisoned
+// lock is still used, since the data behind it is needed to save sessions.
+fn lock_for_exit<'a, T>(mutex: &'a Mutex<T>, name: &str) -> Option<std::sync::MutexGuard<'a, T>> {
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(EXIT_LOCK_TIMEOUT_MS);
+    loop {
+        match mutex.try_lock() {
+            Ok(guard) => return Some(guard),
+            Err(std::sync::TryLockError::Poisoned(e)) => {
+                error!("The {} lock was poisoned; saving sessions anyway", name);
+                return Some(e.into_inner());
+            }
+            Err(std::sync::TryLockError::WouldBlock) if std::time::Instant::now() < deadline => {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+            }
+            Err(std::sync::TryLockError::WouldBlock) => {
+                error!("Timed out waiting for the {} lock at exit", name);
+                return None;
+            }
+        }
+    }
+}
+
+// Ends and saves open sessions so the last stretch of play isn't lost. The
+// snapshot is only cleared once everything saved; otherwise the next start
+// recovers the sessions from it.
+fn finalize_sessions_on_exit(state: &AppState) {
+    let sessions = match lock_for_exit(&state.monitor, "monitor") {
+        Some(mut monitor) => monitor.end_all_sessions(SessionEndReason::AppExit),
+        None => return,
+    };
+    if sessions.is_empty() {
+        return;
+    }
+
+    let db = match lock_for_exit(&state.db, "database") {
+        Some(db) => db,
+        None => return,
+    };
+    let mut saved_all = true;
+    for session in &sessions {
+        if let Err(e) = db.save_session(session) {
+            error!("Failed to save {} at exit: {}", session.game_name, e);
+            saved_all = false;
+        }
+    }
+    if saved_all {
+        if let Err(e) = db.save_active_sessions(&[]) {
+            error!("Failed to clear the active session snapshot at exit: {}", e);
+        }
+    }
+    info!("Saved {} sessions at exit", sessions.len());
+}
+
+fn main() {
+    // `--data-dir <path>` takes precedence over GTT_DATA_DIR
+    let data_dir = Database::resolve_data_dir(std::env::args()
+        .skip_while(|arg| arg != "--data-dir")
+        .nth(1)
+        .map(std::path::PathBuf::from));
+    // Before the database opens, so errors opening it end up in the log file
+    logging::init(&data_dir);
+
+    let database = Database::new(Some(data_dir)).expect("Failed to initialize database");
+    let settings = database.get_settings().expect("Failed to load settings");
+    logging::set_format(&settings.log_format);
+    database.seed_default_games(&GameMonitor::default_games()).expect("Failed to seed default games");
+    let games = database.get_custom_games().expect("Failed to load known games");
+    let curfew_windows = database.get_curfew_windows().expect("Failed to load curfew windows");
+    let blacklist = database.get_blacklist().expect("Failed to load the blacklist");
+    // The app may not have been running at midnight to finalize yesterday
+    if let Err(e) = database.finalize_day_rollover() {
+        error!("Failed to roll over yesterday's budget: {}", e);
+    }
+    if let Err(e) = database.apply_recurring_grants() {
+        error!("Failed to apply recurring grants: {}", e);
+    }
+    // Before monitoring starts, so games still running are picked up as new sessions
+    if let Err(e) = database.recover_orphaned_sessions() {
+        error!("Failed to recover orphaned sessions: {}", e);
+    }
+    if let Err(e) = database.purge_session_trash() {
+        error!("Failed to purge the session trash: {}", e);
+    }
+    let active_profile_id = database.active_profile_id().to_string();
+    let db = Arc::new(Mutex::new(database));
+
+    let mut game_monitor = GameMonitor::new();
+    game_monitor.load_custom_games(games);
+    game_monitor.set_user_blacklist(blacklist);
+    game_monitor.set_web_game_detection(settings.web_game_detection_enabled);
+    game_monitor.set_detection_mode(&settings.detection_mode);
+    game_monitor.set_steam_name_confirmation(settings.confirm_steam_names);
+    game_monitor.set_trust_startup_detections(settings.trust_startup_detections);
+    game_monitor.set_full_scan_interval(settings.full_scan_interval_seconds);
+    game_monitor.set_session_merge_window(settings.session_merge_seconds);
+    game_monitor.set_update_interval(settings.monitor_interval_secs);
+    game_monitor.set_count_foreground_only(settings.count_foreground_only);
+    if settings.monitoring_paused {
+        game_monitor.pause();
+    }
+    game_monitor.set_cpu_idle_detection(settings.cpu_idle_threshold, settings.cpu_idle_seconds);
+    game_monitor.set_input_idle_threshold(settings.idle_threshold_minutes);
+    game_monitor.set_curfew_windows(curfew_windows);
+    game_monitor.set_active_profile(&active_profile_id);
+    let monitor = Arc::new(Mutex::new(game_monitor));
+
+    let loop_health = Arc::new(Mutex::new(LoopHealth::new()));
+    let notification_cooldown = Arc::new(Mutex::new(NotificationCooldown::new(settings.notification_cooldown_seconds)));
+
+    let app_state = AppState {
+        db: db.clone(),
+        monitor: monitor.clone(),
+        loop_health: loop_health.clone(),
+        notification_cooldown: notification_cooldown.clone(),
+    };
+
+    info!("Starting Gaming Time Tracker");
+
+    tauri::Builder::default()
+        .manage(app_state)
+        .system_tray(tray::build(settings.monitoring_paused))
+        .on_system_tray_event(tray::handle_event)
+        .invoke_handler(tauri::generate_handler![
+            get_current_sessions,
+            get_total_active_time,
+            get_budget_status,
+            get_realtime_budget_status,
+            get_budget_exhaustion_time,
+            get_live_countdown,
+            get_recent_sessions,
+            query_sessions,
+            get_dashboard_state,
             add_learning_activity,
+            preview_earned_minutes,
+            get_learning_activities,
+            update_learning_activity,
+            delete_learning_activity,
+            minutes_needed_to_play,
+            get_earn_ratios,
+            set_earn_ratios,
+            get_weekday_allowances,
+            set_weekday_allowances,
+            get_curfew_windows,
+            set_curfew_windows,
+            mark_session_social,
+            get_open_launchers,
+            get_data_dir,
+            open_data_folder,
+            get_log_path,
+            open_log_folder,
+            export_logs,
+            backup_database,
+            restore_database,
+            verify_pin,
+            has_parent_pin,
+            set_parent_pin,
+            budget_if_i_learn,
             get_detected_games,
+            get_loop_health,
+            get_distinct_game_status,
+            set_game_limit,
+            get_game_limits,
+            get_enforcement_log,
+            set_game_notification_pref,
+            set_game_exempt,
+            should_notify_limit,
+            check_permissions,
+            run_diagnostics,
             pause_monitoring,
             resume_monitoring,
+            quit_app,
+            get_monitoring_status,
+            set_web_game_detection,
+            set_detection_mode,
+            get_settings,
+            update_settings,
+            set_steam_name_confirmation,
+            get_pending_game_names,
+            confirm_game_name,
+            set_log_format,
+            validate_configuration,
+            export_today_report,
+            export_history,
+            import_history,
+            get_earn_vs_use,
+            get_usage_stats,
+            get_game_totals,
+            get_session_length_histogram,
+            get_timeseries,
+            get_personal_bests,
+            get_adherence_stats,
+            set_achievement_bonus,
+            list_achievement_bonuses,
+            get_achievement_history,
+            save_today_report,
+            generate_weekly_report,
+            list_active_grants,
+            request_extra_time,
+            get_pending_requests,
+            approve_request,
+            deny_request,
+            list_profiles,
+            create_profile,
+            set_active_profile,
+            delete_profile,
+            revoke_grant,
+            grant_temporary_minutes,
+            split_session_at,
+            add_manual_session,
+            delete_session,
+            borrow_from_tomorrow,
+            grant_overtime,
+            add_recurring_grant,
+            remove_recurring_grant,
+            list_recurring_grants,
+            add_custom_game,
+            remove_custom_game,
+            list_custom_games,
+            add_game_pattern,
+            list_game_patterns,
+            reseed_default_games,
+            list_blacklist,
+            add_blacklist_entry,
+            remove_blacklist_entry,
+            set_game_goal,
+            remove_game_goal,
+            get_game_goal_progress,
+            import_steam_history,
             reset_today_sessions,
+            undo_reset_today,
+            reset_today_earned,
             add_budget_minutes,
             remove_budget_minutes,
             add_fake_playtime,
@@ -492,34 +2568,414 @@ fn main() {
             show_simple_overlay,
             close_overlay_window
         ])
-        .setup(move |_app| {
+        .setup(move |app| {
             let db_clone = db.clone();
             let monitor_clone = monitor.clone();
+            let health_clone = loop_health.clone();
+            let cooldown_clone = notification_cooldown.clone();
+            let app_handle = app.handle();
+
+            match lock_database(&db_clone) {
+                Ok(db) => report_config_warnings(&app_handle, &db),
+                Err(e) => error!("Failed to validate the configuration: {}", e),
+            }
 
             tauri::async_runtime::spawn(async move {
-                let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+                let mut next_tick = tokio::time::Instant::now();
+                let mut update_interval = 1; // monitor_interval_secs, as of the last tick
+                let mut current_date = lock_database(&db_clone)
+                    .map(|db| db.current_day())
+                    .unwrap_or_else(|_| chrono::Local::now().date_naive());
+                let mut warned_over_limit: Vec<String> = Vec::new(); // Games already warned about today
+                let mut limit_enforced: HashMap<String, chrono::DateTime<chrono::Utc>> = HashMap::new(); // Games closed at their own limit today, and when
+                let mut fired_thresholds: Vec<i32> = Vec::new(); // Warning thresholds already shown today
+                let mut last_enforced: Option<chrono::DateTime<chrono::Utc>> = None; // When games were last auto-closed
+                let mut last_curfew_enforced: Option<chrono::DateTime<chrono::Utc>> = None; // When games were last closed in the current curfew
+                let mut last_budget: Option<BudgetStatus> = None; // Last budget pushed to the frontend
+                let mut last_active_seconds: Option<i64> = None;
+                // Wall-clock time of the last monitor update. A monotonic clock may
+                // stand still while the PC sleeps, which would hide the gap.
+                let mut last_update_at: Option<chrono::DateTime<chrono::Utc>> = None;
 
                 loop {
-                    interval.tick().await;
-
-                    if let Ok(mut monitor) = monitor_clone.try_lock() {
-                        monitor.update();
+                    tokio::time::sleep_until(next_tick).await;
+                    let tick_started = tokio::time::Instant::now();
+
+                    let mut nag = None;
+                    let mut goal_alerts = Vec::new();
+                    let mut record_alerts = Vec::new();
+                    let mut countdown = None; // Stays None when the state couldn't be read this tick
+                    let mut errors = Vec::new();
+                    let mut completed = false;
+                    let mut over_limit_games = Vec::new();
+                    let mut enforcement = None;
+                    let mut enforce = false;
+                    let mut started_sessions = Vec::new();
+                    let mut ended_sessions = Vec::new();
+                    let mut budget = None;
+                    let mut active_seconds = None;
+                    let mut limited_games = Vec::new();
+                    let mut curfew_games = Vec::new();
+                    let mut threshold_warning = None;
+                    let mut style = "overlay".to_string(); // notification_style, read with the database
+
+                    if let Some(mut monitor) = try_lock_or_recover(&monitor_clone, "monitor") {
+                        // Processes are only re-enumerated every update_interval
+                        // seconds; the extra ticks in between just keep
+                        // countdowns and enforcement on time
+                        let now = chrono::Utc::now();
+                        update_interval = monitor.update_interval_seconds();
+                        let update_due = last_update_at
+                            .is_none_or(|last| (now - last).num_milliseconds() + 500 >= update_interval * 1000);
+                        if update_due {
+                            if let Some(gap) = last_update_at.map(|last| (now - last).num_seconds()) {
+                                if gap > monitor.sleep_gap_seconds() {
+                                    monitor.handle_resume_gap(gap);
+                                }
+                            }
+                            last_update_at = Some(now);
+
+                            let active_before: Vec<Option<String>> = monitor.get_active_sessions().into_iter()
+                                .map(|session| session.id)
+                                .collect();
+                            monitor.update();
+                            started_sessions = monitor.get_active_sessions().into_iter()
+                                .filter(|session| !active_before.contains(&session.id))
+                                .collect();
+                        }
+                        active_seconds = Some(monitor.get_total_active_time());
 
                         let completed_sessions = monitor.get_completed_sessions();
 
-                        if let Ok(db) = db_clone.try_lock() {
-                            for session in completed_sessions {
-                                if let Err(e) = db.save_session(&session) {
-                                    error!("Failed to save session: {}", e);
+                        let db = match try_lock_database(&db_clone) {
+                            Some(Ok(db)) => Some(db),
+                            Some(Err(e)) => {
+                                loop_error(&mut errors, format!("Failed to lock the database: {}", e));
+                                None
+                            }
+                            None => None,
+                        };
+                        if let Some(db) = db {
+                            style = notification_style(&db);
+                            let today = db.current_day();
+                            if today != current_date {
+                                if let Err(e) = db.finalize_day_rollover() {
+                                    loop_error(&mut errors, format!("Failed to roll over yesterday's budget: {}", e));
+                                }
+                                if let Err(e) = db.apply_recurring_grants() {
+                                    loop_error(&mut errors, format!("Failed to apply recurring grants: {}", e));
+                                }
+                                // Checked every rollover so achievement bonuses are granted even
+                                // when the celebrations themselves are turned off
+                                match db.take_personal_best_alerts() {
+                                    Ok(alerts) => {
+                                        if db.get_settings().map(|s| s.personal_best_notifications).unwrap_or(false) {
+                                            record_alerts = alerts;
+                                        }
+                                    }
+                                    Err(e) => loop_error(&mut errors, format!("Failed to check personal bests: {}", e)),
+                                }
+                                current_date = today;
+                                lock_or_recover(&cooldown_clone, "notification cooldown").reset_all();
+                                warned_over_limit.clear();
+                                limit_enforced.clear();
+                                fired_thresholds.clear();
+                            }
+
+                            let sessions_ended = !completed_sessions.is_empty();
+
+                            if let Err(e) = db.save_sessions(&completed_sessions) {
+                                // Retried one by one, so one bad session doesn't cost the others.
+                                // Any that still fail are kept for the next tick.
+                                loop_error(&mut errors, format!("Failed to save sessions: {}", e));
+                                let mut unsaved = Vec::new();
+                                for session in completed_sessions {
+                                    match db.save_session(&session) {
+                                        Ok(()) => ended_sessions.push(session),
+                                        Err(e) => {
+                                            loop_error(&mut errors, format!("Failed to save session: {}", e));
+                                            unsaved.push(session);
+                                        }
+                                    }
+                                }
+                                if !unsaved.is_empty() {
+                                    monitor.requeue_completed_sessions(unsaved);
+                                }
+                            } else {
+                                ended_sessions.extend(completed_sessions);
+                            }
+
+                            if let Err(e) = monitor.persist_active(&db) {
+                                loop_error(&mut errors, format!("Failed to snapshot active sessions: {}", e));
+                            }
+
+                            // Goal progress only changes when a session is recorded
+                            if sessions_ended {
+                                match db.take_goal_alerts() {
+                                    Ok(alerts) => goal_alerts = alerts,
+                                    Err(e) => loop_error(&mut errors, format!("Failed to check game goals: {}", e)),
+                                }
+                            }
+
+                            nag = over_budget_nag(&db, &mut monitor);
+                            countdown = Some(countdown_state(&db, &monitor));
+
+                            let now = chrono::Utc::now();
+                            enforcement = enforcement_countdown(&db, &mut monitor, last_enforced, now);
+                            if let Some((_, seconds_left, games)) = &enforcement {
+                                // The grace period takes over the countdown window once time is up
+                                countdown = Some(Some((*seconds_left, games.clone())));
+                                enforce = monitor.enforcement_due(now);
+                            }
+
+                            match distinct_game_status(&db, &monitor) {
+                                Ok(status) => {
+                                    for game in status.over_limit_games {
+                                        if !warned_over_limit.contains(&game) {
+                                            warned_over_limit.push(game.clone());
+                                            over_limit_games.push((game, status.limit));
+                                        }
+                                    }
+                                }
+                                Err(e) => loop_error(&mut errors, format!("Failed to check distinct games: {}", e)),
+                            }
+
+                            match realtime_budget_status(&db, &monitor) {
+                                Ok(status) => budget = Some(status),
+                                Err(e) => loop_error(&mut errors, format!("Failed to compute budget: {}", e)),
+                            }
+
+                            // Only warn while someone is playing a game that wants warnings
+                            if let (Some(status), Ok(settings)) = (&budget, db.get_settings()) {
+                                if monitor.should_notify_limit() {
+                                    let remaining = status.remaining_today_minutes;
+                                    if let Some(threshold) = crossed_warning_threshold(remaining, &settings.warning_thresholds, &mut fired_thresholds) {
+                                        threshold_warning = Some((threshold_severity(threshold, &settings.warning_thresholds), remaining.max(0)));
+                                    }
+                                }
+                            }
+
+                            // Budget warnings can fire again once time is added back
+                            if let Some(status) = &budget {
+                                let threshold = db.get_settings().map(|settings| settings.warning_threshold_minutes).unwrap_or(15);
+                                lock_or_recover(&cooldown_clone, "notification cooldown")
+                                    .reset_cleared_budget_warnings(status.remaining_today_minutes, threshold);
+                            }
+
+                            if db.get_settings().map(|settings| settings.enforcement_enabled).unwrap_or(true) {
+                                match games_over_limit(&db, &monitor, &limit_enforced) {
+                                    Ok(games) => limited_games = games,
+                                    Err(e) => loop_error(&mut errors, format!("Failed to check game limits: {}", e)),
+                                }
+
+                                // Curfew ignores the budget entirely. Games are closed once,
+                                // and again only if one is started during the same curfew.
+                                if monitor.in_curfew(chrono::Local::now()) {
+                                    curfew_games = monitor.get_billable_sessions().into_iter()
+                                        .filter(|session| last_curfew_enforced.is_none_or(|last| session.start_time > last))
+                                        .map(|session| session.game_name)
+                                        .collect();
+                                } else {
+                                    last_curfew_enforced = None;
                                 }
                             }
+                            completed = true;
+                        } else {
+                            // Database busy (e.g. a command mid-write) or unusable; save next tick instead
+                            monitor.requeue_completed_sessions(completed_sessions);
+                        }
+                    }
+
+                    // Pushed to the frontend only on change, so it doesn't need to poll
+                    for session in &started_sessions {
+                        if let Err(e) = app_handle.emit_all("session-started", session) {
+                            loop_error(&mut errors, format!("Failed to emit session-started: {}", e));
+                        }
+                    }
+                    for session in &ended_sessions {
+                        if let Err(e) = app_handle.emit_all("session-ended", session) {
+                            loop_error(&mut errors, format!("Failed to emit session-ended: {}", e));
+                        }
+                    }
+                    if let Some(status) = budget {
+                        if last_budget.as_ref() != Some(&status) {
+                            if let Err(e) = app_handle.emit_all("budget-update", &status) {
+                                loop_error(&mut errors, format!("Failed to emit budget-update: {}", e));
+                            }
+                            tray::show_remaining(&app_handle, status.remaining_today_minutes);
+                            last_budget = Some(status);
+                        }
+                    }
+                    if let Some(seconds) = active_seconds {
+                        if last_active_seconds != Some(seconds) {
+                            if let Err(e) = app_handle.emit_all("active-time-update", seconds) {
+                                loop_error(&mut errors, format!("Failed to emit active-time-update: {}", e));
+                            }
+                            last_active_seconds = Some(seconds);
+                        }
+                    }
+
+                    for progress in &goal_alerts {
+                        let (title, message, urgency) = goal_alert_notification(progress);
+                        if let Err(e) = deliver_notification(&app_handle, &style, title, message, urgency) {
+                            loop_error(&mut errors, format!("Failed to show goal notification: {}", e));
+                        }
+                    }
+
+                    if let Some((severity, remaining)) = threshold_warning {
+                        // Time running out is announced by the enforcement overlay instead
+                        if !(severity == "exceeded" && matches!(enforcement, Some((true, _, _)))) {
+                            let (title, message) = match severity {
+                                "exceeded" => ("Gaming Time Exceeded".to_string(),
+                                               "Your gaming budget for today is used up.".to_string()),
+                                "critical" => ("Final Warning".to_string(),
+                                               format!("Only {} minute{} of gaming left today. Save your progress now!",
+                                                       remaining, if remaining == 1 { "" } else { "s" })),
+                                _ => ("Gaming Time Warning".to_string(),
+                                      format!("{} minutes of gaming left today. Start wrapping up soon.", remaining)),
+                            };
+                            if let Err(e) = deliver_warning(&app_handle, &style, title, message, severity.to_string()).await {
+                                loop_error(&mut errors, format!("Failed to show {} overlay: {}", severity, e));
+                            }
+                        }
+                    }
+
+                    if let Some((true, seconds_left, _)) = &enforcement {
+                        let title = "Gaming Time Exceeded".to_string();
+                        let message = if *seconds_left > 0 {
+                            format!("Your gaming budget for today is used up. Your games will be closed in {}:{:02}. Save your progress now!",
+                                    seconds_left / 60, seconds_left % 60)
+                        } else {
+                            "Your gaming budget for today is used up. Your games are being closed now.".to_string()
+                        };
+                        if let Err(e) = deliver_warning(&app_handle, &style, title, message, "exceeded".to_string()).await {
+                            loop_error(&mut errors, format!("Failed to show enforcement overlay: {}", e));
+                        }
+                        // The overlay already says what happened, so skip this tick's reminder
+                        nag = None;
+                    }
+
+                    // Also closes the countdown once play stops or time runs out.
+                    // The countdown window stays on top, so other styles count
+                    // down in the tray tooltip instead.
+                    match &countdown {
+                        Some(Some((remaining_seconds, games))) if style == "overlay" => {
+                            if let Err(e) = update_countdown_overlay(&app_handle, *remaining_seconds, games, enforcement.is_some()) {
+                                loop_error(&mut errors, format!("Failed to update countdown overlay: {}", e));
+                            }
+                        }
+                        Some(Some((remaining_seconds, _))) => {
+                            close_countdown_overlay(&app_handle);
+                            if style == "tray" {
+                                tray::show_warning(&app_handle, if enforcement.is_some() { "Games close in" } else { "Gaming time left" },
+                                                   &format!("{}:{:02}", remaining_seconds / 60, remaining_seconds % 60));
+                            }
+                        }
+                        Some(None) => close_countdown_overlay(&app_handle),
+                        None => {}
+                    }
+
+                    for (game, limit) in over_limit_games {
+                        let message = format!("{} is over today's limit of {} different games. Consider going back to a game you've already played.", game, limit);
+                        if let Err(e) = deliver_notification(&app_handle, &style, "Too Many Games Today".to_string(), message, "warning".to_string()) {
+                            loop_error(&mut errors, format!("Failed to show game limit notification: {}", e));
+                        }
+                    }
+
+                    for message in record_alerts {
+                        if let Err(e) = deliver_notification(&app_handle, &style, "Personal Best!".to_string(), message, "info".to_string()) {
+                            loop_error(&mut errors, format!("Failed to show personal best notification: {}", e));
+                        }
+                    }
+
+                    // A game past its own limit is closed on its own; other games keep running
+                    for (process_name, game_name, limit) in limited_games {
+                        limit_enforced.insert(normalize_process_name(&process_name), chrono::Utc::now());
+                        let message = format!("{} has reached its daily limit of {} minutes and is being closed. Other games can keep running.",
+                                              game_name, limit);
+                        if let Err(e) = deliver_warning(&app_handle, &style, "Game Limit Reached".to_string(), message, "exceeded".to_string()).await {
+                            loop_error(&mut errors, format!("Failed to show game limit overlay: {}", e));
+                        }
+
+                        if close_games(&monitor_clone, Some(&process_name)).await.closed.is_empty() {
+                            loop_error(&mut errors, format!("Failed to close {} at its daily limit", game_name));
+                        } else {
+                            info!("{} reached its {}-minute daily limit and was closed", game_name, limit);
+                            record_enforcement(&app_handle, &db_clone, &[game_name], EnforcementReason::GameLimit, &mut errors);
+                        }
+                    }
+
+                    if !curfew_games.is_empty() {
+                        last_curfew_enforced = Some(chrono::Utc::now());
+                        let message = format!("Gaming isn't allowed right now. Closing: {}", curfew_games.join(", "));
+                        if let Err(e) = deliver_warning(&app_handle, &style, "Gaming Curfew".to_string(), message, "exceeded".to_string()).await {
+                            loop_error(&mut errors, format!("Failed to show curfew overlay: {}", e));
+                        }
+
+                        let result = close_games(&monitor_clone, None).await;
+                        info!("Curfew in effect; closed {} games: {:?}", result.closed.len(), result.closed);
+                        if !result.resisted.is_empty() || !result.failed.is_empty() {
+                            loop_error(&mut errors, format!("Games still running during curfew: {:?}",
+                                                            [result.resisted, result.failed].concat()));
+                        }
+                        let closed = result.closed;
+                        record_enforcement(&app_handle, &db_clone, &closed, EnforcementReason::Curfew, &mut errors);
+                    }
+
+                    if enforce {
+                        last_enforced = Some(chrono::Utc::now());
+
+                        let result = close_games(&monitor_clone, None).await;
+                        lock_or_recover(&monitor_clone, "monitor").cancel_enforcement();
+                        info!("Grace period over; closed {} games: {:?}", result.closed.len(), result.closed);
+                        if !result.resisted.is_empty() || !result.failed.is_empty() {
+                            loop_error(&mut errors, format!("Games still running after the grace period: {:?}",
+                                                            [result.resisted, result.failed].concat()));
+                        }
+                        let closed = result.closed;
+                        record_enforcement(&app_handle, &db_clone, &closed, EnforcementReason::BudgetExceeded, &mut errors);
+                        close_countdown_overlay(&app_handle);
+                    }
+
+                    if let Some((title, message)) = nag {
+                        if let Err(e) = deliver_warning(&app_handle, &style, title, message, "exceeded".to_string()).await {
+                            loop_error(&mut errors, format!("Failed to show over-budget reminder: {}", e));
                         }
                     }
+
+                    lock_or_recover(&health_clone, "loop health").record_tick(chrono::Utc::now(), completed, errors);
+
+                    // Tick every second while time is nearly up, so the grace
+                    // period and countdown stay smooth and games close on time
+                    let playing_last_minute = matches!(&last_budget, Some(status) if status.remaining_today_minutes <= 1)
+                        && last_active_seconds.is_some_and(|seconds| seconds > 0);
+                    let fast_tick = enforcement.is_some() || matches!(countdown, Some(Some(_))) || playing_last_minute;
+                    let tick_seconds = if fast_tick { 1 } else { update_interval };
+                    next_tick = tick_started + tokio::time::Duration::from_secs(tick_seconds as u64);
                 }
             });
 
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app_handle, event| match event {
+            // Closing the main window only hides it to the tray. Quitting stops
+            // tracking, so it's left to tray::quit and quit_app, which ask for
+            // the parent PIN.
+            tauri::RunEvent::WindowEvent { label, event: tauri::WindowEvent::CloseRequested { api, .. }, .. }
+                if label == "main" =>
+            {
+                api.prevent_close();
+                if let Some(window) = app_handle.get_window(&label) {
+                    if let Err(e) = window.hide() {
+                        error!("Failed to hide the main window: {}", e);
+                    }
+                }
+            }
+            // Sent when the last window closes; exiting from code skips it
+            tauri::RunEvent::ExitRequested { api, .. } => api.prevent_exit(),
+            tauri::RunEvent::Exit => finalize_sessions_on_exit(&app_handle.state::<AppState>()),
+            _ => {}
+        });
 }
\ No newline at end of file
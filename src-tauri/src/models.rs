@@ -1,5 +1,108 @@
 use serde::{Deserialize, Serialize};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SessionEndReason {
+    Natural, // The game exited on its own
+    ForceClose, // The app closed the game
+    Recovered, // Still open when the app stopped unexpectedly; ended at its last snapshot
+    AppExit, // Still open when the app was closed
+    ProfileSwitch, // Still open when another profile was made active
+    Sleep, // Cut off when the PC went to sleep; play after waking is a new session
+}
+
+impl SessionEndReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SessionEndReason::Natural => "natural",
+            SessionEndReason::ForceClose => "force_close",
+            SessionEndReason::Recovered => "recovered",
+            SessionEndReason::AppExit => "app_exit",
+            SessionEndReason::ProfileSwitch => "profile_switch",
+            SessionEndReason::Sleep => "sleep",
+        }
+    }
+}
+
+impl FromStr for SessionEndReason {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "natural" => Ok(SessionEndReason::Natural),
+            "force_close" => Ok(SessionEndReason::ForceClose),
+            "recovered" => Ok(SessionEndReason::Recovered),
+            "app_exit" => Ok(SessionEndReason::AppExit),
+            "profile_switch" => Ok(SessionEndReason::ProfileSwitch),
+            "sleep" => Ok(SessionEndReason::Sleep),
+            _ => Err(format!("Unknown session end reason: {}", value)),
+        }
+    }
+}
+
+// Why enforcement closed a game
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnforcementReason {
+    BudgetExceeded, // The grace period ran out with the budget used up
+    Curfew, // Played during a curfew window
+    GameLimit, // The game went past its own daily limit
+}
+
+impl EnforcementReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnforcementReason::BudgetExceeded => "budget_exceeded",
+            EnforcementReason::Curfew => "curfew",
+            EnforcementReason::GameLimit => "game_limit",
+        }
+    }
+}
+
+impl FromStr for EnforcementReason {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "budget_exceeded" => Ok(EnforcementReason::BudgetExceeded),
+            "curfew" => Ok(EnforcementReason::Curfew),
+            "game_limit" => Ok(EnforcementReason::GameLimit),
+            _ => Err(format!("Unknown enforcement reason: {}", value)),
+        }
+    }
+}
+
+// One player sharing the computer; sessions, learning and budgets are kept per profile
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub daily_allowance_minutes: i32,
+    pub created_at: DateTime<Utc>,
+    pub is_active: bool,
+}
+
+// Extra time asked for from the warning overlay, waiting on a parent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeRequest {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub minutes: i32,
+    pub reason: String,
+    pub status: String, // "pending", "approved" or "denied"
+    pub profile_id: String,
+}
+
+// One game closed by enforcement, kept so parents can see it happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnforcementEntry {
+    pub id: String,
+    pub timestamp: DateTime<Utc>,
+    pub game_name: String,
+    pub reason: EnforcementReason,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameSession {
@@ -12,46 +115,121 @@ pub struct GameSession {
     pub is_social_session: bool,
     pub is_concurrent: bool, // New field
     pub concurrent_session_ids: Vec<String>, // IDs of other concurrent sessions
+    pub is_web_game: bool, // Detected from a browser tab title rather than a process
+    pub idle_seconds: i64, // Time the game was judged idle, excluded from the duration
+    #[serde(default)]
+    pub locked_seconds: i64, // Time the PC was asleep or locked, excluded from the duration
+    pub is_imported: bool, // Summary imported from another service, not tracked by the app
+    pub end_reason: Option<SessionEndReason>,
+    #[serde(default)]
+    pub detection_source: String, // Path that created the session: "known_game", "steam_heuristic", "launcher_child", "window_title", "steam_import", "manual", "debug"
+    #[serde(default)]
+    pub pids: Vec<u32>, // Running instances of the game; the session ends once all have exited
+    #[serde(default)]
+    pub profile_id: String, // Profile the session counts against; empty means whichever is active when saved
+    #[serde(default = "Utc::now")]
+    pub last_seen: DateTime<Utc>, // Last update that found the game running
+    #[serde(default)]
+    pub is_manual: bool, // Entered by a parent after the fact rather than detected; billed like any other
+}
+
+// Narrows session history; every field is optional and an empty filter lists
+// the most recent sessions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SessionFilter {
+    #[serde(default)]
+    pub game_name: Option<String>, // Case-insensitive substring of the game name
+    #[serde(default)]
+    pub start: Option<DateTime<Utc>>, // Sessions starting at or after this time
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>, // Sessions starting at or before this time
+    #[serde(default)]
+    pub min_duration_seconds: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: Option<usize>,
+}
+
+// One page of filtered sessions, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionPage {
+    pub sessions: Vec<GameSession>,
+    pub total: i64, // Sessions matching the filter across all pages
 }
 
 impl GameSession {
     pub fn new(game_name: String, process_name: String) -> Self {
+        let now = Utc::now();
         Self {
             id: Some(uuid::Uuid::new_v4().to_string()),
             game_name,
             process_name,
-            start_time: Utc::now(),
+            start_time: now,
             end_time: None,
             duration_seconds: None,
             is_social_session: false,
             is_concurrent: false,
             concurrent_session_ids: Vec::new(),
+            is_web_game: false,
+            idle_seconds: 0,
+            locked_seconds: 0,
+            is_imported: false,
+            end_reason: None,
+            detection_source: String::new(),
+            pids: Vec::new(),
+            profile_id: String::new(),
+            last_seen: now,
+            is_manual: false,
         }
     }
 
-    pub fn end_session(&mut self) {
-        self.end_time = Some(Utc::now());
-        if let Some(end) = self.end_time {
-            self.duration_seconds = Some((end - self.start_time).num_seconds());
-        }
+    // A game that exited on its own ends at last_seen rather than when the exit
+    // was noticed, which may be a tick or a whole sleep later
+    pub fn end_session(&mut self, end_time: DateTime<Utc>) {
+        let end = end_time.max(self.start_time);
+        self.end_time = Some(end);
+        self.duration_seconds = Some(((end - self.start_time).num_seconds() - self.excluded_seconds()).max(0));
     }
 
     pub fn current_duration(&self) -> i64 {
-        match self.end_time {
+        let elapsed = match self.end_time {
             Some(end) => (end - self.start_time).num_seconds(),
             None => (Utc::now() - self.start_time).num_seconds(),
-        }
+        };
+        (elapsed - self.excluded_seconds()).max(0)
+    }
+
+    // Time within the session that isn't counted as play
+    pub fn excluded_seconds(&self) -> i64 {
+        self.idle_seconds + self.locked_seconds
+    }
+
+    // Whether the session shares any time with [start, end). Sessions that only
+    // touch end to start don't overlap. An open session is taken to run until now.
+    pub fn overlaps(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> bool {
+        let own_end = self.end_time.unwrap_or_else(Utc::now);
+        self.start_time < end && start < own_end
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BudgetStatus {
     pub daily_allowance_minutes: i32,
     pub used_today_minutes: i32,
     pub remaining_today_minutes: i32,
     pub rollover_minutes: i32,
     pub earned_minutes: i32,
+    pub bonus_minutes: i32, // Active one-off grants that expire on their own
+    pub recurring_bonus_minutes: i32, // Active grants from recurring weekly boosts
+    pub debt_repayment_minutes: i32, // Time borrowed on an earlier day, deducted from today
+    pub debt_minutes: i32, // Time borrowed against future days
+    pub debt_capacity_minutes: i32, // How much more can be borrowed before hitting the cap
     pub total_available_minutes: i32,
+    pub remaining_base: i32, // What's left of today's allowance after debt repayment and play
+    pub remaining_rollover: i32,
+    pub remaining_bonus: i32, // One-off and recurring grants together
+    pub remaining_earned: i32,
 }
 
 impl BudgetStatus {
@@ -62,14 +240,261 @@ impl BudgetStatus {
             remaining_today_minutes: daily_allowance,
             rollover_minutes: 0,
             earned_minutes: 0,
+            bonus_minutes: 0,
+            recurring_bonus_minutes: 0,
+            debt_repayment_minutes: 0,
+            debt_minutes: 0,
+            debt_capacity_minutes: 0,
             total_available_minutes: daily_allowance,
+            remaining_base: daily_allowance,
+            remaining_rollover: 0,
+            remaining_bonus: 0,
+            remaining_earned: 0,
         }
     }
 
+    // Debt repayment and then play are taken from the sources in a fixed order:
+    // base allowance, rollover, bonus grants, earned time. Base goes first since
+    // the day's rollover is worked out from how much of it was left; rollover
+    // and grants come before earned time because they expire on a schedule.
     pub fn update_usage(&mut self, used_minutes: i32) {
         self.used_today_minutes = used_minutes;
-        self.total_available_minutes = self.daily_allowance_minutes + self.rollover_minutes + self.earned_minutes;
+        self.total_available_minutes = (self.daily_allowance_minutes + self.rollover_minutes + self.earned_minutes
+            + self.bonus_minutes + self.recurring_bonus_minutes - self.debt_repayment_minutes).max(0);
         self.remaining_today_minutes = (self.total_available_minutes - used_minutes).max(0);
+
+        let mut to_deduct = self.debt_repayment_minutes.max(0) + used_minutes.max(0);
+        let mut deduct = |available: i32| {
+            let taken = to_deduct.min(available.max(0));
+            to_deduct -= taken;
+            available.max(0) - taken
+        };
+        self.remaining_base = deduct(self.daily_allowance_minutes);
+        self.remaining_rollover = deduct(self.rollover_minutes);
+        self.remaining_bonus = deduct(self.bonus_minutes + self.recurring_bonus_minutes);
+        self.remaining_earned = deduct(self.earned_minutes);
+    }
+}
+
+// Seed for a timer the UI ticks down on its own and resyncs now and then.
+// Rounded up to whole minutes, remaining_seconds matches the realtime
+// budget's remaining_today_minutes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LiveCountdown {
+    pub remaining_seconds: i64, // Never negative
+    pub as_of: DateTime<Utc>,
+    pub active_game_count: usize, // The timer only runs while this is above zero
+}
+
+// The last seven days, today included, for the printable weekly report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeeklyReport {
+    pub generated_at: DateTime<Utc>,
+    pub profile_name: String,
+    pub days: Vec<ReportDay>, // Oldest first
+    pub top_games: Vec<ReportGame>, // Most played first
+    pub earned_minutes: i32,
+    pub enforcement_events: Vec<EnforcementEntry>, // Newest first
+    pub days_within_budget: i32, // Out of the finished days in the week
+    pub finished_days: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportDay {
+    pub date: NaiveDate,
+    pub used_minutes: i32,
+    pub allowance_minutes: i32, // Base allowance, weekday overrides included
+    pub earned_minutes: i32,
+    pub within_budget: Option<bool>, // None for today, which isn't over yet
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportGame {
+    pub game_name: String,
+    pub minutes: i32,
+    pub session_count: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DashboardState {
+    pub budget: BudgetStatus, // Includes time from games still open
+    pub active_sessions: Vec<GameSession>, // duration_seconds holds the live duration so far
+    pub total_active_seconds: i64,
+    pub recent_sessions: Vec<GameSession>,
+    pub is_paused: bool,
+    pub next_reset: DateTime<Utc>, // Start of the next budget day, when the daily budget resets
+    pub generated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EarnUseStats {
+    pub period: String,
+    pub earned_minutes: i32,
+    pub used_minutes: i32,
+    pub ratio: Option<f64>, // earned / used, None when nothing was used
+    pub net_minutes: i32, // earned - used
+}
+
+impl EarnUseStats {
+    pub fn new(period: String, earned_minutes: i32, used_minutes: i32) -> Self {
+        Self {
+            period,
+            earned_minutes,
+            used_minutes,
+            ratio: if used_minutes > 0 { Some(earned_minutes as f64 / used_minutes as f64) } else { None },
+            net_minutes: earned_minutes - used_minutes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyTotal {
+    pub date: NaiveDate, // Local calendar day
+    pub minutes: i32,
+}
+
+// Everything ever tracked for one game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameTotal {
+    pub game_name: String,
+    pub total_minutes: i64, // Overlapping sessions of the game are counted once
+    pub session_count: i32,
+    pub first_played: DateTime<Utc>,
+    pub last_played: DateTime<Utc>, // When the most recent session ended
+    pub average_session_minutes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageStats {
+    pub period: String,
+    pub daily_totals: Vec<DailyTotal>, // Oldest first, with zero for days without play
+    pub busiest_day: Option<DailyTotal>, // None when nothing was played
+    pub total_minutes: i32,
+    pub earned_minutes: i32,
+    pub average_minutes_per_active_day: f64, // Days without play are left out
+}
+
+impl UsageStats {
+    pub fn new(period: String, daily_totals: Vec<(NaiveDate, i32)>, earned_minutes: i32) -> Self {
+        let daily_totals: Vec<DailyTotal> = daily_totals.into_iter()
+            .map(|(date, minutes)| DailyTotal { date, minutes })
+            .collect();
+        let total_minutes = daily_totals.iter().map(|day| day.minutes).sum();
+        let active_days = daily_totals.iter().filter(|day| day.minutes > 0).count();
+
+        Self {
+            period,
+            busiest_day: daily_totals.iter().filter(|day| day.minutes > 0).max_by_key(|day| day.minutes).cloned(),
+            total_minutes,
+            earned_minutes,
+            average_minutes_per_active_day: if active_days > 0 { total_minutes as f64 / active_days as f64 } else { 0.0 },
+            daily_totals,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PersonalBests {
+    pub longest_under_budget_streak_days: i32, // Consecutive finished days within the allowance
+    pub most_learning_minutes_in_day: i32,
+    pub longest_break_minutes: i64, // Longest stretch between gaming sessions
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdherenceStats {
+    pub current_streak_days: i32, // Finished days within budget, ending yesterday
+    pub longest_streak_days: i32,
+    pub over_budget_days_last_30: i32,
+}
+
+// Achievement milestones that can carry a bonus, one per personal best
+pub const ACHIEVEMENT_MILESTONES: [&str; 3] = ["longest_streak", "most_learning_day", "longest_break"];
+
+impl PersonalBests {
+    // (milestone, new record value, celebration message) for every record that
+    // beats the previous bests
+    pub fn records_broken(&self, previous: &PersonalBests) -> Vec<(&'static str, i64, String)> {
+        let mut records = Vec::new();
+
+        if self.longest_under_budget_streak_days > previous.longest_under_budget_streak_days {
+            records.push(("longest_streak", self.longest_under_budget_streak_days as i64,
+                          format!("New record: {} days in a row within your gaming budget!",
+                                  self.longest_under_budget_streak_days)));
+        }
+        if self.most_learning_minutes_in_day > previous.most_learning_minutes_in_day {
+            records.push(("most_learning_day", self.most_learning_minutes_in_day as i64,
+                          format!("New record: {} minutes of learning in a single day!",
+                                  self.most_learning_minutes_in_day)));
+        }
+        if self.longest_break_minutes > previous.longest_break_minutes {
+            records.push(("longest_break", self.longest_break_minutes,
+                          format!("New record: your longest break from gaming, {}h {}m!",
+                                  self.longest_break_minutes / 60, self.longest_break_minutes % 60)));
+        }
+
+        records
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementBonus {
+    pub milestone: String, // One of ACHIEVEMENT_MILESTONES
+    pub minutes: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AchievementGrant {
+    pub achievement: String, // "<milestone>:<record value>"
+    pub grant_id: String,
+    pub minutes: i32,
+    pub granted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeseriesPoint {
+    pub bucket_start: DateTime<Utc>,
+    pub value: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistogramBin {
+    pub label: String, // e.g. "15–30m"
+    pub min_minutes: i32, // Inclusive
+    pub max_minutes: Option<i32>, // Exclusive; None for the open-ended last bin
+    pub count: usize,
+}
+
+impl HistogramBin {
+    // Bins covering [0, e1), [e1, e2), ..., [en, ∞) for ascending edges in minutes
+    pub fn from_edges(edges: &[i32]) -> Vec<Self> {
+        let mut bins = Vec::new();
+        let mut lower = 0;
+
+        for &edge in edges {
+            let label = if lower == 0 {
+                format!("<{}", Self::format_minutes(edge))
+            } else {
+                format!("{}–{}", Self::format_minutes(lower), Self::format_minutes(edge))
+            };
+            bins.push(Self { label, min_minutes: lower, max_minutes: Some(edge), count: 0 });
+            lower = edge;
+        }
+
+        bins.push(Self {
+            label: format!(">{}", Self::format_minutes(lower)),
+            min_minutes: lower,
+            max_minutes: None,
+            count: 0,
+        });
+        bins
+    }
+
+    fn format_minutes(minutes: i32) -> String {
+        if minutes >= 60 && minutes % 60 == 0 {
+            format!("{}h", minutes / 60)
+        } else {
+            format!("{}m", minutes)
+        }
     }
 }
 
@@ -81,18 +506,37 @@ pub struct LearningActivity {
     pub duration_minutes: i32,
     pub earned_gaming_minutes: i32,
     pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub is_voided: bool, // Kept in history but no longer counts toward earned minutes
 }
 
+// Learning minutes per gaming minute for activity types without their own ratio (1:5)
+pub const DEFAULT_EARN_RATIO: i32 = 5;
+
 impl LearningActivity {
-    pub fn new(activity_type: String, description: String, duration_minutes: i32) -> Self {
-        // Different learning types earn different rates
-        let earned_gaming_minutes = match activity_type.as_str() {
-            "coding" => duration_minutes / 4,      // 1:4 ratio (15 min gaming per hour)
-            "reading" => duration_minutes / 6,     // 1:6 ratio (10 min gaming per hour)
-            "course" => duration_minutes / 4,      // 1:4 ratio
-            "exercise" => duration_minutes / 3,    // 1:3 ratio (20 min gaming per hour)
-            _ => duration_minutes / 5,             // Default 1:5 ratio
-        };
+    // Learning minutes needed to earn one minute of gaming, until parents configure their own
+    pub fn default_earn_ratios() -> HashMap<String, i32> {
+        let mut ratios = HashMap::new();
+        ratios.insert("coding".to_string(), 4);     // 1:4 ratio (15 min gaming per hour)
+        ratios.insert("reading".to_string(), 6);    // 1:6 ratio (10 min gaming per hour)
+        ratios.insert("course".to_string(), 4);     // 1:4 ratio
+        ratios.insert("exercise".to_string(), 3);   // 1:3 ratio (20 min gaming per hour)
+        ratios
+    }
+
+    // How long to spend on an activity to earn the given gaming minutes
+    pub fn minutes_needed_to_play(game_minutes: i32, earn_ratio: i32) -> i32 {
+        game_minutes.max(0).saturating_mul(earn_ratio.max(1))
+    }
+
+    // Gaming minutes earned before the daily cap is applied
+    pub fn earned_minutes_for(duration_minutes: i32, earn_ratio: i32) -> i32 {
+        duration_minutes / earn_ratio.max(1)
+    }
+
+    // `earn_ratio` is the activity type's learning minutes per gaming minute
+    pub fn new(activity_type: String, description: String, duration_minutes: i32, earn_ratio: i32) -> Self {
+        let earned_gaming_minutes = Self::earned_minutes_for(duration_minutes, earn_ratio);
 
         Self {
             id: Some(uuid::Uuid::new_v4().to_string()),
@@ -101,6 +545,282 @@ impl LearningActivity {
             duration_minutes,
             earned_gaming_minutes,
             timestamp: Utc::now(),
+            is_voided: false,
+        }
+    }
+}
+
+// Gaming minutes an activity would earn against what the daily cap let through
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LearningCredit {
+    pub requested_minutes: i32,
+    pub granted_minutes: i32,
+    pub capped: bool, // The cap let through less than was requested
+}
+
+// Learning needed for a gaming goal, and how much of the goal today's earning
+// cap still lets through
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LearningPlan {
+    pub game_minutes: i32,
+    pub learning_minutes: i32,
+    pub earnable_today_minutes: i32, // At most game_minutes
+    pub exceeds_cap: bool, // Only part of the goal can be earned today
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Grant {
+    pub id: Option<String>,
+    pub grant_type: String, // "temporary", "recurring", "achievement", etc.
+    pub minutes: i32,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Grant {
+    pub fn new(grant_type: String, minutes: i32, reason: String, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            id: Some(uuid::Uuid::new_v4().to_string()),
+            grant_type,
+            minutes,
+            reason,
+            created_at: Utc::now(),
+            expires_at,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecurringGrant {
+    pub id: Option<String>,
+    pub weekday: u32, // 0 = Sunday .. 6 = Saturday
+    pub minutes: i32,
+    pub expires_at_time: Option<String>, // Local "HH:MM" the boost ends, or the end of the day
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameGoal {
+    pub id: Option<String>,
+    pub game_name: String,
+    pub goal_type: String, // "min" (play at least) or "max" (keep under)
+    pub target_minutes: i32, // Per week
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameGoalProgress {
+    pub goal: GameGoal,
+    pub minutes_this_week: i32,
+    pub status: String, // "in_progress" / "met" for min goals, "on_track" / "breached" for max goals
+}
+
+impl GameGoalProgress {
+    pub fn new(goal: GameGoal, minutes_this_week: i32) -> Self {
+        let status = match goal.goal_type.as_str() {
+            "min" if minutes_this_week >= goal.target_minutes => "met",
+            "min" => "in_progress",
+            _ if minutes_this_week > goal.target_minutes => "breached",
+            _ => "on_track",
+        };
+
+        Self {
+            goal,
+            minutes_this_week,
+            status: status.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SteamImportSummary {
+    pub games_imported: usize,
+    pub minutes_imported: i64,
+}
+
+// Outcome of restoring exported history. Skipped rows were already present;
+// rejected ones were malformed and are explained in `problems`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryImportSummary {
+    pub sessions_imported: usize,
+    pub learning_imported: usize,
+    pub skipped: usize,
+    pub rejected: usize,
+    pub problems: Vec<String>, // e.g. "sessions[3]: the duration is negative"
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DistinctGameStatus {
+    pub games_today: Vec<String>, // In the order they were first played
+    pub count: usize,
+    pub limit: i32, // 0 means no limit
+    pub over_limit_games: Vec<String>, // Games started after the limit was reached
+}
+
+impl DistinctGameStatus {
+    pub fn new(games_today: Vec<String>, limit: i32) -> Self {
+        let over_limit_games = if limit > 0 {
+            games_today.iter().skip(limit as usize).cloned().collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            count: games_today.len(),
+            games_today,
+            limit,
+            over_limit_games,
+        }
+    }
+}
+
+// Limits each notification type to one showing per cooldown, so a warning
+// that stays true across ticks isn't repeated every second
+#[derive(Debug, Clone)]
+pub struct NotificationCooldown {
+    pub cooldown_seconds: i64,
+    last_shown: HashMap<String, DateTime<Utc>>,
+}
+
+impl NotificationCooldown {
+    pub fn new(cooldown_seconds: i64) -> Self {
+        Self {
+            cooldown_seconds,
+            last_shown: HashMap::new(),
+        }
+    }
+
+    // Records the showing when it's allowed
+    pub fn try_fire(&mut self, notification_type: &str, now: DateTime<Utc>) -> bool {
+        if let Some(last) = self.last_shown.get(notification_type) {
+            if (now - *last).num_seconds() < self.cooldown_seconds {
+                return false;
+            }
+        }
+
+        self.last_shown.insert(notification_type.to_string(), now);
+        true
+    }
+
+    // The condition behind this type went away, so its next warning shows at once
+    pub fn reset(&mut self, notification_type: &str) {
+        self.last_shown.remove(notification_type);
+    }
+
+    pub fn reset_all(&mut self) {
+        self.last_shown.clear();
+    }
+
+    // Lifts the budget warnings whose thresholds the remaining time is back above
+    pub fn reset_cleared_budget_warnings(&mut self, remaining_minutes: i32, warning_threshold_minutes: i32) {
+        if remaining_minutes > 0 {
+            self.reset("exceeded");
+        }
+        if remaining_minutes > warning_threshold_minutes {
+            self.reset("warning");
+            self.reset("critical");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoopHealth {
+    pub started_at: DateTime<Utc>,
+    pub last_tick: Option<DateTime<Utc>>, // Any iteration, even one that skipped work on a busy lock
+    pub last_success: Option<DateTime<Utc>>, // Last iteration that updated and saved without errors
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+    pub consecutive_errors: u32, // Iterations in a row that hit at least one error
+    pub is_stalled: bool, // No successful iteration for longer than expected
+}
+
+impl Default for LoopHealth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoopHealth {
+    pub fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            last_tick: None,
+            last_success: None,
+            last_error: None,
+            last_error_at: None,
+            consecutive_errors: 0,
+            is_stalled: false,
+        }
+    }
+
+    // `completed` is whether the iteration got both locks and did its full work
+    pub fn record_tick(&mut self, now: DateTime<Utc>, completed: bool, errors: Vec<String>) {
+        self.last_tick = Some(now);
+
+        if let Some(error) = errors.into_iter().last() {
+            self.last_error = Some(error);
+            self.last_error_at = Some(now);
+            self.consecutive_errors += 1;
+        } else if completed {
+            self.last_success = Some(now);
+            self.consecutive_errors = 0;
+        }
+    }
+
+    pub fn check_stalled(&mut self, now: DateTime<Utc>, stall_seconds: i64) {
+        let reference = self.last_success.unwrap_or(self.started_at);
+        self.is_stalled = (now - reference).num_seconds() > stall_seconds;
+    }
+}
+
+// Outcome of closing games, by display name
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CloseResult {
+    pub closed: Vec<String>, // Every instance is gone
+    pub resisted: Vec<String>, // Kills were accepted but the game kept running
+    pub failed: Vec<String>, // The OS refused to kill at least one instance
+}
+
+// Snapshot of the app's health, meant to be pasted into a bug report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostics {
+    pub generated_at: DateTime<Utc>,
+    pub app_version: String,
+    pub data_dir: String,
+    pub database_writable: bool,
+    pub database_encrypted: bool,
+    pub schema_version: Option<i32>, // None when it couldn't be read
+    pub supported_schema_version: i32,
+    pub process_enumeration: bool, // More than this app's own process is visible
+    pub visible_processes: usize,
+    pub known_games: usize,
+    pub notifications_available: Option<bool>, // None when the platform can't check without showing one
+    pub monitoring_paused: bool,
+    pub problems: Vec<String>, // Readable summary of every failed check
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionReport {
+    pub process_enumeration: bool, // Running processes can be listed
+    pub process_termination: bool, // Every running game is owned by us (or we're elevated), so it can be closed
+    pub window_inspection: bool, // Window titles can be read for web game detection
+    pub is_elevated: Option<bool>, // None when the platform doesn't say
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigWarning {
+    pub setting: String,
+    pub severity: String, // "warning" for contradictions, "info" for redundant settings
+    pub message: String,
+}
+
+impl ConfigWarning {
+    pub fn new(setting: &str, severity: &str, message: String) -> Self {
+        Self {
+            setting: setting.to_string(),
+            severity: severity.to_string(),
+            message,
         }
     }
 }
@@ -110,6 +830,102 @@ pub struct GameConfig {
     pub process_name: String,
     pub display_name: String,
     pub is_monitored: bool,
+    #[serde(default = "default_notify_on_limit")]
+    pub notify_on_limit: bool, // False keeps tracking the game but skips limit warnings for it
+    #[serde(default)]
+    pub path_pattern: Option<String>, // Substring the executable path must contain; None matches on the name alone
+    #[serde(default)]
+    pub is_launcher: bool, // A store or launcher client: shown as open but never billed
+    #[serde(default)]
+    pub title_pattern: Option<String>, // Case-insensitive regex one of the process's window titles must match
+    #[serde(default)]
+    pub kind: GameMatchKind, // How process_name is compared with running processes
+    #[serde(default)]
+    pub is_exempt: bool, // Sessions are still recorded but never billed against the budget or closed
+}
+
+// Games with versioned or randomized executable names (GTA5_1.67.exe, Unreal
+// shipping binaries) are configured with a regex instead of an exact name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMatchKind {
+    #[default]
+    Exact, // The normalized process name equals process_name
+    Regex, // process_name is a case-insensitive regex searched in the process name
+}
+
+impl GameMatchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameMatchKind::Exact => "exact",
+            GameMatchKind::Regex => "regex",
+        }
+    }
+}
+
+impl FromStr for GameMatchKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "exact" => Ok(GameMatchKind::Exact),
+            "regex" => Ok(GameMatchKind::Regex),
+            _ => Err(format!("Unknown game match kind: {}", value)),
+        }
+    }
+}
+
+fn default_notify_on_limit() -> bool {
+    true
+}
+
+// Hours when gaming is blocked outright. A window whose start is after its
+// end runs past midnight into the next day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurfewWindow {
+    pub weekday: u32, // 0 = Sunday .. 6 = Saturday, the day the window starts
+    pub start_hm: String, // Local "HH:MM"
+    pub end_hm: String, // Local "HH:MM"
+}
+
+impl CurfewWindow {
+    // Whether the given local weekday and time fall inside the window
+    pub fn contains(&self, weekday: u32, time: NaiveTime) -> bool {
+        let (start, end) = match (NaiveTime::parse_from_str(&self.start_hm, "%H:%M"),
+                                  NaiveTime::parse_from_str(&self.end_hm, "%H:%M")) {
+            (Ok(start), Ok(end)) => (start, end),
+            _ => return false,
+        };
+
+        if start < end {
+            weekday == self.weekday && time >= start && time < end
+        } else {
+            // Wraps: the evening of its own day plus the morning after
+            (weekday == self.weekday && time >= start)
+                || (weekday == (self.weekday + 1) % 7 && time < end)
+        }
+    }
+}
+
+// A game's own daily cap, enforced on top of the overall budget
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameLimit {
+    pub process_name: String,
+    pub daily_limit_minutes: i32,
+}
+
+// A process never tracked or closed, even when it's also a known game
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlacklistEntry {
+    pub process_name: String, // Normalized: lowercase, without ".exe"
+    pub is_default: bool, // Built in, so it can't be removed
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingGameName {
+    pub process_name: String,
+    pub provisional_name: String,
+    pub session_count: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -118,4 +934,144 @@ pub struct AppSettings {
     pub rollover_days: i32,
     pub notifications_enabled: bool,
     pub warning_threshold_minutes: i32,
-}
\ No newline at end of file
+    pub web_game_detection_enabled: bool,
+    pub cpu_idle_threshold: f32, // Percent CPU below which a game counts as idle (0 disables)
+    pub cpu_idle_seconds: i64,
+    pub nag_interval_minutes: i32, // Re-show the over-budget warning this often (0 disables)
+    pub quiet_hours: String, // Local "HH:MM-HH:MM" when over-budget reminders stay silent; empty disables
+    pub max_debt_minutes: i32, // Cap on time borrowed against future days
+    pub log_format: String, // "text" or "json"
+    pub detection_mode: String, // "auto" (known games plus Steam and web heuristics) or "whitelist" (known games only)
+    pub confirm_steam_names: bool, // Hold guessed Steam names as unconfirmed until the user reviews them
+    pub personal_best_notifications: bool, // Celebrate broken personal records at rollover
+    pub countdown_minutes: i32, // Show a live countdown overlay in the final minutes (0 disables)
+    pub trust_startup_detections: bool, // Detect games on the first monitor cycle instead of warming up
+    pub full_scan_interval_seconds: i64, // How often every process is refreshed; in between only known candidates are
+    pub max_distinct_games_per_day: i32, // Different games allowed per day before new ones are over the limit (0 disables)
+    pub monitoring_paused: bool, // Survives restarts so a paused monitor stays paused
+    pub enforcement_enabled: bool, // Close games automatically once the budget runs out
+    pub kill_grace_seconds: i64, // Warning period between the budget running out and games closing
+    pub idle_threshold_minutes: i32, // No keyboard or mouse input for this long pauses sessions (0 disables)
+    pub max_earned_minutes_per_day: i32, // Most gaming minutes learning can earn in a day (0 disables)
+    pub notification_cooldown_seconds: i64, // Shortest gap between two notifications of the same type
+    pub warning_thresholds: Vec<i32>, // Remaining minutes that each trigger a warning, highest first
+    pub social_session_discount_percent: i32, // How much cheaper social play is against the budget (0 bills it in full)
+    pub allow_time_requests: bool, // Offer a "request more time" button on the warning overlay
+    pub encryption_enabled: bool, // Encrypt the database file; applied at the next start
+    pub session_merge_seconds: i64, // A game that exits and restarts within this long keeps its session (0 disables)
+    pub monitor_interval_secs: i64, // Seconds between process checks (1-30); higher saves battery
+    pub count_foreground_only: bool, // Only bill games while they own the foreground window (Windows only)
+    pub day_reset_hour: u32, // Local hour (0-12) the budget day starts at, so late-night play isn't split at midnight
+    pub notification_style: String, // "overlay", "tray", "notification_only" or "silent"; only "overlay" opens windows that can take focus
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minutes_needed_to_play_saturates_instead_of_overflowing() {
+        assert_eq!(LearningActivity::minutes_needed_to_play(30, 4), 120);
+        assert_eq!(LearningActivity::minutes_needed_to_play(i32::MAX, 6), i32::MAX);
+        assert_eq!(LearningActivity::minutes_needed_to_play(-5, 6), 0);
+    }
+
+    fn budget_with_every_source() -> BudgetStatus {
+        let mut budget = BudgetStatus::new(60);
+        budget.rollover_minutes = 20;
+        budget.bonus_minutes = 10;
+        budget.recurring_bonus_minutes = 5;
+        budget.earned_minutes = 30;
+        budget
+    }
+
+    fn remaining(budget: &BudgetStatus) -> (i32, i32, i32, i32) {
+        (budget.remaining_base, budget.remaining_rollover, budget.remaining_bonus, budget.remaining_earned)
+    }
+
+    #[test]
+    fn usage_is_taken_from_base_then_rollover_then_bonus_then_earned() {
+        let mut budget = budget_with_every_source();
+
+        budget.update_usage(50);
+        assert_eq!(remaining(&budget), (10, 20, 15, 30));
+
+        budget.update_usage(70);
+        assert_eq!(remaining(&budget), (0, 10, 15, 30));
+
+        budget.update_usage(90);
+        assert_eq!(remaining(&budget), (0, 0, 5, 30));
+
+        budget.update_usage(100);
+        assert_eq!(remaining(&budget), (0, 0, 0, 25));
+        assert_eq!(budget.remaining_today_minutes, 25);
+    }
+
+    #[test]
+    fn debt_repayment_is_deducted_before_play() {
+        let mut budget = budget_with_every_source();
+        budget.debt_repayment_minutes = 70;
+
+        budget.update_usage(20);
+        assert_eq!(remaining(&budget), (0, 0, 5, 30));
+        assert_eq!(budget.total_available_minutes, 55);
+        assert_eq!(budget.remaining_today_minutes, 35);
+    }
+
+    #[test]
+    fn overspending_empties_every_source() {
+        let mut budget = budget_with_every_source();
+
+        budget.update_usage(200);
+        assert_eq!(remaining(&budget), (0, 0, 0, 0));
+        assert_eq!(budget.remaining_today_minutes, 0);
+    }
+
+    fn session_between(start: DateTime<Utc>, end: Option<DateTime<Utc>>) -> GameSession {
+        let mut session = GameSession::new("Game".to_string(), "game.exe".to_string());
+        session.start_time = start;
+        session.end_time = end;
+        session
+    }
+
+    #[test]
+    fn sessions_that_only_touch_do_not_overlap() {
+        let noon = Utc::now() - chrono::Duration::hours(6);
+        let hour = chrono::Duration::hours(1);
+        let session = session_between(noon, Some(noon + hour));
+
+        assert!(!session.overlaps(noon - hour, noon));
+        assert!(!session.overlaps(noon + hour, noon + hour * 2));
+    }
+
+    #[test]
+    fn nested_and_partial_intervals_overlap() {
+        let noon = Utc::now() - chrono::Duration::hours(6);
+        let hour = chrono::Duration::hours(1);
+        let session = session_between(noon, Some(noon + hour * 2));
+
+        assert!(session.overlaps(noon + hour / 2, noon + hour));
+        assert!(session.overlaps(noon - hour, noon + hour * 3));
+        assert!(session.overlaps(noon - hour, noon + hour / 2));
+        assert!(session.overlaps(noon + hour, noon + hour * 3));
+    }
+
+    #[test]
+    fn disjoint_intervals_do_not_overlap() {
+        let noon = Utc::now() - chrono::Duration::hours(6);
+        let hour = chrono::Duration::hours(1);
+        let session = session_between(noon, Some(noon + hour));
+
+        assert!(!session.overlaps(noon - hour * 3, noon - hour));
+        assert!(!session.overlaps(noon + hour * 2, noon + hour * 3));
+    }
+
+    #[test]
+    fn an_open_session_runs_until_now() {
+        let noon = Utc::now() - chrono::Duration::hours(6);
+        let session = session_between(noon, None);
+
+        assert!(session.overlaps(Utc::now() - chrono::Duration::minutes(1), Utc::now() + chrono::Duration::hours(1)));
+        assert!(!session.overlaps(Utc::now() + chrono::Duration::minutes(1), Utc::now() + chrono::Duration::hours(1)));
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+// Returns the titles of visible top-level windows grouped by owning process id.
+// Only implemented on Windows; other platforms return an empty map so callers
+// simply skip title-based detection.
+#[cfg(target_os = "windows")]
+pub fn window_titles_by_pid() -> HashMap<u32, Vec<String>> {
+    use std::ffi::c_void;
+
+    type Hwnd = *mut c_void;
+    type Lparam = isize;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn EnumWindows(callback: extern "system" fn(Hwnd, Lparam) -> i32, lparam: Lparam) -> i32;
+        fn IsWindowVisible(hwnd: Hwnd) -> i32;
+        fn GetWindowTextW(hwnd: Hwnd, buffer: *mut u16, max_count: i32) -> i32;
+        fn GetWindowThreadProcessId(hwnd: Hwnd, process_id: *mut u32) -> u32;
+    }
+
+    extern "system" fn collect(hwnd: Hwnd, lparam: Lparam) -> i32 {
+        let titles = unsafe { &mut *(lparam as *mut HashMap<u32, Vec<String>>) };
+
+        unsafe {
+            if IsWindowVisible(hwnd) == 0 {
+                return 1;
+            }
+
+            let mut buffer = [0u16; 512];
+            let len = GetWindowTextW(hwnd, buffer.as_mut_ptr(), buffer.len() as i32);
+            if len <= 0 {
+                return 1;
+            }
+
+            let mut pid = 0u32;
+            GetWindowThreadProcessId(hwnd, &mut pid);
+
+            let title = String::from_utf16_lossy(&buffer[..len as usize]);
+            titles.entry(pid).or_default().push(title);
+        }
+
+        1 // Continue enumeration
+    }
+
+    let mut titles: HashMap<u32, Vec<String>> = HashMap::new();
+    unsafe {
+        EnumWindows(collect, &mut titles as *mut _ as Lparam);
+    }
+    titles
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn window_titles_by_pid() -> HashMap<u32, Vec<String>> {
+    HashMap::new()
+}
+
+// Whether the app runs with administrator/root rights. None when it can't be determined.
+#[cfg(target_os = "windows")]
+pub fn is_elevated() -> Option<bool> {
+    use std::ffi::c_void;
+
+    type Handle = *mut c_void;
+
+    const TOKEN_QUERY: u32 = 0x0008;
+    const TOKEN_ELEVATION: i32 = 20; // TOKEN_INFORMATION_CLASS::TokenElevation
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetCurrentProcess() -> Handle;
+        fn CloseHandle(handle: Handle) -> i32;
+    }
+
+    #[link(name = "advapi32")]
+    extern "system" {
+        fn OpenProcessToken(process: Handle, access: u32, token: *mut Handle) -> i32;
+        fn GetTokenInformation(token: Handle, class: i32, info: *mut c_void, length: u32, return_length: *mut u32) -> i32;
+    }
+
+    unsafe {
+        let mut token: Handle = std::ptr::null_mut();
+        if OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) == 0 {
+            return None;
+        }
+
+        let mut elevation = 0u32;
+        let mut returned = 0u32;
+        let ok = GetTokenInformation(
+            token,
+            TOKEN_ELEVATION,
+            &mut elevation as *mut u32 as *mut c_void,
+            std::mem::size_of::<u32>() as u32,
+            &mut returned,
+        );
+        CloseHandle(token);
+
+        if ok == 0 { None } else { Some(elevation != 0) }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn is_elevated() -> Option<bool> {
+    // Effective uid is the second field of the "Uid:" line
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let uid_line = status.lines().find(|line| line.starts_with("Uid:"))?;
+    let effective_uid: u32 = uid_line.split_whitespace().nth(2)?.parse().ok()?;
+    Some(effective_uid == 0)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn is_elevated() -> Option<bool> {
+    None
+}
+
+// Yama ptrace restriction level (0 = classic, 1+ = restricted). Linux only.
+#[cfg(target_os = "linux")]
+pub fn ptrace_scope() -> Option<u32> {
+    std::fs::read_to_string("/proc/sys/kernel/yama/ptrace_scope").ok()?
+        .trim().parse().ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn ptrace_scope() -> Option<u32> {
+    None
+}
+
+// Whether the workstation is locked. Windows refuses to open the input desktop
+// while the lock screen is showing; other platforms always report unlocked.
+#[cfg(target_os = "windows")]
+pub fn is_session_locked() -> bool {
+    use std::ffi::c_void;
+
+    type Hdesk = *mut c_void;
+
+    const DESKTOP_SWITCHDESKTOP: u32 = 0x0100;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn OpenInputDesktop(flags: u32, inherit: i32, access: u32) -> Hdesk;
+        fn CloseDesktop(desktop: Hdesk) -> i32;
+    }
+
+    unsafe {
+        let desktop = OpenInputDesktop(0, 0, DESKTOP_SWITCHDESKTOP);
+        if desktop.is_null() {
+            return true;
+        }
+        CloseDesktop(desktop);
+        false
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn is_session_locked() -> bool {
+    false
+}
+
+// Process owning the window the user is looking at. None where the platform
+// gives no way to ask, which callers treat as every game being in front.
+#[cfg(target_os = "windows")]
+pub fn foreground_pid() -> Option<u32> {
+    use std::ffi::c_void;
+
+    type Hwnd = *mut c_void;
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetForegroundWindow() -> Hwnd;
+        fn GetWindowThreadProcessId(hwnd: Hwnd, process_id: *mut u32) -> u32;
+    }
+
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.is_null() {
+            return None;
+        }
+
+        let mut pid = 0u32;
+        GetWindowThreadProcessId(hwnd, &mut pid);
+        if pid == 0 { None } else { Some(pid) }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn foreground_pid() -> Option<u32> {
+    None
+}
+
+// Seconds since the last keyboard or mouse input anywhere in the session, or
+// None where the platform gives no way to ask.
+#[cfg(target_os = "windows")]
+pub fn seconds_since_last_input() -> Option<i64> {
+    #[repr(C)]
+    struct LastInputInfo {
+        cb_size: u32,
+        dw_time: u32,
+    }
+
+    #[link(name = "user32")]
+    extern "system" {
+        fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetTickCount() -> u32;
+    }
+
+    unsafe {
+        let mut info = LastInputInfo {
+            cb_size: std::mem::size_of::<LastInputInfo>() as u32,
+            dw_time: 0,
+        };
+        if GetLastInputInfo(&mut info) == 0 {
+            return None;
+        }
+        // Both are 32-bit millisecond tick counts, so wrapping_sub survives the 49-day rollover
+        Some((GetTickCount().wrapping_sub(info.dw_time) / 1000) as i64)
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn seconds_since_last_input() -> Option<i64> {
+    None
+}
+
+// Forcefully terminates a process and every child it spawned. Returns false when
+// it couldn't, including on platforms without a tree kill.
+#[cfg(target_os = "windows")]
+pub fn terminate_process_tree(pid: u32) -> bool {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+    std::process::Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &pid.to_string()])
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn terminate_process_tree(_pid: u32) -> bool {
+    false
+}
+
+// Whether a notification could be shown, checked without showing one. Only
+// Linux and the BSDs can ask the notification server; elsewhere it's None.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn notifications_available() -> Option<bool> {
+    Some(notify_rust::get_server_information().is_ok())
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn notifications_available() -> Option<bool> {
+    None
+}
+
+// Shows a folder in the system file manager
+pub fn open_folder(path: &std::path::Path) -> Result<(), String> {
+    #[cfg(target_os = "windows")]
+    let program = "explorer";
+    #[cfg(target_os = "macos")]
+    let program = "open";
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let program = "xdg-open";
+
+    std::process::Command::new(program)
+        .arg(path)
+        .spawn()
+        .map(|_| ())
+        .map_err(|e| format!("Failed to open {:?}: {}", path, e))
+}
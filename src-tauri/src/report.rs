@@ -0,0 +1,203 @@
+// Renders the weekly report as one self-contained HTML page (inline CSS, no
+// scripts or external assets) so it can be saved, mailed or printed as is.
+
+use chrono::Local;
+
+use crate::models::{EnforcementReason, WeeklyReport};
+
+// Escapes text for use inside HTML elements and attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// "1h 05m", or "45m" under an hour
+fn format_minutes(minutes: i32) -> String {
+    if minutes >= 60 {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+fn reason_label(reason: EnforcementReason) -> &'static str {
+    match reason {
+        EnforcementReason::BudgetExceeded => "Budget used up",
+        EnforcementReason::Curfew => "Curfew",
+        EnforcementReason::GameLimit => "Game limit reached",
+    }
+}
+
+pub fn weekly_report_html(report: &WeeklyReport) -> String {
+    let first = report.days.first().map(|day| day.date.format("%b %-d").to_string()).unwrap_or_default();
+    let last = report.days.last().map(|day| day.date.format("%b %-d, %Y").to_string()).unwrap_or_default();
+    let title = if report.profile_name.is_empty() {
+        "Weekly Gaming Report".to_string()
+    } else {
+        format!("Weekly Gaming Report for {}", report.profile_name)
+    };
+
+    let has_data = report.days.iter().any(|day| day.used_minutes > 0 || day.earned_minutes > 0)
+        || !report.enforcement_events.is_empty();
+
+    let body = if has_data {
+        [
+            usage_section(report),
+            games_section(report),
+            summary_section(report),
+            enforcement_section(report),
+        ].concat()
+    } else {
+        r#"<p class="empty">No gaming or learning has been recorded this week yet. Check back after a few days of use.</p>"#.to_string()
+    };
+
+    format!(r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="UTF-8">
+    <title>{title}</title>
+    <style>
+        body {{
+            font-family: Arial, sans-serif;
+            color: #1a202c;
+            margin: 0 auto;
+            padding: 30px;
+            max-width: 760px;
+        }}
+
+        h1 {{ font-size: 26px; margin-bottom: 4px; }}
+        h2 {{ font-size: 18px; margin-top: 30px; border-bottom: 2px solid #e2e8f0; padding-bottom: 4px; }}
+        .subtitle {{ color: #718096; margin-top: 0; }}
+        .empty {{ color: #718096; font-style: italic; margin-top: 30px; }}
+
+        .day {{ display: flex; align-items: center; margin: 6px 0; }}
+        .day-label {{ width: 90px; font-size: 14px; }}
+        .bar-track {{ flex: 1; background: #edf2f7; border-radius: 4px; height: 18px; position: relative; }}
+        .bar {{ height: 100%; border-radius: 4px; background: #48bb78; }}
+        .bar.over {{ background: #f56565; }}
+        .bar.today {{ background: #4299e1; }}
+        .allowance {{ position: absolute; top: -3px; bottom: -3px; border-left: 2px dashed #2d3748; }}
+        .day-value {{ width: 150px; text-align: right; font-size: 13px; color: #4a5568; }}
+
+        table {{ width: 100%; border-collapse: collapse; font-size: 14px; }}
+        th, td {{ text-align: left; padding: 6px 8px; border-bottom: 1px solid #e2e8f0; }}
+        th {{ color: #4a5568; }}
+        td.number {{ text-align: right; }}
+
+        .stats {{ display: flex; gap: 16px; }}
+        .stat {{ flex: 1; background: #f7fafc; border-radius: 8px; padding: 12px; text-align: center; }}
+        .stat-value {{ font-size: 24px; font-weight: bold; }}
+        .stat-label {{ font-size: 13px; color: #718096; }}
+
+        .footer {{ margin-top: 40px; font-size: 12px; color: #a0aec0; }}
+
+        @media print {{
+            body {{ padding: 0; }}
+            .bar, .bar-track {{ -webkit-print-color-adjust: exact; print-color-adjust: exact; }}
+        }}
+    </style>
+</head>
+<body>
+    <h1>{title}</h1>
+    <p class="subtitle">{first} – {last}</p>
+    {body}
+    <div class="footer">Generated by Gaming Time Tracker on {generated}</div>
+</body>
+</html>
+"#,
+        title = escape(&title),
+        first = escape(&first),
+        last = escape(&last),
+        body = body,
+        generated = report.generated_at.with_timezone(&Local).format("%b %-d, %Y at %H:%M"),
+    )
+}
+
+// One bar per day, scaled to the busiest day or the largest allowance, with
+// the day's allowance marked on it
+fn usage_section(report: &WeeklyReport) -> String {
+    let scale = report.days.iter()
+        .map(|day| day.used_minutes.max(day.allowance_minutes + day.earned_minutes))
+        .max()
+        .unwrap_or(0)
+        .max(1) as f64;
+
+    let mut rows = String::new();
+    for day in &report.days {
+        let class = match day.within_budget {
+            Some(false) => "bar over",
+            Some(true) => "bar",
+            None => "bar today",
+        };
+        let allowance = day.allowance_minutes + day.earned_minutes;
+        rows.push_str(&format!(
+            r#"<div class="day"><div class="day-label">{label}</div><div class="bar-track"><div class="{class}" style="width: {width:.1}%"></div><div class="allowance" style="left: {marker:.1}%"></div></div><div class="day-value">{used} of {allowance}</div></div>"#,
+            label = day.date.format("%a %b %-d"),
+            class = class,
+            width = day.used_minutes as f64 / scale * 100.0,
+            marker = allowance as f64 / scale * 100.0,
+            used = format_minutes(day.used_minutes),
+            allowance = format_minutes(allowance),
+        ));
+    }
+
+    format!("<h2>Daily Play</h2>{}", rows)
+}
+
+fn games_section(report: &WeeklyReport) -> String {
+    if report.top_games.is_empty() {
+        return "<h2>Top Games</h2><p>No games were played this week.</p>".to_string();
+    }
+
+    let mut rows = String::new();
+    for game in report.top_games.iter().take(10) {
+        rows.push_str(&format!(
+            r#"<tr><td>{}</td><td class="number">{}</td><td class="number">{}</td></tr>"#,
+            escape(&game.game_name), format_minutes(game.minutes), game.session_count,
+        ));
+    }
+
+    format!(r#"<h2>Top Games</h2><table><tr><th>Game</th><th class="number">Time</th><th class="number">Sessions</th></tr>{}</table>"#, rows)
+}
+
+fn summary_section(report: &WeeklyReport) -> String {
+    let played: i32 = report.days.iter().map(|day| day.used_minutes).sum();
+    let adherence = if report.finished_days > 0 {
+        format!("{} of {}", report.days_within_budget, report.finished_days)
+    } else {
+        "–".to_string()
+    };
+
+    format!(
+        r#"<h2>Summary</h2><div class="stats"><div class="stat"><div class="stat-value">{}</div><div class="stat-label">Played</div></div><div class="stat"><div class="stat-value">{}</div><div class="stat-label">Earned by learning</div></div><div class="stat"><div class="stat-value">{}</div><div class="stat-label">Days within budget</div></div></div>"#,
+        format_minutes(played), format_minutes(report.earned_minutes), adherence,
+    )
+}
+
+fn enforcement_section(report: &WeeklyReport) -> String {
+    if report.enforcement_events.is_empty() {
+        return "<h2>Games Closed</h2><p>No games had to be closed this week.</p>".to_string();
+    }
+
+    let mut rows = String::new();
+    for entry in &report.enforcement_events {
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            entry.timestamp.with_timezone(&Local).format("%a %b %-d, %H:%M"),
+            escape(&entry.game_name),
+            reason_label(entry.reason),
+        ));
+    }
+
+    format!("<h2>Games Closed</h2><table><tr><th>When</th><th>Game</th><th>Why</th></tr>{}</table>", rows)
+}
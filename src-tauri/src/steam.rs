@@ -0,0 +1,83 @@
+use std::time::Duration;
+use chrono::{DateTime, TimeZone, Utc};
+
+const STEAM_API_HOST: &str = "api.steampowered.com";
+
+// Name the API key is kept under in the OS keyring
+pub const API_KEY_SECRET: &str = "steam-api-key";
+
+#[derive(Debug, Clone)]
+pub struct OwnedGame {
+    pub appid: u64,
+    pub name: String,
+    pub playtime_minutes: i64,
+    pub last_played: Option<DateTime<Utc>>,
+}
+
+pub fn validate_credentials(api_key: &str, steam_id: &str) -> Result<(), String> {
+    if api_key.len() != 32 || !api_key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Steam API key should be 32 hexadecimal characters".to_string());
+    }
+    if steam_id.len() != 17 || !steam_id.chars().all(|c| c.is_ascii_digit()) {
+        return Err("Steam ID should be the 17-digit SteamID64".to_string());
+    }
+    Ok(())
+}
+
+// Fetches lifetime playtime for every game the account owns via
+// IPlayerService/GetOwnedGames. The profile's game details must be public.
+pub fn fetch_owned_games(api_key: &str, steam_id: &str) -> Result<Vec<OwnedGame>, String> {
+    validate_credentials(api_key, steam_id)?;
+
+    let path = format!(
+        "/IPlayerService/GetOwnedGames/v0001/?key={}&steamid={}&include_appinfo=1&format=json",
+        urlencoding::encode(api_key),
+        urlencoding::encode(steam_id)
+    );
+
+    let body = http_get(STEAM_API_HOST, &path)?;
+    parse_owned_games(&body)
+}
+
+fn parse_owned_games(body: &str) -> Result<Vec<OwnedGame>, String> {
+    let json: serde_json::Value = serde_json::from_str(body)
+        .map_err(|e| format!("Invalid response from Steam: {}", e))?;
+
+    // A private profile returns an empty response object rather than an error
+    let games = match json["response"]["games"].as_array() {
+        Some(games) => games,
+        None => return Ok(Vec::new()),
+    };
+
+    Ok(games.iter()
+        .filter_map(|game| {
+            let appid = game["appid"].as_u64()?;
+            Some(OwnedGame {
+                appid,
+                name: game["name"].as_str()
+                    .map(|name| name.to_string())
+                    .unwrap_or_else(|| format!("Steam App {}", appid)),
+                playtime_minutes: game["playtime_forever"].as_i64().unwrap_or(0),
+                last_played: game["rtime_last_played"].as_i64()
+                    .filter(|&timestamp| timestamp > 0)
+                    .and_then(|timestamp| Utc.timestamp_opt(timestamp, 0).single()),
+            })
+        })
+        .collect())
+}
+
+// The API key travels in the query string, so Steam is only ever reached over
+// HTTPS. Errors name the host rather than the URL to keep the key out of logs.
+fn http_get(host: &str, path: &str) -> Result<String, String> {
+    let response = ureq::get(&format!("https://{}{}", host, path))
+        .set("Accept", "application/json")
+        .timeout(Duration::from_secs(15))
+        .call();
+
+    match response {
+        Ok(response) => response.into_string().map_err(|e| format!("Failed to read response: {}", e)),
+        Err(ureq::Error::Status(401 | 403, _)) => Err("Steam rejected the API key".to_string()),
+        Err(ureq::Error::Status(code, response)) => Err(format!("Steam request failed: {} {}", code, response.status_text())),
+        Err(e) => Err(format!("Failed to connect to {}: {}", host, e.kind())),
+    }
+}
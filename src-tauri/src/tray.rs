@@ -0,0 +1,131 @@
+// The system tray icon. Its tooltip shows the time left today, and with the
+// "tray" notification style warnings go there too, since changing a tooltip
+// never takes focus away from a fullscreen game. The menu pauses, opens or
+// quits the app.
+
+use log::{error, info};
+use tauri::{AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu, SystemTrayMenuItem};
+
+use crate::{lock_db, lock_monitor, require_parent_pin, AppState};
+
+const TOGGLE_PAUSE_ID: &str = "toggle_pause";
+const OPEN_ID: &str = "open";
+const QUIT_ID: &str = "quit";
+
+// Windows cuts tray tooltips off at 128 characters
+const TOOLTIP_MAX_CHARS: usize = 120;
+
+fn pause_label(paused: bool) -> &'static str {
+    if paused { "Resume monitoring" } else { "Pause monitoring" }
+}
+
+pub fn build(paused: bool) -> SystemTray {
+    let menu = SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new(TOGGLE_PAUSE_ID, pause_label(paused)))
+        .add_item(CustomMenuItem::new(OPEN_ID, "Open Gaming Time Tracker"))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new(QUIT_ID, "Quit"));
+
+    SystemTray::new().with_menu(menu).with_tooltip("Gaming Time Tracker")
+}
+
+pub fn handle_event(app_handle: &AppHandle, event: SystemTrayEvent) {
+    match event {
+        SystemTrayEvent::MenuItemClick { id, .. } => match id.as_str() {
+            TOGGLE_PAUSE_ID => toggle_pause(app_handle),
+            OPEN_ID => show_main_window(app_handle),
+            QUIT_ID => quit(app_handle),
+            _ => {}
+        },
+        SystemTrayEvent::DoubleClick { .. } => show_main_window(app_handle),
+        _ => {}
+    }
+}
+
+fn set_tooltip(app_handle: &AppHandle, text: &str) {
+    let tooltip: String = text.chars().take(TOOLTIP_MAX_CHARS).collect();
+    if let Err(e) = app_handle.tray_handle().set_tooltip(&tooltip) {
+        error!("Failed to update the tray tooltip: {}", e);
+    }
+}
+
+pub fn show_remaining(app_handle: &AppHandle, remaining_minutes: i32) {
+    let text = if remaining_minutes > 0 {
+        format!("Gaming Time Tracker: {} min left today", remaining_minutes)
+    } else {
+        "Gaming Time Tracker: no gaming time left today".to_string()
+    };
+    set_tooltip(app_handle, &text);
+}
+
+pub fn show_warning(app_handle: &AppHandle, title: &str, message: &str) {
+    set_tooltip(app_handle, &format!("{}: {}", title, message));
+}
+
+pub fn show_paused(app_handle: &AppHandle, paused: bool) {
+    if let Err(e) = app_handle.tray_handle().get_item(TOGGLE_PAUSE_ID).set_title(pause_label(paused)) {
+        error!("Failed to update the tray menu: {}", e);
+    }
+}
+
+fn show_main_window(app_handle: &AppHandle) {
+    if let Some(window) = app_handle.get_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+// Resuming is always allowed, like resume_monitoring. Pausing needs the parent
+// PIN when one is set, and only the main window can ask for it.
+fn toggle_pause(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let db = match lock_db(&state) {
+        Ok(db) => db,
+        Err(e) => return error!("Failed to toggle monitoring from the tray: {}", e),
+    };
+    let mut monitor = lock_monitor(&state);
+
+    let pause = !monitor.is_paused();
+    if pause && require_parent_pin(&db, None).is_err() {
+        drop(monitor);
+        drop(db);
+        show_main_window(app_handle);
+        if let Err(e) = app_handle.emit_all("tray-pause-requested", ()) {
+            error!("Failed to emit tray-pause-requested: {}", e);
+        }
+        return;
+    }
+
+    if let Err(e) = db.update_setting("monitoring_paused", if pause { "true" } else { "false" }) {
+        return error!("Failed to save the paused state: {}", e);
+    }
+    if pause {
+        monitor.pause();
+    } else {
+        monitor.resume();
+    }
+    info!("Monitoring {} from the tray", if pause { "paused" } else { "resumed" });
+    show_paused(app_handle, pause);
+}
+
+// Quitting stops tracking, so like pausing it needs the parent PIN when one
+// is set, which only the main window can ask for
+fn quit(app_handle: &AppHandle) {
+    let state = app_handle.state::<AppState>();
+    let pin_required = match lock_db(&state) {
+        Ok(db) => require_parent_pin(&db, None).is_err(),
+        Err(e) => return error!("Failed to quit from the tray: {}", e),
+    };
+
+    if pin_required {
+        show_main_window(app_handle);
+        if let Err(e) = app_handle.emit_all("tray-quit-requested", ()) {
+            error!("Failed to emit tray-quit-requested: {}", e);
+        }
+        return;
+    }
+
+    info!("Quitting from the tray");
+    app_handle.exit(0);
+}
@@ -1,5 +1,6 @@
 import React, { useState, useEffect, useRef } from 'react';
 import { invoke } from '@tauri-apps/api/tauri';
+import { listen } from '@tauri-apps/api/event';
 import { sendNotification, isPermissionGranted, requestPermission } from '@tauri-apps/api/notification';
 import { WebviewWindow, appWindow } from '@tauri-apps/api/window';
 import {
@@ -39,6 +40,10 @@ interface BudgetStatus {
   rollover_minutes: number;
   earned_minutes: number;
   total_available_minutes: number;
+  remaining_base: number;
+  remaining_rollover: number;
+  remaining_bonus: number;
+  remaining_earned: number;
 }
 
 interface LearningActivity {
@@ -50,6 +55,27 @@ interface LearningActivity {
   timestamp: string;
 }
 
+interface CloseResult {
+  closed: string[];
+  resisted: string[];
+  failed: string[];
+}
+
+interface EnforcementEntry {
+  id: string;
+  timestamp: string;
+  game_name: string;
+  reason: 'budget_exceeded' | 'curfew' | 'game_limit';
+}
+
+interface Profile {
+  id: string;
+  name: string;
+  daily_allowance_minutes: number;
+  created_at: string;
+  is_active: boolean;
+}
+
 type TabType = 'dashboard' | 'learning' | 'history' | 'settings';
 
 function App() {
@@ -57,6 +83,7 @@ function App() {
   const [totalActiveTime, setTotalActiveTime] = useState<number>(0);
   const [budgetStatus, setBudgetStatus] = useState<BudgetStatus | null>(null);
   const [recentSessions, setRecentSessions] = useState<GameSession[]>([]);
+  const [parentPin, setParentPin] = useState<string>('');
   const [isMonitoring, setIsMonitoring] = useState(true);
   const [activeTab, setActiveTab] = useState<TabType>('dashboard');
 
@@ -175,8 +202,11 @@ function App() {
   const checkWarnings = async (budget: BudgetStatus) => {
     const remaining = budget.remaining_today_minutes;
 
+    // Games can opt out of limit warnings; they're still tracked and closed
+    const notify = await invoke<boolean>('should_notify_limit').catch(() => true);
+
     // 5-minute warning
-    if (remaining <= 5 && remaining > 1 && !fiveMinuteWarningShown.current) {
+    if (notify && remaining <= 5 && remaining > 1 && !fiveMinuteWarningShown.current) {
       fiveMinuteWarningShown.current = true;
 
       const notificationData = {
@@ -202,7 +232,7 @@ function App() {
     }
 
     // 1-minute warning
-    if (remaining <= 1 && remaining > 0 && !oneMinuteWarningShown.current) {
+    if (notify && remaining <= 1 && remaining > 0 && !oneMinuteWarningShown.current) {
       oneMinuteWarningShown.current = true;
 
       const notificationData = {
@@ -238,26 +268,41 @@ function App() {
         remainingMinutes: 0
       };
 
-      // Try system notification first
-      try {
-        await invoke('show_system_notification', {
-          title: notificationData.title,
-          message: notificationData.message,
-          urgency: notificationData.type
-        });
-      } catch (error) {
-        console.error('Failed to send system notification:', error);
-      }
+      if (notify) {
+        // Try system notification first
+        try {
+          await invoke('show_system_notification', {
+            title: notificationData.title,
+            message: notificationData.message,
+            urgency: notificationData.type
+          });
+        } catch (error) {
+          console.error('Failed to send system notification:', error);
+        }
 
-      // Show game overlay
-      await showGameDisruptiveOverlay(notificationData);
+        // Show game overlay
+        await showGameDisruptiveOverlay(notificationData);
+      }
 
       // Automatically close games after a shorter 5-second delay
       console.log('Budget exceeded - closing games in 5 seconds...');
       setTimeout(async () => {
         try {
           console.log('Time expired - attempting to close all games now...');
-          const closedGames = await invoke<string[]>('close_all_games');
+          const result = await invoke<CloseResult>('close_all_games');
+          const closedGames = result.closed;
+          const stillRunning = [...result.resisted, ...result.failed];
+          if (stillRunning.length > 0) {
+            try {
+              await invoke('show_system_notification', {
+                title: "❌ Some Games Are Still Running",
+                message: `Could not close: ${stillRunning.join(', ')}. Please close them manually.`,
+                urgency: 'error'
+              });
+            } catch (error) {
+              console.error('Failed to send still-running notification:', error);
+            }
+          }
           if (closedGames.length > 0) {
             console.log(`Automatically closed games: ${closedGames.join(', ')}`);
             // Show a success notification
@@ -332,11 +377,27 @@ function App() {
     }
   }, [budgetStatus, activeSessions]);
 
-  // Initial data fetch and periodic updates
+  // Initial data fetch, then live updates pushed by the monitoring loop
   useEffect(() => {
     fetchData();
-    const interval = setInterval(fetchData, 1000); // Update every second
-    return () => clearInterval(interval);
+
+    const unlisten = Promise.all([
+      listen<BudgetStatus>('budget-update', (event) => setBudgetStatus(event.payload)),
+      listen<number>('active-time-update', (event) => setTotalActiveTime(event.payload)),
+      listen<GameSession>('session-started', () => fetchData()),
+      listen<GameSession>('session-ended', () => fetchData()),
+      // The backend has already shown its overlay; just note it and refresh
+      listen<EnforcementEntry>('enforcement', (event) => {
+        console.log(`Closed ${event.payload.game_name} (${event.payload.reason})`);
+        fetchData();
+      }),
+      // Everything shown belongs to the active profile, so reload it all
+      listen<Profile>('profile-changed', () => fetchData()),
+    ]);
+
+    return () => {
+      unlisten.then((unlisteners) => unlisteners.forEach((stop) => stop()));
+    };
   }, []);
 
   // Initialize notification permissions
@@ -380,18 +441,29 @@ function App() {
   // Debug functions
   const resetTodaySessions = async () => {
     try {
-      await invoke('reset_today_sessions');
+      await invoke('reset_today_sessions', { pin: parentPin });
       await fetchData(); // Refresh data
-      alert('✅ Today\'s sessions cleared!');
+      alert('✅ Today\'s sessions cleared! Use Undo Reset to bring them back.');
     } catch (error) {
       console.error('Failed to reset sessions:', error);
       alert('❌ Failed to reset sessions');
     }
   };
 
+  const undoResetToday = async () => {
+    try {
+      const restored = await invoke<number>('undo_reset_today', { pin: parentPin });
+      await fetchData(); // Refresh data
+      alert(`✅ Restored ${restored} sessions!`);
+    } catch (error) {
+      console.error('Failed to undo reset:', error);
+      alert(`❌ ${error}`);
+    }
+  };
+
   const addBudgetMinutes = async (minutes: number) => {
     try {
-      await invoke('add_budget_minutes', { minutes });
+      await invoke('add_budget_minutes', { minutes, pin: parentPin });
       await fetchData(); // Refresh data
       alert(`✅ Added ${minutes} minutes to budget!`);
     } catch (error) {
@@ -402,7 +474,7 @@ function App() {
 
   const removeBudgetMinutes = async (minutes: number) => {
     try {
-      await invoke('remove_budget_minutes', { minutes });
+      await invoke('remove_budget_minutes', { minutes, pin: parentPin });
       await fetchData(); // Refresh data
       alert(`✅ Removed ${minutes} minutes from budget!`);
     } catch (error) {
@@ -413,7 +485,7 @@ function App() {
 
   const addFakePlaytime = async (minutes: number) => {
     try {
-      await invoke('add_fake_playtime', { minutes });
+      await invoke('add_fake_playtime', { minutes, pin: parentPin });
       await fetchData(); // Refresh data
       alert(`✅ Added ${minutes} minutes of fake playtime!`);
     } catch (error) {
@@ -424,10 +496,13 @@ function App() {
 
   const closeAllGames = async () => {
     try {
-      const closedGames = await invoke<string[]>('close_all_games');
+      const result = await invoke<CloseResult>('close_all_games');
       await fetchData(); // Refresh data
-      if (closedGames.length > 0) {
-        alert(`✅ Closed games: ${closedGames.join(', ')}`);
+      const stillRunning = [...result.resisted, ...result.failed];
+      if (stillRunning.length > 0) {
+        alert(`⚠️ Closed: ${result.closed.join(', ') || 'none'}\nStill running: ${stillRunning.join(', ')}`);
+      } else if (result.closed.length > 0) {
+        alert(`✅ Closed games: ${result.closed.join(', ')}`);
       } else {
         alert('ℹ️ No games were running to close');
       }
@@ -648,6 +723,14 @@ function App() {
       <div className="fixed bottom-4 right-4 bg-gray-800 border border-gray-600 rounded-lg p-4 max-w-sm">
         <h3 className="text-sm font-bold mb-3 text-purple-400">🔧 Debug Panel</h3>
         <div className="space-y-2">
+          <input
+            type="password"
+            inputMode="numeric"
+            value={parentPin}
+            onChange={(e) => setParentPin(e.target.value)}
+            placeholder="Parent PIN"
+            className="w-full bg-gray-700 border border-gray-600 text-white text-sm py-2 px-3 rounded"
+          />
           <button
             onClick={testOverlay}
             className="w-full bg-purple-600 hover:bg-purple-700 text-white text-sm py-2 px-3 rounded transition-colors"
@@ -684,6 +767,12 @@ function App() {
           >
             🔄 Reset Today
           </button>
+          <button
+            onClick={undoResetToday}
+            className="w-full bg-gray-600 hover:bg-gray-700 text-white text-sm py-2 px-3 rounded transition-colors"
+          >
+            ↩️ Undo Reset
+          </button>
         </div>
       </div>
     </div>